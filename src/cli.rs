@@ -0,0 +1,483 @@
+//! =============================================================
+//! Rust Game Hosting Server - cli.rs
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Command line interface of the `web_server` binary
+//! =============================================================
+
+use crate::{
+    auth::{ApiTokens, PasswordFile},
+    config::{self, BackendConfig, Config, ServerConfig},
+    WebServer,
+};
+use std::{
+    collections::HashSet,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+/// Help text printed by `--help` and after usage errors
+pub const USAGE: &str = "\
+Usage: web_server [--config <path>] [<command>]
+
+Commands:
+  serve [--bind <ip>] [--port <port>]   Runs the web server (default)
+  check-config                          Validates the config file
+  list-servers                          Lists the configured servers
+  start <server>                        Starts a server without the web interface
+  stop <server>                         Stops a server without the web interface
+  status <server>                       Prints the status of a server as json,
+                                        not for servers with the `direct` backend
+  hash-password <user>                  Reads a password from stdin, prints a password file line
+  generate-token <user>                 Prints a new API token and its [[auth.tokens]] entry
+
+Options:
+  --config <path>   Config file, defaults to web_server.toml
+  --version         Prints the version
+  --help            Prints this text";
+
+/// Exit status of the binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Everything worked
+    Success = 0,
+    /// The requested operation failed
+    Failure = 1,
+    /// The command line couldn't be understood
+    Usage = 2,
+    /// The config file is missing or invalid
+    Config = 3,
+    /// The server named on the command line isn't configured
+    UnknownServer = 4,
+}
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        Self::from(exit as u8)
+    }
+}
+
+/// What the binary was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run the web server, optionally overriding the ip and port of every listen address
+    Serve {
+        /// Ip replacing the configured ones
+        bind: Option<IpAddr>,
+        /// Port replacing the configured ones
+        port: Option<u16>,
+    },
+    /// Load and validate the config file
+    CheckConfig,
+    /// Print the configured servers
+    ListServers,
+    /// Start the named server
+    Start(String),
+    /// Stop the named server
+    Stop(String),
+    /// Print the status of the named server
+    Status(String),
+    /// Print a password file line for the user, the password is read from stdin
+    HashPassword(String),
+    /// Print a new API token for the user
+    GenerateToken(String),
+    /// Print the version
+    Version,
+    /// Print [`USAGE`]
+    Help,
+}
+
+/// Command line that couldn't be understood
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageError(String);
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl std::error::Error for UsageError {}
+
+/// Parsed command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cli {
+    /// Config file to use
+    pub config: PathBuf,
+    /// What to do
+    pub command: Command,
+}
+
+impl Cli {
+    /// Parses the arguments, without the program name
+    ///
+    /// `--config` may come before or after the command
+    ///
+    /// # Errors
+    /// Returns a [`UsageError`] for unknown commands or options and missing values
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::cli::{Cli, Command};
+    ///
+    /// let cli = Cli::parse(["stop", "arma", "--config", "/etc/games.toml"].map(String::from)).unwrap();
+    ///
+    /// assert_eq!(cli.command, Command::Stop("arma".to_owned()));
+    /// assert_eq!(cli.config.to_str(), Some("/etc/games.toml"));
+    /// ```
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, UsageError> {
+        let mut config = PathBuf::from(config::DEFAULT_PATH);
+        let mut positional = Vec::new();
+        let mut bind = None;
+        let mut port = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |option: &str| {
+                args.next()
+                    .ok_or_else(|| UsageError(format!("{option} needs a value")))
+            };
+            match arg.as_str() {
+                "--config" => config = PathBuf::from(value("--config")?),
+                "--bind" => {
+                    let ip = value("--bind")?;
+                    bind =
+                        Some(ip.parse().map_err(|_| {
+                            UsageError(format!("--bind: {ip:?} isn't an ip address"))
+                        })?);
+                }
+                "--port" => {
+                    let number = value("--port")?;
+                    port = Some(
+                        number
+                            .parse()
+                            .map_err(|_| UsageError(format!("--port: {number:?} isn't a port")))?,
+                    );
+                }
+                "--version" | "-V" => positional.insert(0, "version".to_owned()),
+                "--help" | "-h" => positional.insert(0, "help".to_owned()),
+                option if option.starts_with('-') => {
+                    return Err(UsageError(format!("Unknown option: {option}")));
+                }
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let command = positional.next();
+        let mut server = |command: &str| {
+            positional
+                .next()
+                .ok_or_else(|| UsageError(format!("{command} needs the path of a server")))
+        };
+        let command = match command.as_deref() {
+            None | Some("serve") => Command::Serve { bind, port },
+            Some("check-config") => Command::CheckConfig,
+            Some("list-servers") => Command::ListServers,
+            Some("start") => Command::Start(server("start")?),
+            Some("stop") => Command::Stop(server("stop")?),
+            Some("status") => Command::Status(server("status")?),
+            Some("hash-password") => {
+                Command::HashPassword(user(positional.next(), "hash-password")?)
+            }
+            Some("generate-token") => {
+                Command::GenerateToken(user(positional.next(), "generate-token")?)
+            }
+            Some("version") => Command::Version,
+            Some("help") => Command::Help,
+            Some(unknown) => return Err(UsageError(format!("Unknown command: {unknown}"))),
+        };
+        if let Some(extra) = positional.next() {
+            return Err(UsageError(format!("Unexpected argument: {extra}")));
+        }
+        if !matches!(command, Command::Serve { .. }) && (bind.is_some() || port.is_some()) {
+            return Err(UsageError(
+                "--bind and --port only work with serve".to_owned(),
+            ));
+        }
+
+        Ok(Self { config, command })
+    }
+
+    /// Runs the command, printing results to stdout and problems to stderr
+    #[must_use]
+    pub fn run(self) -> Exit {
+        match self.command {
+            Command::Version => {
+                println!("web_server {}", env!("CARGO_PKG_VERSION"));
+                return Exit::Success;
+            }
+            Command::Help => {
+                println!("{USAGE}");
+                return Exit::Success;
+            }
+            Command::HashPassword(ref user) => return hash_password(user),
+            Command::GenerateToken(ref user) => return generate_token(user),
+            _ => {}
+        }
+
+        let mut config = match Config::load(&self.config) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("\x1b[31mInvalid config: {e}\x1b[39m");
+                return Exit::Config;
+            }
+        };
+
+        match self.command {
+            Command::Serve { bind, port } => {
+                override_listen(&mut config, bind, port);
+                WebServer::from_parsed_config(&config).run()
+            }
+            Command::CheckConfig => {
+                println!(
+                    "{}: ok, {} server(s)",
+                    self.config.display(),
+                    config.servers.len()
+                );
+                Exit::Success
+            }
+            Command::ListServers => {
+                for server in &config.servers {
+                    println!(
+                        "{}\t{}\t{}",
+                        server.path,
+                        server.kind,
+                        server.name.as_deref().unwrap_or(&server.path)
+                    );
+                }
+                Exit::Success
+            }
+            Command::Start(ref path) | Command::Stop(ref path) | Command::Status(ref path) => {
+                let Some(server_config) = config.servers.iter().find(|s| &s.path == path) else {
+                    eprintln!(
+                        "\x1b[31mUnknown server: {path}, see `web_server list-servers`\x1b[39m"
+                    );
+                    return Exit::UnknownServer;
+                };
+                run_locally(&self.command, server_config)
+            }
+            Command::Version
+            | Command::Help
+            | Command::HashPassword(_)
+            | Command::GenerateToken(_) => Exit::Success,
+        }
+    }
+}
+
+/// Returns the user name argument of `command`
+fn user(argument: Option<String>, command: &str) -> Result<String, UsageError> {
+    argument
+        .filter(|user| !user.is_empty() && !user.contains(':'))
+        .ok_or_else(|| UsageError(format!("{command} needs a user name without ':'")))
+}
+
+/// Reads a password from stdin and prints the password file line for `user`
+fn hash_password(user: &str) -> Exit {
+    eprintln!("Password for {user}:");
+    let mut password = String::new();
+    if let Err(e) = std::io::stdin().read_line(&mut password) {
+        eprintln!("\x1b[31mCouldn't read the password: {e}\x1b[39m");
+        return Exit::Failure;
+    }
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        eprintln!("\x1b[31mThe password can't be empty\x1b[39m");
+        return Exit::Failure;
+    }
+
+    match PasswordFile::hash_password(user, password) {
+        Ok(line) => {
+            println!("{line}");
+            Exit::Success
+        }
+        Err(e) => {
+            eprintln!("\x1b[31mCouldn't hash the password: {e}\x1b[39m");
+            Exit::Failure
+        }
+    }
+}
+
+/// Prints a new API token for `user` and the config entry accepting it
+fn generate_token(user: &str) -> Exit {
+    match ApiTokens::generate() {
+        Ok((token, sha256)) => {
+            println!("Token, send it as `Authorization: Bearer <token>`:\n{token}\n");
+            println!("Add to the config, after the [auth] table:");
+            println!("[[auth.tokens]]\nuser = {user:?}\nsha256 = \"{sha256}\"");
+            Exit::Success
+        }
+        Err(e) => {
+            eprintln!("\x1b[31mCouldn't generate a token: {e}\x1b[39m");
+            Exit::Failure
+        }
+    }
+}
+
+/// Replaces the ip and/or port of every configured listen address
+fn override_listen(config: &mut Config, bind: Option<IpAddr>, port: Option<u16>) {
+    for address in &mut config.listen {
+        // already validated by Config::load
+        let Ok(mut socket_address) = address.parse::<SocketAddr>() else {
+            continue;
+        };
+        if let Some(bind) = bind {
+            socket_address.set_ip(bind);
+        }
+        if let Some(port) = port {
+            socket_address.set_port(port);
+        }
+        *address = socket_address.to_string();
+    }
+    let mut seen = HashSet::new();
+    config.listen.retain(|address| seen.insert(address.clone()));
+}
+
+/// Runs start, stop or status on a server without going through HTTP
+///
+/// A `direct` process is a child of whoever started it, so it would die with this
+/// command or be unknown to it
+fn run_locally(command: &Command, server_config: &ServerConfig) -> Exit {
+    if matches!(server_config.backend, Some(BackendConfig::Direct { .. })) {
+        eprintln!(
+            "\x1b[31m{}: the `direct` backend only works in the web server, use its web interface\x1b[39m",
+            server_config.path
+        );
+        return Exit::Failure;
+    }
+    let mut server = server_config.build();
+    let result = match command {
+        Command::Start(_) => server.start(),
+        Command::Stop(_) => server.stop(),
+        _ => server.update_status(),
+    };
+    if let Err(e) = result {
+        eprintln!("\x1b[31m{}: {e}\x1b[39m", server.get_path());
+        return Exit::Failure;
+    }
+
+    match server.to_json() {
+        Ok(json) => {
+            println!("{json}");
+            Exit::Success
+        }
+        Err(e) => {
+            eprintln!("\x1b[31m{}: {e}\x1b[39m", server.get_path());
+            Exit::Failure
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{override_listen, run_locally, Cli, Command, Exit, UsageError};
+    use crate::config::Config;
+    use std::{fs, path::Path};
+
+    /// Parses `args` like the binary does
+    fn parse(args: &[&str]) -> Result<Cli, UsageError> {
+        Cli::parse(args.iter().map(|&arg| arg.to_owned()))
+    }
+
+    /// Returns the message of the [`UsageError`] `args` cause
+    fn usage_error(args: &[&str]) -> String {
+        parse(args).unwrap_err().to_string()
+    }
+
+    /// Parses `text` as a config in the empty directory `web_server_cli_{name}`
+    fn config(name: &str, text: &str) -> Config {
+        let directory = std::env::temp_dir().join(format!("web_server_cli_{name}"));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("static")).unwrap();
+        fs::create_dir_all(directory.join("game")).unwrap();
+        Config::parse(text, &directory.join("web_server.toml"), &directory).unwrap()
+    }
+
+    #[test]
+    fn serve_is_the_default_command() {
+        let cli = parse(&[]).unwrap();
+        assert_eq!(
+            cli.command,
+            Command::Serve {
+                bind: None,
+                port: None
+            }
+        );
+        assert_eq!(cli.config, Path::new("web_server.toml"));
+
+        let cli = parse(&["--port", "8080", "--bind", "::1"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Command::Serve {
+                bind: Some("::1".parse().unwrap()),
+                port: Some(8080)
+            }
+        );
+    }
+
+    #[test]
+    fn commands_take_their_argument() {
+        let cli = parse(&["--config", "games.toml", "status", "arma"]).unwrap();
+        assert_eq!(cli.command, Command::Status("arma".to_owned()));
+        assert_eq!(cli.config, Path::new("games.toml"));
+        assert_eq!(
+            parse(&["hash-password", "alex"]).unwrap().command,
+            Command::HashPassword("alex".to_owned())
+        );
+        assert_eq!(
+            parse(&["list-servers"]).unwrap().command,
+            Command::ListServers
+        );
+        assert_eq!(parse(&["--help"]).unwrap().command, Command::Help);
+        assert_eq!(parse(&["-V"]).unwrap().command, Command::Version);
+    }
+
+    #[test]
+    fn bad_command_lines_are_usage_errors() {
+        assert_eq!(usage_error(&["--config"]), "--config needs a value");
+        assert_eq!(
+            usage_error(&["--port", "65536"]),
+            "--port: \"65536\" isn't a port"
+        );
+        assert_eq!(
+            usage_error(&["--bind", "localhost"]),
+            "--bind: \"localhost\" isn't an ip address"
+        );
+        assert_eq!(usage_error(&["--verbose"]), "Unknown option: --verbose");
+        assert_eq!(usage_error(&["restart"]), "Unknown command: restart");
+        assert_eq!(usage_error(&["start"]), "start needs the path of a server");
+        assert_eq!(usage_error(&["stop", "a", "b"]), "Unexpected argument: b");
+        assert_eq!(
+            usage_error(&["generate-token", "a:b"]),
+            "generate-token needs a user name without ':'"
+        );
+        assert_eq!(
+            usage_error(&["status", "arma", "--port", "80"]),
+            "--bind and --port only work with serve"
+        );
+    }
+
+    #[test]
+    fn overridden_listen_addresses_are_unique() {
+        let mut config = config(
+            "listen",
+            "listen = [\"127.0.0.1:80\", \"127.0.0.2:80\", \"127.0.0.1:81\"]",
+        );
+
+        override_listen(&mut config, None, Some(8080));
+        assert_eq!(config.listen, ["127.0.0.1:8080", "127.0.0.2:8080"]);
+        override_listen(&mut config, Some("0.0.0.0".parse().unwrap()), None);
+        assert_eq!(config.listen, ["0.0.0.0:8080"]);
+    }
+
+    #[test]
+    fn direct_servers_are_not_run_locally() {
+        let config = config(
+            "direct",
+            "listen = [\"127.0.0.1:31415\"]\n[[servers]]\nkind = \"bash\"\npath = \"game\"\n\
+             [servers.backend]\nkind = \"direct\"\ncommand = [\"sleep\", \"30\"]",
+        );
+
+        let exit = run_locally(&Command::Start("game".to_owned()), &config.servers[0]);
+        assert_eq!(exit, Exit::Failure);
+    }
+}
@@ -0,0 +1,1649 @@
+//! =============================================================
+//! Rust Game Hosting Server - config.rs
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Loads and validates the TOML file describing a deployment
+//! =============================================================
+//!
+//! ```toml
+//! listen = ["192.168.11.69:31415"]
+//! static_root = "static"
+//!
+//! [idle]
+//! shutdown = true
+//! after_minutes = 30
+//!
+//! [workers]
+//! threads = 8
+//! queue = 64
+//!
+//! [auth]
+//! password_file = "users.passwd"
+//! secure_cookie = true
+//! session_minutes = 720
+//! tokens = [{ user = "backup-script", sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08" }]
+//!
+//! [auth.roles.admin]
+//! "*" = ["view", "start", "stop", "restart", "console", "configure", "power"]
+//!
+//! [auth.roles.friends]
+//! minecraft = ["view", "start", "stop", "restart"]
+//!
+//! [auth.users]
+//! nacor = ["admin"]
+//! backup-script = ["friends"]
+//!
+//! [[servers]]
+//! kind = "minecraft"
+//! path = "minecraft"
+//! name = "Minecraft"
+//! working_dir = "/home/nacor/minecraft"
+//! log_file = "/home/nacor/minecraft/screenlog.0"
+//! properties = "/home/nacor/minecraft/server.properties"
+//! rcon = { address = "127.0.0.1:25575", password = "hunter2" }
+//! ping = "127.0.0.1:25565"
+//! query = "127.0.0.1:25565"
+//! status = "query"
+//! scripts = { start = "minecraft/start.sh", stop = "minecraft/stop.sh", status = "minecraft/status.sh" }
+//! timeouts = { start = 120, stop = 120, status = 15, ready = 300 }
+//! watchdog = { restart = "on-failure", max_retries = 5, backoff = 10, max_backoff = 600, unhealthy = 300 }
+//! probes = [
+//!     { kind = "tcp", address = "127.0.0.1:25565" },
+//!     { kind = "log", file = "/home/nacor/minecraft/logs/latest.log", pattern = 'Done \(.*\)! For help' },
+//! ]
+//!
+//! [[servers]]
+//! kind = "arma"
+//! path = "arma"
+//! name = "Arma 3"
+//! working_dir = "/home/nacor/Steam/arma3"
+//! query = "127.0.0.1:2303"
+//! backend = { kind = "screen" }
+//!
+//! [servers.arma]
+//! hostname = "Nacor's Wasteland"
+//! max_players = 40
+//! missions = ["Wasteland.Altis"]
+//! preset = "/home/nacor/Steam/arma3/wasteland.html"
+//! mods = ["CBA_A3"]
+//!
+//! [[servers]]
+//! kind = "bash"
+//! path = "terraria"
+//! name = "Terraria"
+//! working_dir = "/home/nacor/terraria"
+//!
+//! [servers.backend]
+//! kind = "direct"
+//! command = ["./TerrariaServer", "-config", "serverconfig.txt"]
+//! pty = true
+//! stop_command = "exit"
+//! env = { TERM = "xterm" }
+//! log_file = "/home/nacor/terraria/console.log"
+//! ```
+//!
+//! Relative paths are relative to the directory of the config file.
+
+use crate::auth::{ApiTokens, Auth, PasswordFile, Permission, Roles};
+use crate::hostable_servers::{
+    arma::{self, preset, setup::Setup},
+    backend::{self, ProcessBackend},
+    minecraft::{self, access::MojangApi, StatusSource},
+    probe::Probe,
+    process::CancelToken,
+    rcon::RconLogin,
+    supervisor::Supervisor,
+    watchdog::{RestartPolicy, WatchdogSettings},
+    GeneralBashServer, HostableServer, Scripts, Settings, Timeouts,
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Config file used when none is given
+pub const DEFAULT_PATH: &str = "web_server.toml";
+
+/// First path segments that are taken by the web server itself
+const RESERVED_PATHS: [&str; 10] = [
+    "file",
+    "favicon.ico",
+    "available-servers",
+    "Shutdown",
+    "Ping",
+    "hello.html",
+    "login",
+    "logout",
+    "whoami",
+    "events",
+];
+
+/// Everything that can be wrong with a config file
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file couldn't be read
+    Io {
+        /// Config file
+        path: PathBuf,
+        /// Underlying error
+        source: std::io::Error,
+    },
+    /// The file isn't valid TOML or doesn't have the expected shape
+    Parse {
+        /// Config file
+        path: PathBuf,
+        /// Parser message, including line and column
+        message: String,
+    },
+    /// A value is well formed but not usable
+    Invalid {
+        /// Config file
+        path: PathBuf,
+        /// Dotted name of the offending key, like `servers[1].path`
+        field: String,
+        /// What is wrong with it
+        reason: String,
+    },
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Parse { path, message } => write!(f, "{}: {message}", path.display()),
+            Self::Invalid {
+                path,
+                field,
+                reason,
+            } => write!(f, "{}: {field}: {reason}", path.display()),
+        }
+    }
+}
+impl std::error::Error for ConfigError {}
+
+/// Configuration of a whole [`crate::WebServer`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Addresses to listen on, `ip:port`
+    pub listen: Vec<String>,
+    /// Directory `hello.html`, `/file/<path>` and `<server>/update.js` are served from,
+    /// without logging in. Defaults to `static`, it can't hold the config file, the
+    /// password file or the files of a server
+    #[serde(default = "default_static_root")]
+    pub static_root: PathBuf,
+    /// What to do when nobody uses the web server
+    #[serde(default)]
+    pub idle: IdleConfig,
+    /// Connection handling threads
+    #[serde(default)]
+    pub workers: WorkersConfig,
+    /// Who may use the POST routes, nobody if missing
+    pub auth: Option<AuthConfig>,
+    /// Hosted game servers
+    #[serde(default)]
+    pub servers: Vec<ServerConfig>,
+}
+
+/// Dotted name of an invalid key and what is wrong with it
+struct Invalid(String, String);
+impl Invalid {
+    /// Shorthand for keys and reasons that don't need formatting
+    fn new(field: &str, reason: &str) -> Self {
+        Self(field.to_owned(), reason.to_owned())
+    }
+}
+
+/// Authentication, see [`crate::auth`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// `user:hash` lines for HTTP Basic and `/login`, see [`PasswordFile`], outside of
+    /// [`Config::static_root`]
+    pub password_file: Option<PathBuf>,
+    /// Whether the session cookie is only sent over https
+    #[serde(default = "default_true")]
+    pub secure_cookie: bool,
+    /// Minutes a login lasts
+    #[serde(default = "default_session_minutes")]
+    pub session_minutes: u64,
+    /// Bearer tokens for scripts
+    #[serde(default)]
+    pub tokens: Vec<TokenConfig>,
+    /// Permissions per server path, `"*"` for every server, by role name
+    #[serde(default)]
+    pub roles: BTreeMap<String, BTreeMap<String, Vec<Permission>>>,
+    /// Roles of every user, without any entry every user may do everything
+    #[serde(default)]
+    pub users: BTreeMap<String, Vec<String>>,
+    /// Content of `password_file`, read while loading the config
+    #[serde(skip)]
+    pub passwords: Option<PasswordFile>,
+}
+impl AuthConfig {
+    /// Creates the [`Auth`] described by `self`
+    #[must_use]
+    pub fn build(&self) -> Auth {
+        let mut auth = Auth::new(
+            Duration::from_mins(self.session_minutes),
+            self.secure_cookie,
+        );
+        if let Some(passwords) = &self.passwords {
+            auth.set_password_file(passwords.clone());
+        }
+        if !self.tokens.is_empty() {
+            let mut tokens = ApiTokens::new();
+            for token in &self.tokens {
+                tokens.add_hashed(&token.user, &token.sha256);
+            }
+            auth.add_authenticator(Box::new(tokens));
+        }
+        if !self.users.is_empty() {
+            let mut roles = Roles::new();
+            for (role, servers) in &self.roles {
+                for (server_path, permissions) in servers {
+                    roles.grant(role, server_path, permissions.iter().copied());
+                }
+            }
+            for (user, user_roles) in &self.users {
+                for role in user_roles {
+                    roles.assign(user, role);
+                }
+            }
+            auth.set_roles(roles);
+        }
+        auth
+    }
+}
+
+impl AuthConfig {
+    /// Checks the values of the `[auth]` table, `servers` are the configured server paths
+    fn check(&self, servers: &HashSet<&str>) -> Result<(), Invalid> {
+        if self.session_minutes == 0 {
+            return Err(Invalid::new("auth.session_minutes", "has to be at least 1"));
+        }
+        for (i, token) in self.tokens.iter().enumerate() {
+            if token.sha256.len() != 64 || !token.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Invalid(
+                    format!("auth.tokens[{i}].sha256"),
+                    "has to be 64 hex digits".to_owned(),
+                ));
+            }
+        }
+
+        for (role, grants) in &self.roles {
+            for (server_path, permissions) in grants {
+                if server_path == Roles::EVERY_SERVER {
+                    continue;
+                }
+                if !servers.contains(server_path.as_str()) {
+                    return Err(Invalid(
+                        format!("auth.roles.{role}.{server_path}"),
+                        "isn't the path of a configured server".to_owned(),
+                    ));
+                }
+                if permissions.contains(&Permission::Power) {
+                    return Err(Invalid(
+                        format!("auth.roles.{role}.{server_path}"),
+                        format!(
+                            "\"power\" shuts down the whole machine, grant it on \"{}\"",
+                            Roles::EVERY_SERVER
+                        ),
+                    ));
+                }
+            }
+        }
+        for (user, roles) in &self.users {
+            if let Some(role) = roles.iter().find(|role| !self.roles.contains_key(*role)) {
+                return Err(Invalid(
+                    format!("auth.users.{user}"),
+                    format!("{role:?} isn't a role in auth.roles"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bearer token, create one with `web_server generate-token <user>`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    /// User the token acts as
+    pub user: String,
+    /// Hex SHA-256 of the token
+    pub sha256: String,
+}
+
+/// Idle policy, the machine is shut down after a while without requests
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IdleConfig {
+    /// Whether to shut down at all
+    #[serde(default = "default_true")]
+    pub shutdown: bool,
+    /// Minutes without a single request before shutting down
+    #[serde(default = "default_idle_minutes")]
+    pub after_minutes: u64,
+}
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            shutdown: true,
+            after_minutes: default_idle_minutes(),
+        }
+    }
+}
+impl IdleConfig {
+    /// Returns the idle time after which to shut down, `None` to never shut down
+    #[must_use]
+    pub const fn timeout(&self) -> Option<Duration> {
+        if self.shutdown {
+            Some(Duration::from_mins(self.after_minutes))
+        } else {
+            None
+        }
+    }
+}
+
+/// Size of the worker pool, see [`crate::WebServer::set_worker_pool`]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkersConfig {
+    /// Number of threads
+    #[serde(default = "default_threads")]
+    pub threads: usize,
+    /// Connections that may wait for a free thread
+    #[serde(default = "default_queue")]
+    pub queue: usize,
+}
+impl Default for WorkersConfig {
+    fn default() -> Self {
+        Self {
+            threads: default_threads(),
+            queue: default_queue(),
+        }
+    }
+}
+
+/// Implementation used for a hosted server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerKind {
+    /// [`GeneralBashServer`]
+    Bash,
+    /// [`minecraft::Server`]
+    Minecraft,
+    /// [`arma::Server`]
+    Arma,
+}
+impl fmt::Display for ServerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bash => "bash",
+            Self::Minecraft => "minecraft",
+            Self::Arma => "arma",
+        })
+    }
+}
+
+/// One hosted server
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Implementation to use
+    pub kind: ServerKind,
+    /// Identifier used in links and for the screen session
+    pub path: String,
+    /// Name shown to people, defaults to `path`
+    pub name: Option<String>,
+    /// Directory the scripts run in, defaults to the directory of the config file
+    pub working_dir: Option<PathBuf>,
+    /// Lifecycle scripts, default to `{path}/start.sh`, ...
+    #[serde(default)]
+    pub scripts: ScriptsConfig,
+    /// Seconds every script may run before it is killed
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
+    /// Readiness probes, see [`Probe`]
+    #[serde(default)]
+    pub probes: Vec<Probe>,
+    /// Crash detection and restarts
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    /// Screen log of a `minecraft` server, defaults to `Minecraft/screenlog.0`
+    pub log_file: Option<PathBuf>,
+    /// `server.properties` of a `minecraft` server, defaults to `Minecraft/server.properties`
+    pub properties: Option<PathBuf>,
+    /// RCON interface of a `minecraft` or `bash` server, for admin commands and
+    /// used instead of the status script of a `minecraft` server
+    pub rcon: Option<RconConfig>,
+    /// `host:port` a `minecraft` server answers the Server List Ping on, its game port
+    pub ping: Option<String>,
+    /// `host:port` of the UDP Query of a `minecraft` server, `query.port` in `server.properties`,
+    /// or of the Steam query port of an `arma` server, the game port + 1
+    pub query: Option<String>,
+    /// Where a `minecraft` server gets its players from, defaults to the first of `rcon`,
+    /// `query` and `ping` that is set, the log without any
+    pub status: Option<StatusSource>,
+    /// What runs the process of the server, its scripts by default
+    pub backend: Option<BackendConfig>,
+    /// `server.cfg`, mods and command line of an `arma` server
+    pub arma: Option<ArmaConfig>,
+}
+
+/// [`ProcessBackend`] of a [`ServerConfig`], a table with a `kind`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum BackendConfig {
+    /// [`backend::Scripts`], the start and stop scripts
+    Scripts,
+    /// [`backend::Screen`], `command` in the screen session `{path}_server`
+    Screen {
+        /// Program followed by its arguments, run in the working directory,
+        /// built from the `arma` table of an `arma` server
+        #[serde(default)]
+        command: Vec<String>,
+        /// Console line stopping the server, the session is quit without one
+        stop_command: Option<String>,
+        /// File screen logs the session to
+        log_file: Option<PathBuf>,
+    },
+    /// [`backend::Tmux`], `command` in the tmux session `{path}_server`
+    Tmux {
+        /// Program followed by its arguments, run in the working directory,
+        /// built from the `arma` table of an `arma` server
+        #[serde(default)]
+        command: Vec<String>,
+        /// Console line stopping the server, the session is killed without one
+        stop_command: Option<String>,
+    },
+    /// [`backend::Systemd`], a `systemctl --user` unit
+    Systemd {
+        /// Name of the unit, defaults to `{path}.service`
+        unit: Option<String>,
+    },
+    /// [`Supervisor`], spawned by the web server itself
+    Direct {
+        /// Program followed by its arguments, run in the working directory,
+        /// built from the `arma` table of an `arma` server
+        #[serde(default)]
+        command: Vec<String>,
+        /// Whether the process runs under a pseudo terminal
+        #[serde(default)]
+        pty: bool,
+        /// Console line stopping the server, it gets SIGTERM without one
+        stop_command: Option<String>,
+        /// Environment variables added to the inherited ones
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        /// File the output is copied to
+        log_file: Option<PathBuf>,
+    },
+}
+
+/// Source RCON interface of a [`ServerConfig`], `enable-rcon=true` in `server.properties`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RconConfig {
+    /// `host:port`, the port is `rcon.port` in `server.properties`
+    pub address: String,
+    /// `rcon.password` in `server.properties`
+    pub password: String,
+    /// Commands stopping a `bash` server, like `["save", "quit"]`, its backend stops it without
+    #[serde(default)]
+    pub stop: Vec<String>,
+}
+
+/// `server.cfg` and command line of an `arma` [`ServerConfig`], see [`Setup`]
+///
+/// Paths other than `preset` are relative to the working directory, Arma runs in there
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArmaConfig {
+    /// Name in the server browser, defaults to the name of the server
+    pub hostname: Option<String>,
+    /// Password to join, anybody may join without one
+    pub password: Option<String>,
+    /// Player slots, defaults to 32
+    pub max_players: Option<u32>,
+    /// Mission rotation, like `["Wasteland.Altis"]`, from the `mpmissions` directory
+    #[serde(default)]
+    pub missions: Vec<String>,
+    /// `Recruit`, `Regular`, `Veteran` or `Custom`, defaults to `Regular`
+    pub difficulty: Option<String>,
+    /// Lines added to the `server.cfg` as they are, like `passwordAdmin = "hunter2";`
+    #[serde(default)]
+    pub extra: Vec<String>,
+    /// Where the `server.cfg` is written, defaults to `server.cfg`
+    pub config_file: Option<PathBuf>,
+    /// Server program, defaults to `./arma3server_x64`
+    pub executable: Option<String>,
+    /// Profile the server saves its settings under, defaults to `server`
+    pub profile: Option<String>,
+    /// Game port, defaults to 2302
+    pub port: Option<u16>,
+    /// Mod preset exported by the Arma 3 Launcher, an HTML file
+    pub preset: Option<PathBuf>,
+    /// Names of the mods of the preset that are loaded, all of them if missing
+    pub mods: Option<Vec<String>>,
+    /// Where steamcmd downloads the mods to, defaults to `steamapps/workshop/content/107410`
+    pub workshop_dir: Option<PathBuf>,
+    /// More parameters of the server, like `["-autoInit"]`
+    #[serde(default)]
+    pub parameters: Vec<String>,
+}
+impl ArmaConfig {
+    /// Difficulties Arma knows
+    const DIFFICULTIES: [&str; 4] = ["Recruit", "Regular", "Veteran", "Custom"];
+
+    /// Returns the [`Setup`] with the defaults filled in, `name` is the default hostname
+    #[must_use]
+    pub fn setup(&self, name: &str) -> Setup {
+        let defaults = Setup::new(self.hostname.as_deref().unwrap_or(name));
+        let server_cfg = defaults.server_cfg;
+        Setup {
+            server_cfg: arma::setup::ServerCfg {
+                password: self.password.clone(),
+                max_players: self.max_players.unwrap_or(server_cfg.max_players),
+                missions: self.missions.clone(),
+                difficulty: self.difficulty.clone().unwrap_or(server_cfg.difficulty),
+                extra: self.extra.clone(),
+                ..server_cfg
+            },
+            config_file: self.config_file.clone().unwrap_or(defaults.config_file),
+            executable: self.executable.clone().unwrap_or(defaults.executable),
+            profile: self.profile.clone().unwrap_or(defaults.profile),
+            port: self.port.unwrap_or(defaults.port),
+            preset: self.preset.clone(),
+            mods: self.mods.clone(),
+            workshop_dir: self.workshop_dir.clone().unwrap_or(defaults.workshop_dir),
+            parameters: self.parameters.clone(),
+        }
+    }
+
+    /// Checks the values of the `arma` table, the field names are relative to it
+    fn check(&self) -> Result<(), Invalid> {
+        let texts = [
+            ("hostname", self.hostname.as_ref()),
+            ("password", self.password.as_ref()),
+            ("difficulty", self.difficulty.as_ref()),
+        ];
+        let missions = self
+            .missions
+            .iter()
+            .map(|mission| ("missions", Some(mission)));
+        for (name, text) in texts.into_iter().chain(missions) {
+            if text.is_some_and(|text| text.contains(['\n', '\r'])) {
+                return Err(Invalid::new(name, "has to fit on one line"));
+            }
+        }
+        if self.max_players == Some(0) {
+            return Err(Invalid::new("max_players", "has to be at least 1"));
+        }
+        if matches!(self.port, Some(0 | u16::MAX)) {
+            return Err(Invalid::new(
+                "port",
+                "has to be between 1 and 65534, the query port is the one after it",
+            ));
+        }
+        if let Some(difficulty) = &self.difficulty {
+            if !Self::DIFFICULTIES.contains(&difficulty.as_str()) {
+                return Err(Invalid(
+                    "difficulty".to_owned(),
+                    format!(
+                        "{difficulty:?} isn't one of {}",
+                        Self::DIFFICULTIES.join(", ")
+                    ),
+                ));
+            }
+        }
+        if self.executable.as_deref() == Some("") {
+            return Err(Invalid::new("executable", "can't be empty"));
+        }
+
+        let mods = match (&self.preset, &self.mods) {
+            (None, None) => return Ok(()),
+            (None, Some(_)) => return Err(Invalid::new("mods", "needs a `preset` to pick from")),
+            (Some(path), mods) => preset::load(path)
+                .map(|available| (available, mods))
+                .map_err(|e| Invalid("preset".to_owned(), e.to_string()))?,
+        };
+        if let (available, Some(mods)) = mods {
+            if let Some(missing) = mods
+                .iter()
+                .find(|name| !available.iter().any(|item| &item.name == *name))
+            {
+                return Err(Invalid(
+                    "mods".to_owned(),
+                    format!("{missing:?} isn't a Workshop mod of the preset"),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Script overrides of a [`ServerConfig`]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptsConfig {
+    /// Start script
+    pub start: Option<PathBuf>,
+    /// Stop script
+    pub stop: Option<PathBuf>,
+    /// Status script
+    pub status: Option<PathBuf>,
+}
+
+/// Timeout overrides of a [`ServerConfig`], in seconds
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeoutsConfig {
+    /// Start script, defaults to 120
+    pub start: Option<u64>,
+    /// Stop script, defaults to 120
+    pub stop: Option<u64>,
+    /// Status script, defaults to 15
+    pub status: Option<u64>,
+    /// Time a started server has to become ready, defaults to 300
+    pub ready: Option<u64>,
+}
+impl TimeoutsConfig {
+    /// Returns the [`Timeouts`] with the defaults filled in
+    #[must_use]
+    pub fn timeouts(&self) -> Timeouts {
+        let defaults = Timeouts::default();
+        let seconds = |value: Option<u64>, default| value.map_or(default, Duration::from_secs);
+        Timeouts {
+            start: seconds(self.start, defaults.start),
+            stop: seconds(self.stop, defaults.stop),
+            status: seconds(self.status, defaults.status),
+            ready: seconds(self.ready, defaults.ready),
+        }
+    }
+}
+
+/// Watchdog of a [`ServerConfig`], times in seconds
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchdogConfig {
+    /// Time between two status checks, defaults to 15
+    pub interval: Option<u64>,
+    /// `never` (default), `on-failure` or `always`
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    /// Restarts in a row before giving up, defaults to 5
+    pub max_retries: Option<u32>,
+    /// Wait before the first restart, doubled for every further one, defaults to 10
+    pub backoff: Option<u64>,
+    /// Longest wait before a restart, defaults to 600
+    pub max_backoff: Option<u64>,
+    /// Time a server may be unhealthy before it counts as crashed, forever if missing
+    pub unhealthy: Option<u64>,
+    /// Lines of output kept with every crash, defaults to 50
+    pub log_lines: Option<usize>,
+}
+impl WatchdogConfig {
+    /// Returns the [`WatchdogSettings`] with the defaults filled in
+    #[must_use]
+    pub fn settings(&self) -> WatchdogSettings {
+        let defaults = WatchdogSettings::default();
+        let seconds = |value: Option<u64>, default| value.map_or(default, Duration::from_secs);
+        WatchdogSettings {
+            interval: seconds(self.interval, defaults.interval),
+            restart: self.restart,
+            max_retries: self.max_retries.unwrap_or(defaults.max_retries),
+            backoff: seconds(self.backoff, defaults.backoff),
+            max_backoff: seconds(self.max_backoff, defaults.max_backoff),
+            unhealthy: self.unhealthy.map(Duration::from_secs),
+            log_lines: self.log_lines.unwrap_or(defaults.log_lines),
+        }
+    }
+}
+
+/// Default for [`Config::static_root`]
+fn default_static_root() -> PathBuf {
+    PathBuf::from("static")
+}
+/// Serde can't default to `true` on its own
+const fn default_true() -> bool {
+    true
+}
+/// Default for [`IdleConfig::after_minutes`]
+const fn default_idle_minutes() -> u64 {
+    30
+}
+/// Default for [`AuthConfig::session_minutes`]
+const fn default_session_minutes() -> u64 {
+    12 * 60
+}
+/// Default for [`WorkersConfig::threads`]
+const fn default_threads() -> usize {
+    8
+}
+/// Default for [`WorkersConfig::queue`]
+const fn default_queue() -> usize {
+    64
+}
+
+impl Config {
+    /// Reads, parses and validates the config file at `path`
+    ///
+    /// Relative paths inside of it are resolved against its directory
+    ///
+    /// # Errors
+    /// Returns a [`ConfigError`] naming the file, and the key or line that is wrong
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let base_dir = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        Self::parse(&text, path, base_dir)
+    }
+
+    /// Parses and validates `text`, `path` is only used for error messages
+    ///
+    /// # Errors
+    /// Returns a [`ConfigError`] naming the key or line that is wrong
+    ///
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use web_server::config::Config;
+    ///
+    /// let error = Config::parse("listen = [\"nowhere\"]", Path::new("test.toml"), Path::new("."))
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "test.toml: listen[0]: \"nowhere\" isn't an `ip:port` address"
+    /// );
+    /// ```
+    pub fn parse(text: &str, path: &Path, base_dir: &Path) -> Result<Self, ConfigError> {
+        let mut config: Self = toml::from_str(text).map_err(|e| ConfigError::Parse {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+        config.resolve_paths(base_dir);
+        config.validate(path)?;
+        config.load_password_file(path)?;
+        Ok(config)
+    }
+
+    /// Makes every relative path relative to `base_dir` and fills in the defaults
+    fn resolve_paths(&mut self, base_dir: &Path) {
+        // scripts run in their working directory, so they have to be absolute
+        let base_dir = std::path::absolute(base_dir).unwrap_or_else(|_| base_dir.to_owned());
+        let resolve = |path: &Path| {
+            if path.is_absolute() {
+                path.to_owned()
+            } else {
+                base_dir.join(path)
+            }
+        };
+
+        self.static_root = resolve(&self.static_root);
+        if let Some(auth) = &mut self.auth {
+            auth.password_file = auth.password_file.as_deref().map(resolve);
+        }
+        for server in &mut self.servers {
+            server.working_dir = Some(resolve(
+                server
+                    .working_dir
+                    .as_deref()
+                    .unwrap_or_else(|| Path::new("")),
+            ));
+
+            match &mut server.backend {
+                None | Some(BackendConfig::Scripts) => Self::resolve_scripts(server, &resolve),
+                // no scripts, only a command
+                Some(
+                    BackendConfig::Screen { log_file, .. } | BackendConfig::Direct { log_file, .. },
+                ) => *log_file = log_file.as_deref().map(resolve),
+                Some(BackendConfig::Tmux { .. } | BackendConfig::Systemd { .. }) => {}
+            }
+            if server.kind == ServerKind::Minecraft {
+                Self::resolve_minecraft(server, &resolve);
+            }
+
+            if let Some(arma) = &mut server.arma {
+                arma.preset = arma.preset.as_deref().map(resolve);
+            }
+
+            for probe in &mut server.probes {
+                match probe {
+                    Probe::Log { file, .. } => *file = resolve(file),
+                    Probe::Command { working_dir, .. } => {
+                        working_dir.clone_from(&server.working_dir);
+                    }
+                    Probe::Tcp { .. } | Probe::Udp { .. } => {}
+                }
+            }
+        }
+    }
+
+    /// Fills in the default scripts of `server`, and the status script of a `minecraft` server
+    fn resolve_scripts(server: &mut ServerConfig, resolve: &impl Fn(&Path) -> PathBuf) {
+        let directory = Path::new(&server.path);
+        let scripts = &mut server.scripts;
+        scripts.start = Some(resolve(
+            scripts
+                .start
+                .as_deref()
+                .unwrap_or(&directory.join("start.sh")),
+        ));
+        scripts.stop = Some(resolve(
+            scripts
+                .stop
+                .as_deref()
+                .unwrap_or(&directory.join("stop.sh")),
+        ));
+        if server.kind == ServerKind::Minecraft {
+            scripts.status = Some(resolve(
+                scripts
+                    .status
+                    .as_deref()
+                    .unwrap_or(&directory.join("status.sh")),
+            ));
+        } else {
+            scripts.status = scripts.status.as_deref().map(resolve);
+        }
+    }
+
+    /// Fills in the default log and properties files of a `minecraft` server, the log of
+    /// its backend if it has one, after the backend is resolved
+    fn resolve_minecraft(server: &mut ServerConfig, resolve: &impl Fn(&Path) -> PathBuf) {
+        let backend_log = match &server.backend {
+            Some(
+                BackendConfig::Screen { log_file, .. } | BackendConfig::Direct { log_file, .. },
+            ) => log_file.clone(),
+            _ => None,
+        };
+        server.log_file = Some(resolve(
+            server
+                .log_file
+                .as_deref()
+                .or(backend_log.as_deref())
+                .unwrap_or_else(|| Path::new("Minecraft/screenlog.0")),
+        ));
+        server.properties = Some(resolve(
+            server
+                .properties
+                .as_deref()
+                .unwrap_or_else(|| Path::new("Minecraft/server.properties")),
+        ));
+    }
+
+    /// Checks everything serde can't
+    fn validate(&self, path: &Path) -> Result<(), ConfigError> {
+        self.check()
+            .and_then(|()| self.check_static_root(path))
+            .map_err(|Invalid(field, reason)| ConfigError::Invalid {
+                path: path.to_owned(),
+                field,
+                reason,
+            })
+    }
+
+    /// [`Config::validate`] without the file name
+    fn check(&self) -> Result<(), Invalid> {
+        if self.listen.is_empty() {
+            return Err(Invalid::new("listen", "at least one address is needed"));
+        }
+        for (i, address) in self.listen.iter().enumerate() {
+            if address.parse::<SocketAddr>().is_err() {
+                return Err(Invalid(
+                    format!("listen[{i}]"),
+                    format!("{address:?} isn't an `ip:port` address"),
+                ));
+            }
+        }
+
+        if !self.static_root.is_dir() {
+            return Err(Invalid::new(
+                "static_root",
+                &format!("{} isn't a directory", self.static_root.display()),
+            ));
+        }
+        if self.idle.shutdown && self.idle.after_minutes == 0 {
+            return Err(Invalid::new("idle.after_minutes", "has to be at least 1"));
+        }
+        if self.workers.threads == 0 {
+            return Err(Invalid::new("workers.threads", "has to be at least 1"));
+        }
+        let mut paths = HashSet::new();
+        for (i, server) in self.servers.iter().enumerate() {
+            if !paths.insert(server.path.as_str()) {
+                return Err(Invalid(
+                    format!("servers[{i}].path"),
+                    format!("{:?} is used by another server", server.path),
+                ));
+            }
+            server.check().map_err(|Invalid(field, reason)| {
+                Invalid(format!("servers[{i}].{field}"), reason)
+            })?;
+        }
+
+        if let Some(auth) = &self.auth {
+            auth.check(&paths)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that nothing secret is served from `static_root`, `config_file` is this file
+    ///
+    /// `/file/<path>` needs no login, so the config file with the RCON passwords, the
+    /// password file and the files of every server have to be outside of it
+    fn check_static_root(&self, config_file: &Path) -> Result<(), Invalid> {
+        let root = self
+            .static_root
+            .canonicalize()
+            .unwrap_or_else(|_| self.static_root.clone());
+        let mut secrets = vec![("the config file".to_owned(), config_file)];
+        if let Some(password_file) = self
+            .auth
+            .as_ref()
+            .and_then(|auth| auth.password_file.as_deref())
+        {
+            secrets.push(("auth.password_file".to_owned(), password_file));
+        }
+        for (i, server) in self.servers.iter().enumerate() {
+            secrets.extend(
+                server
+                    .files()
+                    .into_iter()
+                    .map(|(field, file)| (format!("servers[{i}].{field}"), file)),
+            );
+        }
+
+        for (name, secret) in secrets {
+            // files that don't exist yet can't be canonicalized
+            let secret = secret
+                .canonicalize()
+                .or_else(|_| std::path::absolute(secret))
+                .unwrap_or_else(|_| secret.to_owned());
+            if secret.starts_with(&root) {
+                return Err(Invalid(
+                    "static_root".to_owned(),
+                    format!(
+                        "{} is served without logging in, but contains {name}",
+                        self.static_root.display()
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads [`AuthConfig::password_file`] into [`AuthConfig::passwords`]
+    fn load_password_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let Some(auth) = &mut self.auth else {
+            return Ok(());
+        };
+        if let Some(password_file) = &auth.password_file {
+            let passwords =
+                PasswordFile::load(password_file).map_err(|e| ConfigError::Invalid {
+                    path: path.to_owned(),
+                    field: "auth.password_file".to_owned(),
+                    reason: format!("{}: {e}", password_file.display()),
+                })?;
+            auth.passwords = Some(passwords);
+        }
+        Ok(())
+    }
+
+    /// Creates the [`HostableServer`]s described by [`Config::servers`]
+    #[must_use]
+    pub fn build_servers(&self) -> Vec<Box<dyn HostableServer>> {
+        self.servers.iter().map(ServerConfig::build).collect()
+    }
+}
+
+impl ServerConfig {
+    /// Returns the directory and files of the server by the name of their key, after
+    /// [`Config::resolve_paths`]
+    fn files(&self) -> Vec<(&'static str, &Path)> {
+        let mut files = vec![
+            ("working_dir", self.working_dir.as_deref()),
+            ("scripts.start", self.scripts.start.as_deref()),
+            ("scripts.stop", self.scripts.stop.as_deref()),
+            ("scripts.status", self.scripts.status.as_deref()),
+            ("log_file", self.log_file.as_deref()),
+            ("properties", self.properties.as_deref()),
+            (
+                "arma.preset",
+                self.arma.as_ref().and_then(|arma| arma.preset.as_deref()),
+            ),
+        ];
+        if let Some(
+            BackendConfig::Screen { log_file, .. } | BackendConfig::Direct { log_file, .. },
+        ) = &self.backend
+        {
+            files.push(("backend.log_file", log_file.as_deref()));
+        }
+        files
+            .into_iter()
+            .filter_map(|(field, file)| Some((field, file?)))
+            .collect()
+    }
+
+    /// Checks one `[[servers]]` entry, the field names are relative to it
+    fn check(&self) -> Result<(), Invalid> {
+        if self.path.is_empty()
+            || !self
+                .path
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(Invalid(
+                "path".to_owned(),
+                format!(
+                    "{:?} may only contain letters, digits, '_' and '-'",
+                    self.path
+                ),
+            ));
+        }
+        if RESERVED_PATHS.contains(&self.path.as_str()) {
+            return Err(Invalid(
+                "path".to_owned(),
+                format!("{:?} is used by the web server itself", self.path),
+            ));
+        }
+
+        if let Some(working_dir) = &self.working_dir {
+            if !working_dir.is_dir() {
+                return Err(Invalid(
+                    "working_dir".to_owned(),
+                    format!("{} isn't a directory", working_dir.display()),
+                ));
+            }
+        }
+        self.check_arma()?;
+        self.check_backend()?;
+        self.check_status_sources()?;
+        let scripts = [
+            ("start", &self.scripts.start),
+            ("stop", &self.scripts.stop),
+            ("status", &self.scripts.status),
+        ];
+        for (name, script) in scripts {
+            if let Some(script) = script {
+                if !script.is_file() {
+                    return Err(Invalid(
+                        format!("scripts.{name}"),
+                        format!("{} doesn't exist", script.display()),
+                    ));
+                }
+            }
+        }
+        let timeouts = [
+            ("start", self.timeouts.start),
+            ("stop", self.timeouts.stop),
+            ("status", self.timeouts.status),
+            ("ready", self.timeouts.ready),
+        ];
+        for (name, timeout) in timeouts {
+            if timeout == Some(0) {
+                return Err(Invalid(
+                    format!("timeouts.{name}"),
+                    "has to be at least 1 second".to_owned(),
+                ));
+            }
+        }
+        let watchdog = [
+            ("interval", self.watchdog.interval),
+            ("backoff", self.watchdog.backoff),
+            ("max_backoff", self.watchdog.max_backoff),
+            ("unhealthy", self.watchdog.unhealthy),
+        ];
+        for (name, seconds) in watchdog {
+            if seconds == Some(0) {
+                return Err(Invalid(
+                    format!("watchdog.{name}"),
+                    "has to be at least 1 second".to_owned(),
+                ));
+            }
+        }
+        let watchdog = self.watchdog.settings();
+        if watchdog.max_backoff < watchdog.backoff {
+            return Err(Invalid::new(
+                "watchdog.max_backoff",
+                "can't be shorter than watchdog.backoff",
+            ));
+        }
+        for (i, probe) in self.probes.iter().enumerate() {
+            if matches!(probe, Probe::Command { command, .. } if command.is_empty()) {
+                return Err(Invalid(
+                    format!("probes[{i}].command"),
+                    "needs at least the program".to_owned(),
+                ));
+            }
+        }
+        for (name, file) in [
+            ("log_file", &self.log_file),
+            ("properties", &self.properties),
+        ] {
+            if file.is_some() && self.kind != ServerKind::Minecraft {
+                return Err(Invalid::new(name, "is only used by `minecraft` servers"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks `rcon`, `ping`, `query` and `status`, only `minecraft` servers have all of them
+    fn check_status_sources(&self) -> Result<(), Invalid> {
+        match &self.rcon {
+            Some(_) if self.kind == ServerKind::Arma => Err(Invalid::new(
+                "rcon",
+                "is only used by `minecraft` and `bash` servers, Arma speaks BattlEye RCon",
+            )),
+            Some(rcon) if rcon.password.is_empty() => Err(Invalid::new(
+                "rcon.password",
+                "can't be empty, the server refuses every login then",
+            )),
+            Some(rcon) if !rcon.stop.is_empty() && self.kind == ServerKind::Minecraft => {
+                Err(Invalid::new(
+                    "rcon.stop",
+                    "isn't used by `minecraft` servers, they get `save-all` and `stop`",
+                ))
+            }
+            _ => Ok(()),
+        }?;
+        for (name, address) in [("ping", &self.ping), ("query", &self.query)] {
+            match address {
+                Some(_) if name == "query" && self.kind == ServerKind::Bash => {
+                    return Err(Invalid::new(
+                        name,
+                        "is only used by `minecraft` and `arma` servers",
+                    ));
+                }
+                Some(_) if name == "ping" && self.kind != ServerKind::Minecraft => {
+                    return Err(Invalid::new(name, "is only used by `minecraft` servers"));
+                }
+                Some(address) if !address.contains(':') => {
+                    return Err(Invalid::new(name, "has to be `host:port`"));
+                }
+                _ => {}
+            }
+        }
+        let missing = match self.status {
+            None | Some(StatusSource::Log) => None,
+            Some(StatusSource::Rcon) => self.rcon.is_none().then_some("an `rcon` table"),
+            Some(StatusSource::Ping) => self.ping.is_none().then_some("a `ping` address"),
+            Some(StatusSource::Query) => self.query.is_none().then_some("a `query` address"),
+        };
+        match (self.status, missing) {
+            (Some(_), _) if self.kind != ServerKind::Minecraft => Err(Invalid::new(
+                "status",
+                "is only used by `minecraft` servers",
+            )),
+            (_, Some(missing)) => Err(Invalid("status".to_owned(), format!("needs {missing}"))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the `arma` table, only `arma` servers have one
+    fn check_arma(&self) -> Result<(), Invalid> {
+        match &self.arma {
+            Some(_) if self.kind != ServerKind::Arma => {
+                Err(Invalid::new("arma", "is only used by `arma` servers"))
+            }
+            Some(arma) => arma
+                .check()
+                .map_err(|Invalid(field, reason)| Invalid(format!("arma.{field}"), reason)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the `backend` table
+    ///
+    /// The `arma` table builds the command, so it needs a backend running one
+    fn check_backend(&self) -> Result<(), Invalid> {
+        let command = match &self.backend {
+            None | Some(BackendConfig::Scripts | BackendConfig::Systemd { .. })
+                if self.arma.is_some() =>
+            {
+                return Err(Invalid::new(
+                    "backend",
+                    "has to run the command the `arma` table builds: `screen`, `tmux` or `direct`",
+                ));
+            }
+            None | Some(BackendConfig::Scripts) => return Ok(()),
+            Some(
+                BackendConfig::Screen { command, .. }
+                | BackendConfig::Tmux { command, .. }
+                | BackendConfig::Direct { command, .. },
+            ) => Some(command),
+            Some(BackendConfig::Systemd { .. }) => None,
+        };
+
+        match command {
+            Some(command) if self.arma.is_some() && !command.is_empty() => {
+                return Err(Invalid::new(
+                    "backend.command",
+                    "is built from the `arma` table, leave it out",
+                ));
+            }
+            Some(command) if self.arma.is_none() && command.is_empty() => {
+                return Err(Invalid::new(
+                    "backend.command",
+                    "needs at least the program",
+                ));
+            }
+            _ => {}
+        }
+        let scripts = &self.scripts;
+        if scripts.start.is_some() || scripts.stop.is_some() || scripts.status.is_some() {
+            return Err(Invalid::new(
+                "scripts",
+                "are only run by the `scripts` backend",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the [`Settings`] of this server, the paths have to be resolved already
+    fn settings(&self) -> Settings {
+        let defaults = Scripts::in_directory(&self.path);
+        Settings {
+            path: self.path.clone(),
+            name: self.name.clone().unwrap_or_else(|| self.path.clone()),
+            working_dir: self.working_dir.clone(),
+            scripts: Scripts {
+                start: self.scripts.start.clone().unwrap_or(defaults.start),
+                stop: self.scripts.stop.clone().unwrap_or(defaults.stop),
+                status: self.scripts.status.clone(),
+            },
+            timeouts: self.timeouts.timeouts(),
+            cancel: CancelToken::new(),
+            probes: self.probes.clone(),
+            watchdog: self.watchdog.settings(),
+        }
+    }
+
+    /// Creates the [`HostableServer`] described by `self`
+    #[must_use]
+    pub fn build(&self) -> Box<dyn HostableServer> {
+        match self.kind {
+            ServerKind::Bash => {
+                let server =
+                    GeneralBashServer::with_backend(self.settings(), self.process_backend());
+                Box::new(match &self.rcon {
+                    Some(rcon) => server.with_rcon(
+                        RconLogin {
+                            address: rcon.address.clone(),
+                            password: rcon.password.clone(),
+                        },
+                        rcon.stop.clone(),
+                    ),
+                    None => server,
+                })
+            }
+            ServerKind::Arma => {
+                let settings = self.settings();
+                let setup = self.arma.as_ref().map(|arma| arma.setup(&settings.name));
+                // the Steam query port is the one after the game port
+                let query = self.query.clone().or_else(|| {
+                    setup
+                        .as_ref()
+                        .map(|setup| format!("127.0.0.1:{}", setup.port + 1))
+                });
+                let server = arma::Server::with_backend(settings, self.process_backend());
+                let server = match query {
+                    Some(address) => server.with_query(address),
+                    None => server,
+                };
+                Box::new(match setup {
+                    Some(setup) => server.with_setup(setup),
+                    None => server,
+                })
+            }
+            ServerKind::Minecraft => {
+                if let Err(e) = MojangApi::new(Duration::from_secs(5)).check() {
+                    eprintln!(
+                        "\x1b[31m{}: players added to the lists of the stopped server get offline UUIDs, \
+                         the Mojang API needs curl: {e}\x1b[39m",
+                        self.path
+                    );
+                }
+                let server = minecraft::Server::with_settings(
+                    self.settings(),
+                    self.log_file
+                        .clone()
+                        .unwrap_or_else(|| PathBuf::from("Minecraft/screenlog.0")),
+                );
+                let server = match &self.properties {
+                    Some(properties) => server.with_properties_file(properties.clone()),
+                    None => server,
+                }
+                .with_backend(self.process_backend());
+                let server = match &self.rcon {
+                    Some(rcon) => server.with_rcon(RconLogin {
+                        address: rcon.address.clone(),
+                        password: rcon.password.clone(),
+                    }),
+                    None => server,
+                };
+                let server = match &self.ping {
+                    Some(address) => server.with_ping(address.clone()),
+                    None => server,
+                };
+                let server = match &self.query {
+                    Some(address) => server.with_query(address.clone()),
+                    None => server,
+                };
+                Box::new(match self.status {
+                    Some(source) => server.with_status_source(source),
+                    None => server,
+                })
+            }
+        }
+    }
+
+    /// Returns the [`ProcessBackend`] described by [`ServerConfig::backend`]
+    fn process_backend(&self) -> Box<dyn ProcessBackend> {
+        match self.backend.clone() {
+            None | Some(BackendConfig::Scripts) => Box::new(backend::Scripts),
+            Some(BackendConfig::Screen {
+                command,
+                stop_command,
+                log_file,
+            }) => Box::new(backend::Screen {
+                command,
+                stop_command,
+                log_file,
+            }),
+            Some(BackendConfig::Tmux {
+                command,
+                stop_command,
+            }) => Box::new(backend::Tmux {
+                command,
+                stop_command,
+            }),
+            Some(BackendConfig::Systemd { unit }) => Box::new(backend::Systemd {
+                unit: unit.unwrap_or_else(|| format!("{}.service", self.path)),
+            }),
+            Some(BackendConfig::Direct {
+                command,
+                pty,
+                stop_command,
+                env,
+                log_file,
+            }) => {
+                let mut supervisor = Supervisor::new(command).pty(pty);
+                if let Some(working_dir) = &self.working_dir {
+                    supervisor = supervisor.current_dir(working_dir);
+                }
+                for (key, value) in env {
+                    supervisor = supervisor.env(key, value);
+                }
+                if let Some(stop_command) = stop_command {
+                    supervisor = supervisor.stop_command(stop_command);
+                }
+                if let Some(log_file) = log_file {
+                    supervisor = supervisor.log_file(log_file);
+                }
+                Box::new(supervisor)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigError};
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    /// Creates an empty directory `name` for a test, with a `static` directory inside
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("web_server_config_{name}"));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("static")).unwrap();
+        directory
+    }
+
+    /// Creates the directory of the server `path` in `directory`, with empty start and stop scripts
+    fn game(directory: &Path, path: &str) {
+        fs::create_dir_all(directory.join(path)).unwrap();
+        fs::write(directory.join(path).join("start.sh"), "").unwrap();
+        fs::write(directory.join(path).join("stop.sh"), "").unwrap();
+    }
+
+    /// Parses `text` as the `web_server.toml` in `directory`
+    fn parse(directory: &Path, text: &str) -> Result<Config, ConfigError> {
+        Config::parse(text, &directory.join("web_server.toml"), directory)
+    }
+
+    /// Parses `text` as the `web_server.toml` in `directory`, returns the key and reason it refuses
+    fn invalid(directory: &Path, text: &str) -> (String, String) {
+        match parse(directory, text) {
+            Err(ConfigError::Invalid { field, reason, .. }) => (field, reason),
+            Err(e) => panic!("expected an invalid key, got {e}"),
+            Ok(_) => panic!("expected an invalid key, got a config"),
+        }
+    }
+
+    /// Parses `text` as the `web_server.toml` in `directory`, returns the message of the parser
+    fn malformed(directory: &Path, text: &str) -> String {
+        match parse(directory, text) {
+            Err(ConfigError::Parse { message, .. }) => message,
+            Err(e) => panic!("expected a parse error, got {e}"),
+            Ok(_) => panic!("expected a parse error, got a config"),
+        }
+    }
+
+    /// `listen` and a `bash` server called `game`, followed by `rest` for the server or
+    /// more tables
+    fn with_game(rest: &str) -> String {
+        format!(
+            "listen = [\"127.0.0.1:31415\"]\n[[servers]]\nkind = \"bash\"\npath = \"game\"\n{rest}"
+        )
+    }
+
+    #[test]
+    fn relative_paths_are_resolved_against_the_config() {
+        let directory = directory("relative");
+        game(&directory, "game");
+        fs::write(directory.join("users.passwd"), "").unwrap();
+        let config = parse(
+            &directory,
+            &with_game("scripts.status = \"/bin/true\"\n[auth]\npassword_file = \"users.passwd\""),
+        )
+        .unwrap();
+
+        assert_eq!(config.static_root, directory.join("static"));
+        let auth = config.auth.unwrap();
+        assert_eq!(auth.password_file, Some(directory.join("users.passwd")));
+        assert!(auth.passwords.is_some());
+        let server = &config.servers[0];
+        assert_eq!(server.working_dir.as_deref(), Some(&*directory));
+        assert_eq!(server.scripts.start, Some(directory.join("game/start.sh")));
+        assert_eq!(server.scripts.stop, Some(directory.join("game/stop.sh")));
+        assert_eq!(
+            server.scripts.status.as_deref(),
+            Some(Path::new("/bin/true"))
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_refused() {
+        let directory = directory("missing");
+        game(&directory, "game");
+        assert!(malformed(&directory, "").contains("missing field `listen`"));
+        let message = malformed(
+            &directory,
+            "listen = [\"127.0.0.1:31415\"]\n[[servers]]\nkind = \"bash\"",
+        );
+        assert!(message.contains("missing field `path`"), "{message}");
+        let message = malformed(&directory, &with_game("colour = \"red\""));
+        assert!(message.contains("unknown field `colour`"), "{message}");
+
+        assert_eq!(
+            invalid(&directory, "listen = []"),
+            (
+                "listen".to_owned(),
+                "at least one address is needed".to_owned()
+            )
+        );
+        fs::remove_file(directory.join("game/stop.sh")).unwrap();
+        let (field, reason) = invalid(&directory, &with_game(""));
+        assert_eq!(field, "servers[0].scripts.stop");
+        assert!(reason.ends_with("game/stop.sh doesn't exist"), "{reason}");
+    }
+
+    #[test]
+    fn duplicate_paths_are_refused() {
+        let directory = directory("duplicate");
+        game(&directory, "game");
+        assert_eq!(
+            invalid(
+                &directory,
+                &with_game("[[servers]]\nkind = \"bash\"\npath = \"game\""),
+            ),
+            (
+                "servers[1].path".to_owned(),
+                "\"game\" is used by another server".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn reserved_paths_are_refused() {
+        let directory = directory("reserved");
+        for path in ["events", "login", "file", "Ping"] {
+            game(&directory, path);
+            assert_eq!(
+                invalid(
+                    &directory,
+                    &format!(
+                        "listen = [\"127.0.0.1:31415\"]\n[[servers]]\nkind = \"bash\"\npath = \"{path}\""
+                    ),
+                ),
+                (
+                    "servers[0].path".to_owned(),
+                    format!("\"{path}\" is used by the web server itself")
+                )
+            );
+        }
+        let (field, reason) = invalid(
+            &directory,
+            "listen = [\"127.0.0.1:31415\"]\n[[servers]]\nkind = \"bash\"\npath = \"../game\"",
+        );
+        assert_eq!(field, "servers[0].path");
+        assert!(reason.contains("may only contain"), "{reason}");
+    }
+
+    #[test]
+    fn the_auth_section_is_checked() {
+        let directory = directory("auth");
+        game(&directory, "game");
+        let field = |rest: &str| invalid(&directory, &with_game(rest)).0;
+
+        assert_eq!(field("[auth]\nsession_minutes = 0"), "auth.session_minutes");
+        assert_eq!(
+            field("[[auth.tokens]]\nuser = \"script\"\nsha256 = \"abc\""),
+            "auth.tokens[0].sha256"
+        );
+        assert_eq!(
+            field("[auth.users]\nalice = [\"admin\"]"),
+            "auth.users.alice"
+        );
+        assert_eq!(
+            field("[auth]\npassword_file = \"missing.passwd\""),
+            "auth.password_file"
+        );
+    }
+
+    #[test]
+    fn roles_are_checked() {
+        let directory = directory("roles");
+        game(&directory, "game");
+        let invalid = |rest: &str| invalid(&directory, &with_game(rest));
+
+        assert_eq!(
+            invalid("[auth.roles.friends]\nother = [\"start\"]"),
+            (
+                "auth.roles.friends.other".to_owned(),
+                "isn't the path of a configured server".to_owned()
+            )
+        );
+        let (field, reason) = invalid("[auth.roles.friends]\ngame = [\"power\"]");
+        assert_eq!(field, "auth.roles.friends.game");
+        assert!(
+            reason.starts_with("\"power\" shuts down the whole machine"),
+            "{reason}"
+        );
+
+        let config = parse(
+            &directory,
+            &with_game(
+                "[auth.roles.admin]\n\"*\" = [\"power\", \"start\"]\n[auth.roles.friends]\ngame = [\"view\", \"start\"]\n[auth.users]\nalice = [\"admin\"]\nbob = [\"friends\"]",
+            ),
+        )
+        .unwrap();
+        assert_eq!(config.auth.unwrap().users.len(), 2);
+    }
+
+    #[test]
+    fn minecraft_servers_can_have_a_backend() {
+        let directory = directory("minecraft_backend");
+        let config = parse(
+            &directory,
+            "listen = [\"127.0.0.1:31415\"]\n[[servers]]\nkind = \"minecraft\"\npath = \"mc\"\n\
+             [servers.backend]\nkind = \"direct\"\ncommand = [\"java\", \"-jar\", \"server.jar\"]\n\
+             log_file = \"mc/console.log\"",
+        )
+        .unwrap();
+
+        let server = &config.servers[0];
+        assert_eq!(server.log_file, Some(directory.join("mc/console.log")));
+        assert_eq!(
+            server.properties,
+            Some(directory.join("Minecraft/server.properties"))
+        );
+        assert!(server
+            .build()
+            .to_json()
+            .unwrap()
+            .contains(r#""backend":"direct""#));
+    }
+
+    #[test]
+    fn the_static_root_cant_serve_the_config() {
+        let directory = directory("static_root");
+        let (field, reason) = invalid(
+            &directory,
+            "listen = [\"127.0.0.1:31415\"]\nstatic_root = \".\"",
+        );
+        assert_eq!(field, "static_root");
+        assert!(reason.ends_with("contains the config file"), "{reason}");
+
+        game(&directory, "game");
+        let (field, reason) = invalid(&directory, &with_game("working_dir = \"static\""));
+        assert_eq!(field, "static_root");
+        assert!(reason.ends_with("servers[0].working_dir"), "{reason}");
+    }
+
+    #[test]
+    fn the_password_file_cant_be_served() {
+        let directory = directory("password_file");
+        fs::write(directory.join("static/users.passwd"), "").unwrap();
+        let (field, reason) = invalid(
+            &directory,
+            "listen = [\"127.0.0.1:31415\"]\n[auth]\npassword_file = \"static/users.passwd\"",
+        );
+        assert_eq!(field, "static_root");
+        assert!(reason.ends_with("contains auth.password_file"), "{reason}");
+    }
+}
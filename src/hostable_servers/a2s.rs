@@ -0,0 +1,589 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/a2s.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! The Steam server queries `A2S_INFO`, `A2S_PLAYER` and `A2S_RULES`
+//! =============================================================
+
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+/// Header of a packet that isn't split
+const SINGLE: i32 = -1;
+/// Header of one part of a split answer
+const SPLIT: i32 = -2;
+/// Request of [`A2s::info`]
+const INFO: u8 = 0x54;
+/// Answer to [`INFO`]
+const INFO_ANSWER: u8 = 0x49;
+/// Request of [`A2s::players`]
+const PLAYER: u8 = 0x55;
+/// Answer to [`PLAYER`]
+const PLAYER_ANSWER: u8 = 0x44;
+/// Request of [`A2s::rules`]
+const RULES: u8 = 0x56;
+/// Answer to [`RULES`]
+const RULES_ANSWER: u8 = 0x45;
+/// Answer asking for the request again with the challenge number it contains
+const CHALLENGE: u8 = 0x41;
+/// Payload of [`INFO`]
+const INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+
+/// What `A2S_INFO` tells about a server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Info {
+    /// Protocol version
+    pub protocol: u8,
+    /// Name of the server in the browser
+    pub name: String,
+    /// Map the server is on, Arma 3 sends the terrain like `Altis`
+    pub map: String,
+    /// Folder of the game, like `Arma3`
+    pub folder: String,
+    /// Game or mod, Arma 3 sends the name of the mission instead
+    pub game: String,
+    /// Steam application id, cut to 16 bits
+    pub app_id: u16,
+    /// Players online, including bots
+    pub players: usize,
+    /// Player slots
+    pub max_players: usize,
+    /// Bots online
+    pub bots: usize,
+    /// `d` for dedicated, `l` for non-dedicated, `p` for a `SourceTV` relay
+    pub server_type: char,
+    /// `l` for Linux, `w` for Windows, `m` for macOS
+    pub environment: char,
+    /// Whether joining needs a password
+    pub password: bool,
+    /// Whether the server uses Valve Anti-Cheat
+    pub vac: bool,
+    /// Version of the game
+    pub version: String,
+    /// Game port, if the server sent it
+    pub port: Option<u16>,
+    /// Tags of the server, if it sent them
+    pub keywords: Option<String>,
+    /// Full 64 bit application id, if the server sent it
+    pub game_id: Option<u64>,
+}
+
+/// One player `A2S_PLAYER` lists
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Player {
+    /// Name of the player, empty while they are still connecting
+    pub name: String,
+    /// Score, what it counts depends on the game
+    pub score: i32,
+    /// Seconds the player has been connected
+    pub seconds: f32,
+}
+
+/// Why a query failed
+#[derive(Debug)]
+pub enum A2sError {
+    /// The socket couldn't be opened, sent or read from
+    Io(io::Error),
+    /// Nothing came back, not even after the retries
+    NoAnswer {
+        /// Number of requests sent
+        attempts: u32,
+    },
+    /// The server sent something that isn't an answer to the query
+    Protocol(String),
+}
+
+impl fmt::Display for A2sError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::NoAnswer { attempts } => write!(f, "no answer to {attempts} requests"),
+            Self::Protocol(reason) => write!(f, "unexpected answer: {reason}"),
+        }
+    }
+}
+impl std::error::Error for A2sError {}
+impl From<io::Error> for A2sError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A UDP socket querying the Steam query port of one server
+///
+/// Servers answer a query with a challenge number first, the query is then sent
+/// again with it. Answers bigger than one packet arrive split and are put back
+/// together. A query without an answer is sent again up to `retries` times
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use web_server::hostable_servers::a2s::A2s;
+///
+/// // Arma 3 answers on the game port + 1
+/// let a2s = A2s::connect("127.0.0.1:2303", Duration::from_secs(1), 2).unwrap();
+/// let info = a2s.info().unwrap();
+/// println!("{} on {}, {}/{}", info.game, info.map, info.players, info.max_players);
+/// ```
+#[derive(Debug)]
+pub struct A2s {
+    /// Socket connected to the query port
+    socket: UdpSocket,
+    /// Number of times a query without an answer is sent again
+    retries: u32,
+}
+
+impl A2s {
+    /// Opens a socket sending to the query port at `address`
+    ///
+    /// `timeout` is how long every answer is waited for
+    ///
+    /// # Errors
+    /// Returns [`A2sError::Io`] if the address doesn't resolve or no socket can be opened
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        timeout: Duration,
+        retries: u32,
+    ) -> Result<Self, A2sError> {
+        let address: SocketAddr = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "the address didn't resolve"))?;
+        let local: SocketAddr = if address.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 16], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(address)?;
+        socket.set_read_timeout(Some(timeout))?;
+        Ok(Self { socket, retries })
+    }
+
+    /// Asks for the name, map, player count and more, see [`Info`]
+    ///
+    /// # Errors
+    /// Returns an [`A2sError`] if no answer came or it wasn't an `A2S_INFO` answer
+    pub fn info(&self) -> Result<Info, A2sError> {
+        let answer = self.query(INFO, INFO_PAYLOAD, false, INFO_ANSWER)?;
+        parse_info(&answer).ok_or_else(|| A2sError::Protocol("the info ends early".to_owned()))
+    }
+
+    /// Asks for the players online
+    ///
+    /// # Errors
+    /// Returns an [`A2sError`] if no answer came or it wasn't an `A2S_PLAYER` answer
+    pub fn players(&self) -> Result<Vec<Player>, A2sError> {
+        let answer = self.query(PLAYER, &[], true, PLAYER_ANSWER)?;
+        parse_players(&answer)
+            .ok_or_else(|| A2sError::Protocol("the player list ends early".to_owned()))
+    }
+
+    /// Asks for the server's rules, its settings as names and values
+    ///
+    /// Some games, like Arma 3, put binary data into the values, they are read lossily
+    ///
+    /// # Errors
+    /// Returns an [`A2sError`] if no answer came or it wasn't an `A2S_RULES` answer
+    pub fn rules(&self) -> Result<BTreeMap<String, String>, A2sError> {
+        let answer = self.query(RULES, &[], true, RULES_ANSWER)?;
+        parse_rules(&answer).ok_or_else(|| A2sError::Protocol("the rules end early".to_owned()))
+    }
+
+    /// Sends a query and returns the answer of type `expected`, without its type
+    ///
+    /// `challenged` queries send `-1` as the challenge the first time, the others
+    /// only add one when the server asks for it. A challenge is kept for the retries
+    fn query(
+        &self,
+        kind: u8,
+        payload: &[u8],
+        challenged: bool,
+        expected: u8,
+    ) -> Result<Vec<u8>, A2sError> {
+        let mut challenge = challenged.then_some([0xFF; 4]);
+        let mut attempts = 0;
+        while attempts <= self.retries {
+            attempts += 1;
+            let Some(mut answer) = self.exchange(kind, payload, challenge)? else {
+                continue;
+            };
+            if let [CHALLENGE, a, b, c, d, ..] = answer[..] {
+                challenge = Some([a, b, c, d]);
+                match self.exchange(kind, payload, challenge)? {
+                    Some(again) => answer = again,
+                    None => continue,
+                }
+            }
+            return match answer.split_first() {
+                Some((&kind, rest)) if kind == expected => Ok(rest.to_vec()),
+                Some((kind, _)) => Err(A2sError::Protocol(format!(
+                    "answer type {kind:#04x} instead of {expected:#04x}"
+                ))),
+                None => Err(A2sError::Protocol("an empty answer".to_owned())),
+            };
+        }
+        Err(A2sError::NoAnswer { attempts })
+    }
+
+    /// Sends a query with the `challenge` and receives the answer, `None` after a timeout
+    fn exchange(
+        &self,
+        kind: u8,
+        payload: &[u8],
+        challenge: Option<[u8; 4]>,
+    ) -> Result<Option<Vec<u8>>, A2sError> {
+        let mut packet = SINGLE.to_le_bytes().to_vec();
+        packet.push(kind);
+        packet.extend_from_slice(payload);
+        if let Some(challenge) = challenge {
+            packet.extend_from_slice(&challenge);
+        }
+        self.socket.send(&packet)?;
+        self.receive()
+    }
+
+    /// Receives an answer, putting split ones back together, `None` after a timeout
+    fn receive(&self) -> Result<Option<Vec<u8>>, A2sError> {
+        let mut parts: Vec<Option<Vec<u8>>> = Vec::new();
+        let mut split_id = None;
+        let mut buffer = vec![0; 65_536];
+        loop {
+            let length = match self.socket.recv(&mut buffer) {
+                Ok(length) => length,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                // an ICMP port unreachable from an earlier request, nobody listens
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            let mut packet = Bytes(&buffer[..length]);
+            match packet.i32() {
+                Some(SINGLE) => return Ok(Some(packet.0.to_vec())),
+                Some(SPLIT) => {}
+                _ => return Err(A2sError::Protocol("not an A2S packet".to_owned())),
+            }
+
+            // id, number of parts, number of this part and the size of a part
+            let (Some(id), Some(total), Some(number), Some(_)) =
+                (packet.i32(), packet.u8(), packet.u8(), packet.u16())
+            else {
+                return Err(A2sError::Protocol("a split packet ends early".to_owned()));
+            };
+            if id < 0 {
+                // the highest bit marks bzip2, only engines from before 2006 do that
+                return Err(A2sError::Protocol("compressed answers".to_owned()));
+            }
+            if split_id != Some(id) {
+                // the parts of an answer that was given up on
+                split_id = Some(id);
+                parts = vec![None; usize::from(total)];
+            }
+            let Some(part) = parts.get_mut(usize::from(number)) else {
+                return Err(A2sError::Protocol(format!("part {number} of {total}")));
+            };
+            *part = Some(packet.0.to_vec());
+
+            if parts.iter().all(Option::is_some) {
+                let answer: Vec<u8> = parts.into_iter().flatten().flatten().collect();
+                let mut answer = Bytes(&answer);
+                if answer.i32() != Some(SINGLE) {
+                    return Err(A2sError::Protocol("not an A2S answer".to_owned()));
+                }
+                return Ok(Some(answer.0.to_vec()));
+            }
+        }
+    }
+}
+
+/// Little endian reader over an answer, every read is `None` once it ends
+struct Bytes<'a>(&'a [u8]);
+
+impl Bytes<'_> {
+    /// Takes the next `N` bytes
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (taken, rest) = self.0.split_first_chunk()?;
+        self.0 = rest;
+        Some(*taken)
+    }
+    /// Reads a byte
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+    /// Reads a 16 bit number
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+    /// Reads a signed 32 bit number
+    fn i32(&mut self) -> Option<i32> {
+        self.take().map(i32::from_le_bytes)
+    }
+    /// Reads a 64 bit number
+    fn u64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_le_bytes)
+    }
+    /// Reads a 32 bit float
+    fn f32(&mut self) -> Option<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+    /// Reads a string ending with a zero byte
+    fn string(&mut self) -> Option<String> {
+        let end = self.0.iter().position(|&byte| byte == 0)?;
+        let string = String::from_utf8_lossy(&self.0[..end]).into_owned();
+        self.0 = &self.0[end + 1..];
+        Some(string)
+    }
+}
+
+/// Reads an `A2S_INFO` answer after its type
+fn parse_info(answer: &[u8]) -> Option<Info> {
+    let mut bytes = Bytes(answer);
+    let mut info = Info {
+        protocol: bytes.u8()?,
+        name: bytes.string()?,
+        map: bytes.string()?,
+        folder: bytes.string()?,
+        game: bytes.string()?,
+        app_id: bytes.u16()?,
+        players: bytes.u8()?.into(),
+        max_players: bytes.u8()?.into(),
+        bots: bytes.u8()?.into(),
+        server_type: bytes.u8()?.into(),
+        environment: bytes.u8()?.into(),
+        password: bytes.u8()? == 1,
+        vac: bytes.u8()? == 1,
+        version: bytes.string()?,
+        port: None,
+        keywords: None,
+        game_id: None,
+    };
+
+    // the extra data flag says which optional fields follow
+    let flags = bytes.u8().unwrap_or_default();
+    if flags & 0x80 != 0 {
+        info.port = Some(bytes.u16()?);
+    }
+    if flags & 0x10 != 0 {
+        // the server's Steam id
+        bytes.u64()?;
+    }
+    if flags & 0x40 != 0 {
+        // port and name of SourceTV
+        bytes.u16()?;
+        bytes.string()?;
+    }
+    if flags & 0x20 != 0 {
+        info.keywords = Some(bytes.string()?);
+    }
+    if flags & 0x01 != 0 {
+        info.game_id = Some(bytes.u64()?);
+    }
+    Some(info)
+}
+
+/// Reads an `A2S_PLAYER` answer after its type
+fn parse_players(answer: &[u8]) -> Option<Vec<Player>> {
+    let mut bytes = Bytes(answer);
+    let count = bytes.u8()?;
+    (0..count)
+        .map(|_| {
+            // the index is always 0
+            bytes.u8()?;
+            Some(Player {
+                name: bytes.string()?,
+                score: bytes.i32()?,
+                seconds: bytes.f32()?,
+            })
+        })
+        .collect()
+}
+
+/// Reads an `A2S_RULES` answer after its type
+fn parse_rules(answer: &[u8]) -> Option<BTreeMap<String, String>> {
+    let mut bytes = Bytes(answer);
+    let count = bytes.u16()?;
+    (0..count)
+        .map(|_| Some((bytes.string()?, bytes.string()?)))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{
+        A2s, A2sError, CHALLENGE, INFO, INFO_ANSWER, INFO_PAYLOAD, PLAYER, PLAYER_ANSWER, RULES,
+        RULES_ANSWER, SINGLE, SPLIT,
+    };
+    use std::{
+        net::{SocketAddr, UdpSocket},
+        thread,
+        time::Duration,
+    };
+
+    /// Largest packet Steam servers send, bigger answers are split
+    const MAX_PACKET: usize = 1400;
+    /// How long the client under test waits for every answer
+    const TIMEOUT: Duration = Duration::from_millis(200);
+    /// Challenge number of the fake server
+    const NUMBER: [u8; 4] = [0x4A, 0x8B, 0x11, 0x05];
+
+    /// Appends `text` with a zero byte
+    fn string(packet: &mut Vec<u8>, text: &str) {
+        packet.extend_from_slice(text.as_bytes());
+        packet.push(0);
+    }
+
+    /// `A2S_INFO` answer of an Arma 3 server, after the header
+    fn info() -> Vec<u8> {
+        let mut answer = vec![INFO_ANSWER, 17];
+        string(&mut answer, "Nacor's Arma");
+        string(&mut answer, "Altis");
+        string(&mut answer, "Arma3");
+        string(&mut answer, "Wasteland");
+        answer.extend_from_slice(&107_410_u32.to_le_bytes()[..2]);
+        answer.extend_from_slice(&[2, 40, 0, b'd', b'l', 0, 0]);
+        string(&mut answer, "2.14.150957");
+        answer.push(0x80 | 0x20 | 0x01);
+        answer.extend_from_slice(&2302_u16.to_le_bytes());
+        string(&mut answer, "bt,r214,n0,s7,i2,mf,lf,vt,dt,tsandbox,g65545");
+        answer.extend_from_slice(&107_410_u64.to_le_bytes());
+        answer
+    }
+
+    /// `A2S_PLAYER` answer, one player is still connecting
+    fn players() -> Vec<u8> {
+        let mut answer = vec![PLAYER_ANSWER, 3];
+        for (name, score) in [("Nacor", 12), ("Miller", 3), ("", 0)] {
+            answer.push(0);
+            string(&mut answer, name);
+            answer.extend_from_slice(&i32::to_le_bytes(score));
+            answer.extend_from_slice(&61.5_f32.to_le_bytes());
+        }
+        answer
+    }
+
+    /// `A2S_RULES` answer too big for one packet
+    fn rules() -> Vec<u8> {
+        let mut answer = vec![RULES_ANSWER];
+        answer.extend_from_slice(&200_u16.to_le_bytes());
+        for i in 0..200 {
+            string(&mut answer, &format!("rule{i:03}"));
+            string(&mut answer, &"x".repeat(20));
+        }
+        answer
+    }
+
+    /// Answers queries like a Steam server that wants challenges for everything
+    ///
+    /// Answers bigger than [`MAX_PACKET`] are split and their parts are sent
+    /// last one first. `lose` decides for the n-th packet whether it gets lost
+    pub fn responder(lose: fn(usize) -> bool) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").expect("a free port");
+        let address = socket.local_addr().expect("a bound address");
+        thread::spawn(move || {
+            let mut buffer = [0; MAX_PACKET];
+            for n in 0.. {
+                let Ok((length, client)) = socket.recv_from(&mut buffer) else {
+                    return;
+                };
+                let packet = &buffer[..length];
+                if lose(n) || packet[..4] != SINGLE.to_le_bytes() {
+                    continue;
+                }
+                let challenged = packet.ends_with(&NUMBER);
+                let answer = match packet[4] {
+                    INFO if packet[5..].starts_with(INFO_PAYLOAD) && challenged => info(),
+                    PLAYER if challenged => players(),
+                    RULES if challenged => rules(),
+                    INFO | PLAYER | RULES => [&[CHALLENGE][..], &NUMBER].concat(),
+                    _ => continue,
+                };
+
+                let answer = [&SINGLE.to_le_bytes()[..], &answer].concat();
+                if answer.len() <= MAX_PACKET {
+                    socket
+                        .send_to(&answer, client)
+                        .expect("the client is listening");
+                    continue;
+                }
+                let parts: Vec<Vec<u8>> =
+                    answer.chunks(MAX_PACKET - 12).map(<[u8]>::to_vec).collect();
+                for (number, part) in parts.iter().enumerate().rev() {
+                    let mut packet = SPLIT.to_le_bytes().to_vec();
+                    packet.extend_from_slice(&7_i32.to_le_bytes());
+                    packet.push(u8::try_from(parts.len()).expect("few parts"));
+                    packet.push(u8::try_from(number).expect("few parts"));
+                    packet.extend_from_slice(&1248_u16.to_le_bytes());
+                    packet.extend_from_slice(part);
+                    socket
+                        .send_to(&packet, client)
+                        .expect("the client is listening");
+                }
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn reads_the_info_after_the_challenge() {
+        let address = responder(|_| false);
+        let info = A2s::connect(address, TIMEOUT, 0).unwrap().info().unwrap();
+        assert_eq!(info.name, "Nacor's Arma");
+        assert_eq!(
+            (info.map.as_str(), info.game.as_str()),
+            ("Altis", "Wasteland")
+        );
+        assert_eq!((info.players, info.max_players, info.bots), (2, 40, 0));
+        assert_eq!((info.server_type, info.environment), ('d', 'l'));
+        assert!(!info.password);
+        assert_eq!(info.version, "2.14.150957");
+        assert_eq!(info.port, Some(2302));
+        assert!(info.keywords.unwrap().starts_with("bt,"));
+        assert_eq!(info.game_id, Some(107_410));
+    }
+
+    #[test]
+    fn lists_players() {
+        let address = responder(|_| false);
+        let players = A2s::connect(address, TIMEOUT, 0)
+            .unwrap()
+            .players()
+            .unwrap();
+        let names: Vec<&str> = players.iter().map(|player| player.name.as_str()).collect();
+        assert_eq!(names, ["Nacor", "Miller", ""]);
+        assert_eq!(players[0].score, 12);
+        assert!((players[1].seconds - 61.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn puts_split_rules_together() {
+        let address = responder(|_| false);
+        let rules = A2s::connect(address, TIMEOUT, 0).unwrap().rules().unwrap();
+        assert_eq!(rules.len(), 200);
+        assert_eq!(rules["rule199"], "x".repeat(20));
+    }
+
+    #[test]
+    fn retries_lost_packets() {
+        // the first query and the first one with the challenge get lost
+        let address = responder(|n| n == 0 || n == 2);
+        let a2s = A2s::connect(address, TIMEOUT, 2).unwrap();
+        assert_eq!(a2s.info().unwrap().game, "Wasteland");
+    }
+
+    #[test]
+    fn gives_up_without_answers() {
+        let address = responder(|_| true);
+        let a2s = A2s::connect(address, TIMEOUT, 1).unwrap();
+        assert!(matches!(
+            a2s.players(),
+            Err(A2sError::NoAnswer { attempts: 2 })
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/arma/mod.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Implements [`crate::hostable_servers::HostableServer`] for Arma 3
+//! =============================================================
+
+use super::{
+    a2s::A2s,
+    backend::{self, ProcessBackend},
+    backend_name,
+    lifecycle::{Lifecycle, Observation},
+    probe::{self, Probe, ProbeResult},
+    process::CancelToken,
+    supervisor::{Exit, SupervisorStatus},
+    watchdog::WatchdogSettings,
+    CommandFailure, HostableServer, Players, Selection, Settings, CONSOLE_LINES,
+};
+use serde::Serialize;
+use setup::Setup;
+use std::{path::Path, time::Duration};
+
+pub mod preset;
+pub mod setup;
+
+/// How long every A2S answer is waited for
+const A2S_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often a lost A2S query is sent again
+const A2S_RETRIES: u32 = 2;
+
+/// Arma 3 Server with its players, mission and slots
+///
+/// The process is run by a [`ProcessBackend`] like a [`super::GeneralBashServer`],
+/// what happens inside comes from the Steam query port.
+/// With a [`Setup`] the `server.cfg` and the command line are written before every start,
+/// the missions and mods can be chosen then
+#[derive(Serialize)]
+pub struct Server {
+    /// Path, name and scripts of the Server
+    #[serde(flatten)]
+    settings: Settings,
+    /// Runs the process of the Server
+    #[serde(rename = "backend", serialize_with = "backend_name")]
+    backend: Box<dyn ProcessBackend>,
+    /// Process id and last exit, if the backend knows them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process: Option<SupervisorStatus>,
+    /// `host:port` of the Steam query port, the game port + 1
+    #[serde(skip)]
+    query_address: Option<String>,
+    /// `server.cfg`, mods and command line, the backend's own command without
+    #[serde(skip)]
+    setup: Option<Setup>,
+    /// Lifecycle state of the Server and when it changed
+    #[serde(flatten)]
+    lifecycle: Lifecycle,
+    /// Results of the last readiness probes
+    probes: Vec<ProbeResult>,
+    /// Number of players and their nametags
+    players: Players,
+    /// Mission being played, like `Wasteland`
+    mission: Option<String>,
+    /// Terrain of the mission, like `Altis`
+    map: Option<String>,
+    /// Player slots, 0 until the query port answered
+    slots: usize,
+}
+
+impl Server {
+    /// Creates a new turned off Arma server run by its start and stop scripts in `./arma/`
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_backend(Settings::new("arma"), Box::new(backend::Scripts))
+    }
+    /// Creates a new turned off Arma server whose process is run by `backend`
+    #[must_use]
+    pub fn with_backend(settings: Settings, backend: Box<dyn ProcessBackend>) -> Self {
+        Self {
+            lifecycle: settings.lifecycle(),
+            settings,
+            backend,
+            process: None,
+            query_address: None,
+            setup: None,
+            probes: Vec::new(),
+            players: Players::new(),
+            mission: None,
+            map: None,
+            slots: 0,
+        }
+    }
+    /// Reads the players, mission and slots from the Steam query port at `address`
+    ///
+    /// Without probes the server only counts as ready once the port answers
+    #[must_use]
+    pub fn with_query(mut self, address: String) -> Self {
+        self.query_address = Some(address);
+        self
+    }
+    /// Writes the `server.cfg` and builds the command line from `setup` before every start
+    ///
+    /// The backend has to run a command, like `screen` or `direct`.
+    /// Missions and mods picked on the web page before replace the ones of `setup`
+    #[must_use]
+    pub fn with_setup(mut self, mut setup: Setup) -> Self {
+        if let Err(e) = setup.load_selection(self.working_dir()) {
+            eprintln!(
+                "\x1b[31m[{}] Keeping the configured missions and mods: {e}\x1b[39m",
+                self.settings.name
+            );
+        }
+        self.setup = Some(setup);
+        self
+    }
+    /// Returns the setup, or that missions and mods can't be chosen without one
+    fn setup(&self, action: &str) -> Result<&Setup, CommandFailure> {
+        self.setup
+            .as_ref()
+            .ok_or_else(|| CommandFailure::Unsupported {
+                action: format!("{action} without an `arma` setup"),
+            })
+    }
+    /// Directory Arma runs in
+    fn working_dir(&self) -> &Path {
+        self.settings
+            .working_dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new("."))
+    }
+    /// Writes the `server.cfg` and hands the command line with the selected mods to the backend
+    fn prepare(&mut self) -> Result<(), CommandFailure> {
+        let Some(setup) = &self.setup else {
+            return Ok(());
+        };
+        setup.write_config(self.working_dir())?;
+        let mods = setup.selected_mods()?;
+        println!(
+            "\x1b[36m{}: loading {} mods, {} missions in the rotation\x1b[39m",
+            self.settings.path,
+            mods.len(),
+            setup.server_cfg.missions.len()
+        );
+        let command = setup.command(&mods);
+        self.backend.set_command(command)
+    }
+    /// Forgets what the query port said
+    fn set_default(&mut self) {
+        self.players = Players::new();
+        self.mission = None;
+        self.map = None;
+        self.slots = 0;
+    }
+    /// Asks the query port for the info and the players, returns true if it answered
+    ///
+    /// Players that are still connecting have no name yet and are only counted.
+    /// Every poll gets a new socket, late answers to the last one can't get in the way
+    fn update_query(&mut self) -> bool {
+        let Some(address) = &self.query_address else {
+            return false;
+        };
+        let answer = A2s::connect(address, A2S_TIMEOUT, A2S_RETRIES)
+            .and_then(|a2s| Ok((a2s.info()?, a2s.players()?)));
+
+        match answer {
+            Ok((info, players)) => {
+                self.players = Players {
+                    count: info.players,
+                    name_tags: players
+                        .into_iter()
+                        .map(|player| player.name)
+                        .filter(|name| !name.is_empty())
+                        .collect(),
+                };
+                // Arma puts the mission where other games name themselves
+                self.mission = Some(info.game).filter(|mission| !mission.is_empty());
+                self.map = Some(info.map).filter(|map| !map.is_empty());
+                self.slots = info.max_players;
+                true
+            }
+            Err(e) => {
+                // the port only opens once the mission is loaded
+                eprintln!(
+                    "\x1b[31m{}: A2S query to {address} failed: {e}\x1b[39m",
+                    self.settings.path
+                );
+                self.set_default();
+                false
+            }
+        }
+    }
+}
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostableServer for Server {
+    fn get_path(&self) -> &str {
+        &self.settings.path
+    }
+
+    fn get_name(&self) -> &str {
+        &self.settings.name
+    }
+
+    /// Writes the `server.cfg` if there is a setup and launches the process
+    fn start(&mut self) -> Result<(), CommandFailure> {
+        self.lifecycle.begin_start()?;
+        self.settings.probes.iter_mut().for_each(Probe::mark_start);
+        if let Err(e) = self
+            .prepare()
+            .and_then(|()| self.backend.launch(&self.settings))
+        {
+            self.lifecycle.script_failed();
+            return Err(e);
+        }
+
+        self.update_status()
+    }
+
+    fn stop(&mut self) -> Result<(), CommandFailure> {
+        self.lifecycle.begin_stop()?;
+        if let Err(e) = self.backend.terminate(&self.settings) {
+            self.lifecycle.script_failed();
+            return Err(e);
+        }
+
+        self.update_status()
+    }
+
+    fn update_status(&mut self) -> Result<(), CommandFailure> {
+        let alive = match self.backend.is_alive(&self.settings) {
+            Ok(alive) => alive,
+            Err(e) => {
+                self.lifecycle.observe(Observation::Unknown);
+                return Err(e);
+            }
+        };
+        self.process = self.backend.process();
+
+        let answered = alive && self.update_query();
+        if !alive {
+            self.set_default();
+        }
+        self.probes = if alive {
+            self.check_probes()
+        } else {
+            Vec::new()
+        };
+        let ready = if self.probes.is_empty() {
+            Some(answered || self.query_address.is_none())
+        } else {
+            probe::all_ok(&self.probes)
+        };
+        self.lifecycle
+            .observe(Observation::from_probes(alive, ready));
+
+        Ok(())
+    }
+
+    fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&self)
+    }
+
+    fn cancel_token(&self) -> Option<CancelToken> {
+        Some(self.settings.cancel.clone())
+    }
+
+    fn probes(&self) -> &[Probe] {
+        &self.settings.probes
+    }
+
+    fn lifecycle_mut(&mut self) -> Option<&mut Lifecycle> {
+        Some(&mut self.lifecycle)
+    }
+
+    fn last_exit(&self) -> Option<Exit> {
+        self.process
+            .as_ref()
+            .and_then(|process| process.last_exit.clone())
+    }
+
+    fn watchdog(&self) -> WatchdogSettings {
+        self.settings.watchdog
+    }
+
+    fn send_console(&mut self, line: &str) -> Result<(), CommandFailure> {
+        self.backend.send_input(&self.settings, line)
+    }
+
+    fn console_output(&self) -> Result<Vec<String>, CommandFailure> {
+        self.backend.read_output(&self.settings, CONSOLE_LINES)
+    }
+
+    /// Returns the missions in `mpmissions` and the rotation
+    fn missions(&self) -> Result<Selection, CommandFailure> {
+        let setup = self.setup("missions")?;
+        Ok(Selection {
+            available: Setup::available_missions(self.working_dir())?,
+            selected: setup.server_cfg.missions.clone(),
+        })
+    }
+
+    /// Sets the rotation, every mission has to be in `mpmissions`
+    fn select_missions(&mut self, missions: Vec<String>) -> Result<(), CommandFailure> {
+        let mut setup = self.setup("missions")?.clone();
+        let available = Setup::available_missions(self.working_dir())?;
+        if let Some(missing) = missions.iter().find(|mission| !available.contains(mission)) {
+            return Err(CommandFailure::Rejected {
+                reason: format!("{missing:?} isn't a mission in mpmissions"),
+            });
+        }
+        setup.server_cfg.missions = missions;
+        setup.save_selection(self.working_dir())?;
+        self.setup = Some(setup);
+        Ok(())
+    }
+
+    /// Returns the mods of the preset and the loaded ones, in the order of the preset
+    fn mods(&self) -> Result<Selection, CommandFailure> {
+        let setup = self.setup("mods")?;
+        if setup.preset.is_none() {
+            return Err(CommandFailure::Unsupported {
+                action: "mods without a Workshop preset".to_owned(),
+            });
+        }
+        Ok(Selection {
+            available: setup
+                .preset_mods()?
+                .into_iter()
+                .map(|item| item.name)
+                .collect(),
+            selected: setup
+                .selected_mods()?
+                .into_iter()
+                .map(|item| item.name)
+                .collect(),
+        })
+    }
+
+    /// Sets the loaded mods by their names, the preset decides the load order
+    fn select_mods(&mut self, mods: Vec<String>) -> Result<(), CommandFailure> {
+        let available = self.mods()?.available;
+        if let Some(missing) = mods.iter().find(|name| !available.contains(name)) {
+            return Err(CommandFailure::Rejected {
+                reason: format!("{missing:?} isn't a mod of the preset"),
+            });
+        }
+        let mut setup = self.setup("mods")?.clone();
+        setup.mods = Some(mods);
+        setup.save_selection(self.working_dir())?;
+        self.setup = Some(setup);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{preset::tests::PRESET, Server, Setup};
+    use crate::hostable_servers::{
+        a2s::tests::responder, lifecycle::State, supervisor::Supervisor, HostableServer, Settings,
+    };
+    use std::fs;
+
+    #[test]
+    fn reads_players_mission_and_slots_from_the_query_port() {
+        let address = responder(|_| false);
+        let mut server = Server::with_backend(
+            Settings::new("arma_query"),
+            Box::new(Supervisor::new(["sleep", "30"])),
+        )
+        .with_query(address.to_string());
+
+        server.start().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Running);
+        // the player that is still connecting is only counted
+        assert_eq!(server.players.count, 2);
+        assert_eq!(server.players.name_tags, ["Nacor", "Miller"]);
+        assert_eq!(server.mission.as_deref(), Some("Wasteland"));
+        assert_eq!(server.map.as_deref(), Some("Altis"));
+        assert_eq!(server.slots, 40);
+        let status = server.to_json().unwrap();
+        assert!(status.contains(r#""mission":"Wasteland""#), "{status}");
+
+        server.stop().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Stopped);
+        assert_eq!(server.players.count, 0);
+        assert_eq!((server.mission, server.slots), (None, 0));
+    }
+
+    #[test]
+    fn picked_missions_and_mods_outlive_the_server() {
+        let dir = std::env::temp_dir().join("web_server_arma_selection");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("mpmissions")).unwrap();
+        fs::write(dir.join("mpmissions/Wasteland.Altis.pbo"), "").unwrap();
+        fs::write(dir.join("mpmissions/KotH.Tanoa.pbo"), "").unwrap();
+        fs::write(dir.join("preset.html"), PRESET).unwrap();
+        let mut setup = Setup::new("Test");
+        setup.server_cfg.missions = vec!["Wasteland.Altis".to_owned()];
+        setup.preset = Some(dir.join("preset.html"));
+        let server = || {
+            let mut settings = Settings::new("arma_selection");
+            settings.working_dir = Some(dir.clone());
+            Server::with_backend(settings, Box::new(Supervisor::new(["sleep", "30"])))
+                .with_setup(setup.clone())
+        };
+
+        let mut first = server();
+        first
+            .select_missions(vec!["KotH.Tanoa".to_owned()])
+            .unwrap();
+        first.select_mods(vec!["CBA_A3".to_owned()]).unwrap();
+        assert!(dir.join("server.selection.json").exists());
+
+        let second = server();
+        assert_eq!(second.missions().unwrap().selected, ["KotH.Tanoa"]);
+        assert_eq!(second.mods().unwrap().selected, ["CBA_A3"]);
+    }
+}
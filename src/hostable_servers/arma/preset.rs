@@ -0,0 +1,159 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/arma/preset.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Reads the mod presets the Arma 3 Launcher exports as HTML
+//! =============================================================
+
+use super::CommandFailure;
+use regex::Regex;
+use std::{fs, path::Path, sync::LazyLock};
+
+/// One mod row of a preset, from `<tr data-type="ModContainer">` to its `</tr>`
+static MOD_ROW: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<tr[^>]*data-type="ModContainer"[^>]*>(.*?)</tr>"#)
+        .expect("the mod row pattern is valid")
+});
+/// Name of the mod inside of a row
+static DISPLAY_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<td[^>]*data-type="DisplayName"[^>]*>(.*?)</td>"#)
+        .expect("the display name pattern is valid")
+});
+/// Workshop link inside of a row, `https://steamcommunity.com/sharedfiles/filedetails/?id=450814997`
+static WORKSHOP_ID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"steamcommunity\.com/sharedfiles/filedetails/\?id=(\d+)")
+        .expect("the workshop link pattern is valid")
+});
+
+/// Steam Workshop mod of a preset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mod {
+    /// Workshop item id, the name of its folder in `steamapps/workshop/content/107410`
+    pub id: u64,
+    /// Name shown in the launcher, like `CBA_A3`
+    pub name: String,
+}
+
+/// Returns the Workshop mods of the preset `html`, in the load order of the launcher
+///
+/// Local mods have no Workshop id and are left out
+///
+/// # Example
+/// ```
+/// use web_server::hostable_servers::arma::preset;
+///
+/// let html = r#"<tr data-type="ModContainer">
+///   <td data-type="DisplayName">CBA_A3</td>
+///   <td><a href="https://steamcommunity.com/sharedfiles/filedetails/?id=450814997" data-type="Link">link</a></td>
+/// </tr>"#;
+/// let mods = preset::parse(html);
+/// assert_eq!(mods[0].id, 450_814_997);
+/// assert_eq!(mods[0].name, "CBA_A3");
+/// ```
+#[must_use]
+pub fn parse(html: &str) -> Vec<Mod> {
+    MOD_ROW
+        .captures_iter(html)
+        .filter_map(|row| {
+            let row = row.get(1)?.as_str();
+            let id: u64 = WORKSHOP_ID.captures(row)?.get(1)?.as_str().parse().ok()?;
+            let name = DISPLAY_NAME
+                .captures(row)
+                .and_then(|name| name.get(1))
+                .map_or_else(|| id.to_string(), |name| unescape(name.as_str().trim()));
+            Some(Mod { id, name })
+        })
+        .collect()
+}
+
+/// Reads and parses the preset at `path`
+///
+/// # Errors
+/// Returns [`CommandFailure::Unreadable`] if the file can't be read
+pub fn load(path: &Path) -> Result<Vec<Mod>, CommandFailure> {
+    fs::read_to_string(path)
+        .map(|html| parse(&html))
+        .map_err(|e| CommandFailure::Unreadable {
+            file: path.display().to_string(),
+            reason: e.to_string(),
+        })
+}
+
+/// Replaces the entities the launcher writes into names
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{parse, Mod};
+
+    /// Preset as the Arma 3 Launcher exports it, shortened
+    pub const PRESET: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html>
+  <head>
+    <meta name="arma:Type" content="preset" />
+    <meta name="arma:PresetName" content="Wasteland" />
+  </head>
+  <body>
+    <div class="mod-list">
+      <table>
+        <tr data-type="ModContainer">
+          <td data-type="DisplayName">CBA_A3</td>
+          <td>
+            <span class="from-steam">Steam</span>
+          </td>
+          <td>
+            <a href="https://steamcommunity.com/sharedfiles/filedetails/?id=450814997" data-type="Link">https://steamcommunity.com/sharedfiles/filedetails/?id=450814997</a>
+          </td>
+        </tr>
+        <tr data-type="ModContainer">
+          <td data-type="DisplayName">Enhanced Movement &amp; Rework</td>
+          <td>
+            <span class="from-steam">Steam</span>
+          </td>
+          <td>
+            <a href="https://steamcommunity.com/sharedfiles/filedetails/?id=2034363662" data-type="Link">https://steamcommunity.com/sharedfiles/filedetails/?id=2034363662</a>
+          </td>
+        </tr>
+        <tr data-type="ModContainer">
+          <td data-type="DisplayName">my_local_mod</td>
+          <td>
+            <span class="from-local">Local</span>
+          </td>
+          <td>
+            <span data-type="Link">local:@my_local_mod</span>
+          </td>
+        </tr>
+      </table>
+    </div>
+  </body>
+</html>"#;
+
+    #[test]
+    fn reads_the_workshop_mods_in_order() {
+        assert_eq!(
+            parse(PRESET),
+            [
+                Mod {
+                    id: 450_814_997,
+                    name: "CBA_A3".to_owned(),
+                },
+                Mod {
+                    id: 2_034_363_662,
+                    name: "Enhanced Movement & Rework".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn anything_else_has_no_mods() {
+        assert!(parse("").is_empty());
+        assert!(parse("<html><body>no mods here</body></html>").is_empty());
+    }
+}
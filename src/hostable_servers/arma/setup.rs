@@ -0,0 +1,347 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/arma/setup.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Writes the server.cfg and builds the command line of an Arma server
+//! =============================================================
+
+use super::{
+    preset::{self, Mod},
+    CommandFailure,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Directory Arma looks for missions in, inside of its own directory
+const MISSION_DIR: &str = "mpmissions";
+
+/// The parts of `server.cfg` the web server manages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerCfg {
+    /// Name in the server browser
+    pub hostname: String,
+    /// Password to join, everybody may join without one
+    pub password: Option<String>,
+    /// Player slots
+    pub max_players: u32,
+    /// Mission rotation, templates like `Wasteland.Altis`
+    pub missions: Vec<String>,
+    /// Difficulty every mission is played on, like `Regular`
+    pub difficulty: String,
+    /// Lines added as they are, like `passwordAdmin = "hunter2";`
+    pub extra: Vec<String>,
+}
+impl ServerCfg {
+    /// Returns the content of the `server.cfg`
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::hostable_servers::arma::setup::ServerCfg;
+    ///
+    /// let server_cfg = ServerCfg {
+    ///     hostname: "Nacor's \"Wasteland\"".to_owned(),
+    ///     password: None,
+    ///     max_players: 40,
+    ///     missions: vec!["Wasteland.Altis".to_owned()],
+    ///     difficulty: "Regular".to_owned(),
+    ///     extra: Vec::new(),
+    /// };
+    /// let text = server_cfg.render();
+    /// assert!(text.contains("hostname = \"Nacor's \"\"Wasteland\"\"\";\n"));
+    /// assert!(text.contains("maxPlayers = 40;\n"));
+    /// assert!(text.contains("        template = \"Wasteland.Altis\";\n"));
+    /// ```
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut text =
+            String::from("// Written by the web server before every start, edits are lost\n");
+        let _ = writeln!(text, "hostname = {};", quote(&self.hostname));
+        if let Some(password) = &self.password {
+            let _ = writeln!(text, "password = {};", quote(password));
+        }
+        let _ = writeln!(text, "maxPlayers = {};", self.max_players);
+        for line in &self.extra {
+            let _ = writeln!(text, "{line}");
+        }
+
+        text.push_str("class Missions\n{\n");
+        for (i, mission) in self.missions.iter().enumerate() {
+            let _ = writeln!(text, "    class Mission{}\n    {{", i + 1);
+            let _ = writeln!(text, "        template = {};", quote(mission));
+            let _ = writeln!(text, "        difficulty = {};", quote(&self.difficulty));
+            text.push_str("    };\n");
+        }
+        text.push_str("};\n");
+        text
+    }
+}
+
+/// Missions and mods picked on the web page, see [`Setup::save_selection`]
+#[derive(Serialize, Deserialize)]
+struct SavedSelection {
+    /// Mission rotation
+    missions: Vec<String>,
+    /// Loaded mods, all of the preset if `None`
+    mods: Option<Vec<String>>,
+}
+
+/// Everything an [`super::Server`] writes and passes to Arma before it starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setup {
+    /// Content of the `server.cfg`
+    pub server_cfg: ServerCfg,
+    /// Where the `server.cfg` is written, relative to the working directory
+    pub config_file: PathBuf,
+    /// Server program, relative to the working directory
+    pub executable: String,
+    /// Profile the server saves its settings and logs under
+    pub profile: String,
+    /// Game port, the Steam query port is the one after it
+    pub port: u16,
+    /// Mod preset exported by the Arma 3 Launcher
+    pub preset: Option<PathBuf>,
+    /// Names of the mods of the preset that are loaded, all of them if `None`
+    pub mods: Option<Vec<String>>,
+    /// Directory steamcmd downloads the Workshop items to, relative to the working directory
+    pub workshop_dir: PathBuf,
+    /// More parameters, like `-autoInit`
+    pub parameters: Vec<String>,
+}
+impl Setup {
+    /// Default setup for a server called `hostname`, without missions or mods
+    #[must_use]
+    pub fn new(hostname: &str) -> Self {
+        Self {
+            server_cfg: ServerCfg {
+                hostname: hostname.to_owned(),
+                password: None,
+                max_players: 32,
+                missions: Vec::new(),
+                difficulty: "Regular".to_owned(),
+                extra: Vec::new(),
+            },
+            config_file: PathBuf::from("server.cfg"),
+            executable: "./arma3server_x64".to_owned(),
+            profile: "server".to_owned(),
+            port: 2302,
+            preset: None,
+            mods: None,
+            workshop_dir: PathBuf::from("steamapps/workshop/content/107410"),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Writes the `server.cfg` into `working_dir`
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unwritable`] if the file can't be written
+    pub fn write_config(&self, working_dir: &Path) -> Result<(), CommandFailure> {
+        let path = working_dir.join(&self.config_file);
+        fs::write(&path, self.server_cfg.render()).map_err(|e| CommandFailure::Unwritable {
+            file: path.display().to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Returns where the selection is kept, `server.selection.json` next to `server.cfg`
+    fn selection_file(&self, working_dir: &Path) -> PathBuf {
+        working_dir.join(self.config_file.with_extension("selection.json"))
+    }
+
+    /// Keeps the mission rotation and the loaded mods in a file next to the `server.cfg`
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unwritable`] if the file can't be written
+    pub fn save_selection(&self, working_dir: &Path) -> Result<(), CommandFailure> {
+        let path = self.selection_file(working_dir);
+        let selection = SavedSelection {
+            missions: self.server_cfg.missions.clone(),
+            mods: self.mods.clone(),
+        };
+        serde_json::to_string_pretty(&selection)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()))
+            .map_err(|reason| CommandFailure::Unwritable {
+                file: path.display().to_string(),
+                reason,
+            })
+    }
+
+    /// Replaces the missions and mods of the config with the ones [`Setup::save_selection`] kept
+    ///
+    /// They stay as they are if nothing was kept yet
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unreadable`] if the file can't be read or is broken
+    pub fn load_selection(&mut self, working_dir: &Path) -> Result<(), CommandFailure> {
+        let path = self.selection_file(working_dir);
+        let selection = match fs::read_to_string(&path) {
+            Ok(json) => Ok(json),
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+        .and_then(|json| serde_json::from_str::<SavedSelection>(&json).map_err(|e| e.to_string()));
+        let selection = selection.map_err(|reason| CommandFailure::Unreadable {
+            file: path.display().to_string(),
+            reason,
+        })?;
+        self.server_cfg.missions = selection.missions;
+        self.mods = selection.mods;
+        Ok(())
+    }
+
+    /// Returns the command line loading `mods`
+    #[must_use]
+    pub fn command(&self, mods: &[Mod]) -> Vec<String> {
+        let mut command = vec![
+            self.executable.clone(),
+            format!("-name={}", self.profile),
+            format!("-config={}", self.config_file.display()),
+            format!("-port={}", self.port),
+        ];
+        if !mods.is_empty() {
+            let folders: Vec<String> = mods
+                .iter()
+                .map(|item| {
+                    self.workshop_dir
+                        .join(item.id.to_string())
+                        .display()
+                        .to_string()
+                })
+                .collect();
+            command.push(format!("-mod={}", folders.join(";")));
+        }
+        command.extend(self.parameters.iter().cloned());
+        command
+    }
+
+    /// Returns the missions in the `mpmissions` directory of `working_dir`, packed or not
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unreadable`] if the directory can't be read
+    pub fn available_missions(working_dir: &Path) -> Result<Vec<String>, CommandFailure> {
+        let directory = working_dir.join(MISSION_DIR);
+        let unreadable = |e: std::io::Error| CommandFailure::Unreadable {
+            file: directory.display().to_string(),
+            reason: e.to_string(),
+        };
+        let mut missions = Vec::new();
+        for entry in fs::read_dir(&directory).map_err(unreadable)? {
+            let path = entry.map_err(unreadable)?.path();
+            // `Wasteland.Altis.pbo` or the unpacked `Wasteland.Altis/`
+            let mission = if path.is_dir() {
+                path.file_name()
+            } else if path.extension().is_some_and(|extension| extension == "pbo") {
+                path.file_stem()
+            } else {
+                None
+            };
+            if let Some(mission) = mission {
+                missions.push(mission.to_string_lossy().into_owned());
+            }
+        }
+        missions.sort_unstable();
+        missions.dedup();
+        Ok(missions)
+    }
+
+    /// Returns every Workshop mod of the preset, none without a preset
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unreadable`] if the preset can't be read
+    pub fn preset_mods(&self) -> Result<Vec<Mod>, CommandFailure> {
+        self.preset
+            .as_deref()
+            .map_or_else(|| Ok(Vec::new()), preset::load)
+    }
+
+    /// Returns the mods of the preset that are loaded, in the order of the preset
+    ///
+    /// Selected mods that aren't in the preset anymore are left out
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unreadable`] if the preset can't be read
+    pub fn selected_mods(&self) -> Result<Vec<Mod>, CommandFailure> {
+        let mut mods = self.preset_mods()?;
+        if let Some(selected) = &self.mods {
+            mods.retain(|item| selected.contains(&item.name));
+        }
+        Ok(mods)
+    }
+}
+
+/// Returns `text` as a config string, quotes are doubled
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mod, Setup};
+
+    #[test]
+    fn mods_are_loaded_from_the_workshop_dir() {
+        let mut setup = Setup::new("Test");
+        setup.parameters = vec!["-autoInit".to_owned()];
+        let mods = [
+            Mod {
+                id: 450_814_997,
+                name: "CBA_A3".to_owned(),
+            },
+            Mod {
+                id: 463_939_057,
+                name: "ace".to_owned(),
+            },
+        ];
+
+        assert_eq!(
+            setup.command(&mods),
+            [
+                "./arma3server_x64",
+                "-name=server",
+                "-config=server.cfg",
+                "-port=2302",
+                "-mod=steamapps/workshop/content/107410/450814997;steamapps/workshop/content/107410/463939057",
+                "-autoInit",
+            ]
+        );
+        assert!(!setup
+            .command(&[])
+            .iter()
+            .any(|arg| arg.starts_with("-mod=")));
+    }
+
+    #[test]
+    fn every_mission_of_the_rotation_gets_a_class() {
+        let mut setup = Setup::new("Test");
+        setup.server_cfg.password = Some("secret".to_owned());
+        setup.server_cfg.missions = vec!["Wasteland.Altis".to_owned(), "KotH.Tanoa".to_owned()];
+
+        assert_eq!(
+            setup.server_cfg.render(),
+            "// Written by the web server before every start, edits are lost
+hostname = \"Test\";
+password = \"secret\";
+maxPlayers = 32;
+class Missions
+{
+    class Mission1
+    {
+        template = \"Wasteland.Altis\";
+        difficulty = \"Regular\";
+    };
+    class Mission2
+    {
+        template = \"KotH.Tanoa\";
+        difficulty = \"Regular\";
+    };
+};
+"
+        );
+    }
+}
@@ -0,0 +1,722 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/backend.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Keeps the process of a server running: screen, tmux, systemd or the web server itself
+//! =============================================================
+
+use super::{
+    get_screen_sessions,
+    process::{CommandFailure, Process},
+    supervisor::{Supervisor, SupervisorStatus},
+    Settings,
+};
+use std::{
+    fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often a stopping server is checked on
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Whatever runs the process of a server and connects it to a console
+///
+/// Every method gets the [`Settings`] of the server, for its path, working
+/// directory, timeouts and cancel token
+pub trait ProcessBackend: Send {
+    /// Name used in the config and the status, like `screen`
+    fn name(&self) -> &'static str;
+    /// Starts the process
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure`] if it couldn't be started
+    fn launch(&mut self, settings: &Settings) -> Result<(), CommandFailure>;
+    /// Returns true if the process is running
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure`] if that can't be found out
+    fn is_alive(&self, settings: &Settings) -> Result<bool, CommandFailure>;
+    /// Writes `line` to the console of the process
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] if the backend has no console
+    fn send_input(&mut self, settings: &Settings, line: &str) -> Result<(), CommandFailure>;
+    /// Returns up to `lines` of the latest output, oldest first
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure`] if the output can't be read
+    fn read_output(&self, settings: &Settings, lines: usize)
+        -> Result<Vec<String>, CommandFailure>;
+    /// Stops the process and waits for it to be gone
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure`] if it couldn't be stopped
+    fn terminate(&mut self, settings: &Settings) -> Result<(), CommandFailure>;
+    /// Returns the process id and how the last process ended, if the backend knows
+    fn process(&self) -> Option<SupervisorStatus> {
+        None
+    }
+    /// Replaces the command the next [`ProcessBackend::launch`] runs, for servers building it
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] if the backend doesn't run a command
+    fn set_command(&mut self, _command: Vec<String>) -> Result<(), CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: format!("commands with the {} backend", self.name()),
+        })
+    }
+}
+
+/// `start.sh` and `stop.sh`, which have to create and end the screen session `{path}_server`
+///
+/// The default, this is how the servers in this repository are controlled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scripts;
+
+impl ProcessBackend for Scripts {
+    fn name(&self) -> &'static str {
+        "scripts"
+    }
+
+    fn launch(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        settings
+            .run(&settings.scripts.start, settings.timeouts.start)
+            .map(drop)
+    }
+
+    fn is_alive(&self, settings: &Settings) -> Result<bool, CommandFailure> {
+        Ok(screen_session_exists(settings))
+    }
+
+    fn send_input(&mut self, settings: &Settings, line: &str) -> Result<(), CommandFailure> {
+        screen_stuff(settings, line)
+    }
+
+    fn read_output(
+        &self,
+        settings: &Settings,
+        lines: usize,
+    ) -> Result<Vec<String>, CommandFailure> {
+        screen_hardcopy(settings, lines)
+    }
+
+    fn terminate(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        settings.run(&settings.scripts.stop, settings.timeouts.stop)?;
+        // stop.sh usually only asks the server to stop, without waiting for it
+        if wait_until_gone(self, settings)? {
+            return Ok(());
+        }
+        eprintln!(
+            "\x1b[31m{}: still running {}s after stop.sh, quitting the screen session\x1b[39m",
+            settings.path,
+            settings.timeouts.stop.as_secs()
+        );
+        screen_quit(settings)
+    }
+}
+
+/// A detached screen session `{path}_server` running `command`
+#[derive(Debug, Clone)]
+pub struct Screen {
+    /// Program followed by its arguments
+    pub command: Vec<String>,
+    /// Console line that makes the server shut down, the session is quit without one
+    pub stop_command: Option<String>,
+    /// File screen logs the session to
+    pub log_file: Option<PathBuf>,
+}
+
+impl ProcessBackend for Screen {
+    fn name(&self) -> &'static str {
+        "screen"
+    }
+
+    fn launch(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        let mut screen = tool(settings, "screen", settings.timeouts.start);
+        if let Some(log_file) = &self.log_file {
+            screen = screen.arg("-L").arg("-Logfile").arg(log_file);
+        }
+        screen
+            .args(["-S", &session(settings), "-d", "-m"])
+            .args(&self.command)
+            .run()
+            .map(drop)
+    }
+
+    fn is_alive(&self, settings: &Settings) -> Result<bool, CommandFailure> {
+        Ok(screen_session_exists(settings))
+    }
+
+    fn send_input(&mut self, settings: &Settings, line: &str) -> Result<(), CommandFailure> {
+        screen_stuff(settings, line)
+    }
+
+    fn read_output(
+        &self,
+        settings: &Settings,
+        lines: usize,
+    ) -> Result<Vec<String>, CommandFailure> {
+        screen_hardcopy(settings, lines)
+    }
+
+    fn terminate(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        let stop_command = self.stop_command.clone();
+        stop_gracefully(self, settings, stop_command.as_deref(), screen_quit)
+    }
+
+    fn set_command(&mut self, command: Vec<String>) -> Result<(), CommandFailure> {
+        self.command = command;
+        Ok(())
+    }
+}
+
+/// A detached tmux session `{path}_server` running `command`
+#[derive(Debug, Clone)]
+pub struct Tmux {
+    /// Program followed by its arguments
+    pub command: Vec<String>,
+    /// Console line that makes the server shut down, the session is killed without one
+    pub stop_command: Option<String>,
+}
+
+impl ProcessBackend for Tmux {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    fn launch(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        let mut tmux = tool(settings, "tmux", settings.timeouts.start).args([
+            "new-session",
+            "-d",
+            "-s",
+            &session(settings),
+        ]);
+        // the tmux server would start it in its own directory otherwise
+        if let Some(working_dir) = &settings.working_dir {
+            tmux = tmux.arg("-c").arg(working_dir);
+        }
+        tmux.arg("--").args(&self.command).run().map(drop)
+    }
+
+    fn is_alive(&self, settings: &Settings) -> Result<bool, CommandFailure> {
+        // has-session exits with 1 if the session doesn't exist
+        match tool(settings, "tmux", settings.timeouts.status)
+            .args(["has-session", "-t", &format!("={}", session(settings))])
+            .run()
+        {
+            Ok(_) => Ok(true),
+            Err(CommandFailure::NonZeroExit(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn send_input(&mut self, settings: &Settings, line: &str) -> Result<(), CommandFailure> {
+        let target = format!("={}:", session(settings));
+        tool(settings, "tmux", settings.timeouts.status)
+            .args(["send-keys", "-t", &target, "-l", "--", line])
+            .run()?;
+        tool(settings, "tmux", settings.timeouts.status)
+            .args(["send-keys", "-t", &target, "Enter"])
+            .run()
+            .map(drop)
+    }
+
+    fn read_output(
+        &self,
+        settings: &Settings,
+        lines: usize,
+    ) -> Result<Vec<String>, CommandFailure> {
+        let output = tool(settings, "tmux", settings.timeouts.status)
+            .args(["capture-pane", "-p", "-J", "-t"])
+            .arg(format!("={}:", session(settings)))
+            .args(["-S", &format!("-{lines}")])
+            .run()?;
+        Ok(last_lines(&output.stdout, lines))
+    }
+
+    fn terminate(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        let stop_command = self.stop_command.clone();
+        stop_gracefully(self, settings, stop_command.as_deref(), |settings| {
+            tool(settings, "tmux", settings.timeouts.stop)
+                .args(["kill-session", "-t", &format!("={}", session(settings))])
+                .run()
+                .map(drop)
+        })
+    }
+
+    fn set_command(&mut self, command: Vec<String>) -> Result<(), CommandFailure> {
+        self.command = command;
+        Ok(())
+    }
+}
+
+/// A `systemctl --user` unit, systemd keeps the process and its log
+#[derive(Debug, Clone)]
+pub struct Systemd {
+    /// Name of the unit, like `minecraft.service`
+    pub unit: String,
+}
+
+impl ProcessBackend for Systemd {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn launch(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        tool(settings, "systemctl", settings.timeouts.start)
+            .args(["--user", "start", &self.unit])
+            .run()
+            .map(drop)
+    }
+
+    fn is_alive(&self, settings: &Settings) -> Result<bool, CommandFailure> {
+        // is-active exits with 3 for inactive and failed units
+        match tool(settings, "systemctl", settings.timeouts.status)
+            .args(["--user", "is-active", "--quiet", &self.unit])
+            .run()
+        {
+            Ok(_) => Ok(true),
+            Err(CommandFailure::NonZeroExit(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn send_input(&mut self, _settings: &Settings, _line: &str) -> Result<(), CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "console input for systemd units".to_owned(),
+        })
+    }
+
+    fn read_output(
+        &self,
+        settings: &Settings,
+        lines: usize,
+    ) -> Result<Vec<String>, CommandFailure> {
+        let output = tool(settings, "journalctl", settings.timeouts.status)
+            .args([
+                "--user",
+                "--unit",
+                &self.unit,
+                "--output",
+                "cat",
+                "--no-pager",
+            ])
+            .args(["--lines", &lines.to_string()])
+            .run()?;
+        Ok(last_lines(&output.stdout, lines))
+    }
+
+    fn terminate(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        tool(settings, "systemctl", settings.timeouts.stop)
+            .args(["--user", "stop", &self.unit])
+            .run()
+            .map(drop)
+    }
+}
+
+/// Spawned by the web server itself, see [`Supervisor`]
+impl ProcessBackend for Supervisor {
+    fn name(&self) -> &'static str {
+        "direct"
+    }
+
+    fn launch(&mut self, _settings: &Settings) -> Result<(), CommandFailure> {
+        self.start().map(drop)
+    }
+
+    fn is_alive(&self, _settings: &Settings) -> Result<bool, CommandFailure> {
+        Ok(self.status().is_running())
+    }
+
+    fn send_input(&mut self, _settings: &Settings, line: &str) -> Result<(), CommandFailure> {
+        self.send(line)
+    }
+
+    fn read_output(
+        &self,
+        _settings: &Settings,
+        lines: usize,
+    ) -> Result<Vec<String>, CommandFailure> {
+        let mut output = self.output();
+        output.drain(..output.len().saturating_sub(lines));
+        Ok(output)
+    }
+
+    fn terminate(&mut self, settings: &Settings) -> Result<(), CommandFailure> {
+        self.stop(settings.timeouts.stop, &settings.cancel)
+    }
+
+    fn process(&self) -> Option<SupervisorStatus> {
+        Some(self.status())
+    }
+
+    fn set_command(&mut self, command: Vec<String>) -> Result<(), CommandFailure> {
+        self.set_command(command);
+        Ok(())
+    }
+}
+
+/// Name of the screen or tmux session of a server
+fn session(settings: &Settings) -> String {
+    format!("{}_server", settings.path)
+}
+
+/// Returns `program` set up to run in the working directory, killed after `timeout`
+fn tool(settings: &Settings, program: &str, timeout: Duration) -> Process {
+    let process = Process::new(program).timeout(timeout);
+    match &settings.working_dir {
+        Some(working_dir) => process.current_dir(working_dir),
+        None => process,
+    }
+}
+
+/// Returns the last `lines` lines of `text`, without the empty ones at the end
+fn last_lines(text: &str, lines: usize) -> Vec<String> {
+    let text = text.trim_end();
+    let all: Vec<&str> = if text.is_empty() {
+        Vec::new()
+    } else {
+        text.lines().collect()
+    };
+    all[all.len().saturating_sub(lines)..]
+        .iter()
+        .map(|line| (*line).to_owned())
+        .collect()
+}
+
+/// Returns true if `screen -list` shows the session of the server
+fn screen_session_exists(settings: &Settings) -> bool {
+    get_screen_sessions().contains(&format!(".{}\t", session(settings)))
+}
+
+/// Ends the screen session of the server and whatever runs in it
+fn screen_quit(settings: &Settings) -> Result<(), CommandFailure> {
+    tool(settings, "screen", settings.timeouts.stop)
+        .args(["-S", &session(settings), "-X", "quit"])
+        .run()
+        .map(drop)
+}
+
+/// Types `line` and enter into the screen session of the server
+fn screen_stuff(settings: &Settings, line: &str) -> Result<(), CommandFailure> {
+    tool(settings, "screen", settings.timeouts.status)
+        .args(["-S", &session(settings), "-p", "0", "-X", "stuff"])
+        .arg(format!("{}\r", screen_escape(line)))
+        .run()
+        .map(drop)
+}
+
+/// Escapes `^` and `\` in `line`, `stuff` would type `^M` as enter and run a second command
+fn screen_escape(line: &str) -> String {
+    line.chars()
+        .fold(String::with_capacity(line.len()), |mut escaped, c| {
+            if matches!(c, '^' | '\\') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+}
+
+/// Reads the window and scrollback of the screen session of the server
+fn screen_hardcopy(settings: &Settings, lines: usize) -> Result<Vec<String>, CommandFailure> {
+    let session = session(settings);
+    let copy = std::env::temp_dir().join(format!("{session}.hardcopy"));
+    tool(settings, "screen", settings.timeouts.status)
+        .args(["-S", &session, "-p", "0", "-X", "hardcopy", "-h"])
+        .arg(&copy)
+        .run()?;
+    // screen writes the file after the command returned
+    let deadline = Instant::now() + settings.timeouts.status;
+    let text = loop {
+        match fs::read(&copy) {
+            Ok(text) => break text,
+            Err(_) if Instant::now() < deadline => thread::sleep(STOP_POLL_INTERVAL),
+            Err(e) => {
+                return Err(CommandFailure::Unreadable {
+                    file: copy.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    };
+    let _ = fs::remove_file(&copy);
+    Ok(last_lines(&String::from_utf8_lossy(&text), lines))
+}
+
+/// Sends `stop_command` to the console and waits for the process to go away
+///
+/// Runs `force` if there is no stop command, or the process is still there after the stop timeout
+fn stop_gracefully<B: ProcessBackend>(
+    backend: &mut B,
+    settings: &Settings,
+    stop_command: Option<&str>,
+    force: impl FnOnce(&Settings) -> Result<(), CommandFailure>,
+) -> Result<(), CommandFailure> {
+    if !backend.is_alive(settings)? {
+        return Ok(());
+    }
+    if let Some(line) = stop_command {
+        backend.send_input(settings, line)?;
+        if wait_until_gone(backend, settings)? {
+            return Ok(());
+        }
+        eprintln!(
+            "\x1b[31m{}: still running {}s after {line:?}, ending it\x1b[39m",
+            settings.path,
+            settings.timeouts.stop.as_secs()
+        );
+    }
+    force(settings)
+}
+
+/// Waits for the process to go away, false after the stop timeout or on cancel
+pub(super) fn wait_until_gone(
+    backend: &(impl ProcessBackend + ?Sized),
+    settings: &Settings,
+) -> Result<bool, CommandFailure> {
+    let running = settings.cancel.enter();
+    let deadline = Instant::now() + settings.timeouts.stop;
+    while backend.is_alive(settings)? {
+        if Instant::now() >= deadline || running.is_cancelled() {
+            return Ok(false);
+        }
+        thread::sleep(STOP_POLL_INTERVAL);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{screen_escape, ProcessBackend};
+    use crate::hostable_servers::{
+        lifecycle::State, probe::Probe, CommandFailure, GeneralBashServer, HostableServer, Settings,
+    };
+    use std::{
+        env, fs,
+        net::TcpListener,
+        os::unix::fs::PermissionsExt,
+        path::Path,
+        sync::{Arc, Mutex, MutexGuard},
+    };
+
+    /// What the fake process is doing
+    #[derive(Default)]
+    struct World {
+        /// Whether the process runs
+        alive: bool,
+        /// Number of launches
+        launches: usize,
+        /// Lines written to the console
+        input: Vec<String>,
+        /// Makes the next launch fail
+        broken: bool,
+    }
+
+    /// Backend without any process, the test changes the [`World`] behind its back
+    #[derive(Clone, Default)]
+    struct Fake(Arc<Mutex<World>>);
+    impl Fake {
+        /// Locks the world
+        fn world(&self) -> MutexGuard<'_, World> {
+            self.0.lock().expect("a test panicked")
+        }
+    }
+
+    impl ProcessBackend for Fake {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn launch(&mut self, _settings: &Settings) -> Result<(), CommandFailure> {
+            let mut world = self.world();
+            if world.broken {
+                return Err(CommandFailure::SpawnFailed {
+                    program: "fake".to_owned(),
+                    reason: "broken".to_owned(),
+                });
+            }
+            world.alive = true;
+            world.launches += 1;
+            drop(world);
+            Ok(())
+        }
+
+        fn is_alive(&self, _settings: &Settings) -> Result<bool, CommandFailure> {
+            Ok(self.world().alive)
+        }
+
+        fn send_input(&mut self, _settings: &Settings, line: &str) -> Result<(), CommandFailure> {
+            let mut world = self.world();
+            if !world.alive {
+                return Err(CommandFailure::NotRunning {
+                    program: "fake".to_owned(),
+                });
+            }
+            world.input.push(line.to_owned());
+            drop(world);
+            Ok(())
+        }
+
+        fn read_output(
+            &self,
+            _settings: &Settings,
+            lines: usize,
+        ) -> Result<Vec<String>, CommandFailure> {
+            let world = self.world();
+            let skip = world.input.len().saturating_sub(lines);
+            Ok(world.input[skip..].to_vec())
+        }
+
+        fn terminate(&mut self, _settings: &Settings) -> Result<(), CommandFailure> {
+            self.world().alive = false;
+            Ok(())
+        }
+    }
+
+    /// A server run by a fake backend, and the backend to play with
+    fn server(settings: Settings) -> (GeneralBashServer, Fake) {
+        let fake = Fake::default();
+        let server = GeneralBashServer::with_backend(settings, Box::new(fake.clone()));
+        (server, fake)
+    }
+
+    #[test]
+    fn start_console_and_stop() {
+        let (mut server, fake) = server(Settings::new("fake"));
+        server.update_status().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Stopped);
+
+        server.start().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Running);
+        assert!(server.to_json().unwrap().contains(r#""backend":"fake""#));
+
+        server.send_console("say hi").unwrap();
+        assert_eq!(fake.world().input, ["say hi"]);
+        assert_eq!(server.console_output().unwrap(), ["say hi"]);
+
+        server.stop().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Stopped);
+        assert!(matches!(
+            server.send_console("say bye"),
+            Err(CommandFailure::NotRunning { .. })
+        ));
+    }
+
+    #[test]
+    fn process_going_away_is_a_crash() {
+        let (mut server, fake) = server(Settings::new("fake"));
+        server.start().unwrap();
+
+        fake.world().alive = false;
+        server.update_status().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Crashed);
+
+        server.start().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Running);
+        assert_eq!(fake.world().launches, 2);
+    }
+
+    #[test]
+    fn starting_twice_launches_once() {
+        let (mut server, fake) = server(Settings::new("fake"));
+        server.start().unwrap();
+
+        assert!(matches!(
+            server.start(),
+            Err(CommandFailure::InvalidTransition(_))
+        ));
+        assert_eq!(fake.world().launches, 1);
+    }
+
+    #[test]
+    fn failed_launch_leaves_the_state_unknown() {
+        let (mut server, fake) = server(Settings::new("fake"));
+        fake.world().broken = true;
+
+        assert!(matches!(
+            server.start(),
+            Err(CommandFailure::SpawnFailed { .. })
+        ));
+        assert_eq!(server.lifecycle.state(), State::Unknown);
+        assert!(!fake.world().alive);
+    }
+
+    #[test]
+    fn failing_probes_make_a_running_server_unhealthy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut settings = Settings::new("fake");
+        settings.probes = vec![Probe::Tcp {
+            address: listener.local_addr().unwrap(),
+        }];
+        let (mut server, _fake) = server(settings);
+
+        server.start().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Running);
+
+        drop(listener);
+        server.update_status().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Unhealthy);
+    }
+
+    #[test]
+    fn screen_input_is_typed_literally() {
+        assert_eq!(screen_escape("say hi"), "say hi");
+        // `^M` would be enter and run `op Evil` as a second command
+        assert_eq!(screen_escape("ban x ^Mop Evil"), "ban x \\^Mop Evil");
+        assert_eq!(screen_escape("say \\015"), "say \\\\015");
+    }
+
+    /// Puts a `screen` in front of the PATH whose `-list` prints the file `sessions` in `dir`
+    fn fake_screen(dir: &Path) {
+        let screen = dir.join("screen");
+        fs::write(
+            &screen,
+            format!(
+                "#!/bin/sh\n[ \"$1\" = -list ] && cat '{}'\n",
+                dir.join("sessions").display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&screen, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = env::var("PATH").unwrap_or_default();
+        env::set_var("PATH", format!("{}:{path}", dir.display()));
+    }
+
+    #[test]
+    fn scripts_server_restarts_after_a_slow_stop() {
+        let dir = env::temp_dir().join("web_server_backend_scripts_restart");
+        fs::create_dir_all(&dir).unwrap();
+        let _ = fs::remove_file(dir.join("sessions"));
+        fake_screen(&dir);
+        fs::write(
+            dir.join("start.sh"),
+            "printf '\\t1.scripts_restart_server\\t(Detached)\\n' > sessions\n",
+        )
+        .unwrap();
+        // like `screen -X stuff stop`, the server takes a moment to go away
+        fs::write(
+            dir.join("stop.sh"),
+            "(sleep 1; rm sessions) > /dev/null 2>&1 &\n",
+        )
+        .unwrap();
+        let mut settings = Settings::new("scripts_restart");
+        settings.scripts.start = dir.join("start.sh");
+        settings.scripts.stop = dir.join("stop.sh");
+        settings.working_dir = Some(dir.clone());
+        let mut server = GeneralBashServer::with_settings(settings);
+
+        server.start().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Running);
+
+        server.restart().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Running);
+        assert!(dir.join("sessions").exists());
+
+        server.stop().unwrap();
+        assert_eq!(server.lifecycle.state(), State::Stopped);
+        assert!(!dir.join("sessions").exists());
+    }
+}
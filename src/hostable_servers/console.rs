@@ -0,0 +1,179 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/console.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Turns repeated looks at a server's console into a stream of new lines
+//! =============================================================
+
+use super::{CommandFailure, CONSOLE_LINES};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Numbered lines of a server's console, shared by everybody watching it
+///
+/// Backends can only show the last lines of output, like `screen -X hardcopy`
+/// or `tmux capture-pane` do. Every [`ConsoleFeed::update`] compares such a
+/// look with the previous one and numbers the lines that are new, so clients
+/// can ask for everything after the last line they got. The last
+/// [`CONSOLE_LINES`] lines are kept for clients that join later.
+#[derive(Debug, Default)]
+pub struct ConsoleFeed {
+    /// Recent lines, the first one has the number `first`
+    lines: VecDeque<String>,
+    /// Number of the first line in `lines`
+    first: u64,
+    /// Output seen by the last update
+    snapshot: Vec<String>,
+    /// Why the last update failed
+    error: Option<String>,
+    /// When the last update happened
+    updated: Option<Instant>,
+}
+
+impl ConsoleFeed {
+    /// Returns an empty feed
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the last update is older than `interval`
+    #[must_use]
+    pub fn is_due(&self, interval: Duration) -> bool {
+        self.updated
+            .is_none_or(|updated| updated.elapsed() >= interval)
+    }
+
+    /// Adds the lines of `output` that weren't there at the last update
+    ///
+    /// Lines the previous output ended with and `output` starts with are already known.
+    /// Without any overlap the console was cleared, like after a restart, and all of it is new
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::hostable_servers::console::ConsoleFeed;
+    ///
+    /// let mut feed = ConsoleFeed::new();
+    /// feed.update(Ok(vec!["a".to_owned(), "b".to_owned()]));
+    /// // the backend only shows the last two lines
+    /// feed.update(Ok(vec!["b".to_owned(), "c".to_owned()]));
+    ///
+    /// let (next, lines) = feed.since(0);
+    /// assert_eq!(lines, ["a", "b", "c"]);
+    /// assert_eq!(feed.since(next), (3, vec![]));
+    /// ```
+    pub fn update(&mut self, output: Result<Vec<String>, CommandFailure>) {
+        self.updated = Some(Instant::now());
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            }
+        };
+        self.error = None;
+
+        let known = (0..=self.snapshot.len().min(output.len()))
+            .rev()
+            .find(|&overlap| self.snapshot[self.snapshot.len() - overlap..] == output[..overlap])
+            .unwrap_or_default();
+        self.lines.extend(output[known..].iter().cloned());
+        let surplus = self.lines.len().saturating_sub(CONSOLE_LINES);
+        self.lines.drain(..surplus);
+        self.first += surplus as u64;
+        self.snapshot = output;
+    }
+
+    /// Returns the number of the next line and the lines from number `from` on
+    ///
+    /// Lines that were already dropped are skipped
+    #[must_use]
+    pub fn since(&self, from: u64) -> (u64, Vec<String>) {
+        let skip = usize::try_from(from.saturating_sub(self.first)).unwrap_or(usize::MAX);
+        let lines: Vec<String> = self.lines.iter().skip(skip).cloned().collect();
+        (self.first + self.lines.len() as u64, lines)
+    }
+
+    /// Returns the number of the oldest line still kept, where new clients start
+    #[must_use]
+    pub const fn first(&self) -> u64 {
+        self.first
+    }
+
+    /// Returns why the last update failed
+    #[must_use]
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConsoleFeed;
+    use crate::hostable_servers::{CommandFailure, CONSOLE_LINES};
+
+    /// Turns `lines` into the output of a backend
+    fn output(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| (*line).to_owned()).collect()
+    }
+
+    #[test]
+    fn only_new_lines_are_added() {
+        let mut feed = ConsoleFeed::new();
+        feed.update(Ok(output(&["a", "b", "c"])));
+        feed.update(Ok(output(&["b", "c", "d"])));
+        feed.update(Ok(output(&["b", "c", "d"])));
+        assert_eq!(feed.since(0), (4, output(&["a", "b", "c", "d"])));
+
+        // the longest overlap counts, repeated lines aren't lost
+        let mut feed = ConsoleFeed::new();
+        feed.update(Ok(output(&["x", "x"])));
+        feed.update(Ok(output(&["x", "x", "x"])));
+        assert_eq!(feed.since(0).0, 3);
+        assert_eq!(feed.since(2), (3, vec!["x".to_owned()]));
+    }
+
+    #[test]
+    fn cleared_consoles_are_new() {
+        let mut feed = ConsoleFeed::new();
+        feed.update(Ok(output(&["a", "b"])));
+        feed.update(Ok(output(&["c"])));
+        feed.update(Ok(output(&[])));
+        feed.update(Ok(output(&["a"])));
+        let (next, lines) = feed.since(2);
+        assert_eq!(next, 4);
+        assert_eq!(lines, ["c", "a"]);
+    }
+
+    #[test]
+    fn old_lines_are_dropped() {
+        let mut feed = ConsoleFeed::new();
+        let numbers: Vec<String> = (0..CONSOLE_LINES + 5).map(|n| n.to_string()).collect();
+        feed.update(Ok(numbers[..CONSOLE_LINES].to_vec()));
+        feed.update(Ok(numbers[5..].to_vec()));
+
+        assert_eq!(feed.first(), 5);
+        let (next, lines) = feed.since(0);
+        assert_eq!(next, (CONSOLE_LINES + 5) as u64);
+        assert_eq!(lines.len(), CONSOLE_LINES);
+        assert_eq!(lines[0], "5");
+        assert_eq!(feed.since(next), (next, Vec::new()));
+    }
+
+    #[test]
+    fn failed_updates_keep_the_lines() {
+        let mut feed = ConsoleFeed::new();
+        feed.update(Ok(output(&["a"])));
+        feed.update(Err(CommandFailure::NotRunning {
+            program: "screen".to_owned(),
+        }));
+        assert!(feed.error().is_some());
+        assert_eq!(feed.since(0).1, ["a"]);
+
+        feed.update(Ok(output(&["a", "b"])));
+        assert_eq!(feed.error(), None);
+        assert_eq!(feed.since(0).1, ["a", "b"]);
+    }
+}
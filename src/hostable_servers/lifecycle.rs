@@ -0,0 +1,453 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/lifecycle.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Tracks whether a server is starting, running, stopping or crashed
+//! =============================================================
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// Where a server is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum State {
+    /// Not running
+    Stopped,
+    /// The start script ran, the server isn't ready yet
+    Starting,
+    /// Up and ready
+    Running,
+    /// Up, but its readiness probes fail
+    Unhealthy,
+    /// The stop script ran, the server isn't gone yet
+    Stopping,
+    /// Went down without being stopped, or never became ready
+    Crashed,
+    /// Nobody knows, like before the first status update
+    Unknown,
+}
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// What a status check saw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Observation {
+    /// The process isn't there
+    Down,
+    /// The process is there, but not ready for players
+    NotReady,
+    /// The process is there and ready
+    Ready,
+    /// The check couldn't tell
+    Unknown,
+}
+impl Observation {
+    /// Combines whether the process is there with what the readiness probes said
+    ///
+    /// Without probes a running process counts as ready
+    #[must_use]
+    pub const fn from_probes(alive: bool, probes_ok: Option<bool>) -> Self {
+        match (alive, probes_ok) {
+            (false, _) => Self::Down,
+            (true, Some(false)) => Self::NotReady,
+            (true, Some(true) | None) => Self::Ready,
+        }
+    }
+}
+
+/// Something a [`Lifecycle`] was asked to do that its current [`State`] doesn't allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InvalidTransition {
+    /// State the server was in
+    pub from: State,
+    /// What was asked for, like `start` or `stop`
+    pub action: &'static str,
+}
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Can't {} a server that is {}", self.action, self.from)
+    }
+}
+impl std::error::Error for InvalidTransition {}
+
+/// State machine of a hosted server
+///
+/// Scripts only move it to [`State::Starting`] and [`State::Stopping`],
+/// whether the server is actually up is decided by [`Lifecycle::observe`]
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use web_server::hostable_servers::lifecycle::{Lifecycle, Observation, State};
+///
+/// let mut lifecycle = Lifecycle::new(Duration::from_secs(300));
+/// lifecycle.observe(Observation::Down);
+/// assert_eq!(lifecycle.state(), State::Stopped);
+///
+/// lifecycle.begin_start().unwrap();
+/// assert!(lifecycle.begin_stop().is_err(), "stopping a booting server is refused");
+/// assert!(lifecycle.begin_start().is_err(), "so is starting it twice");
+///
+/// lifecycle.observe(Observation::NotReady);
+/// assert_eq!(lifecycle.state(), State::Starting);
+/// lifecycle.observe(Observation::Ready);
+/// assert_eq!(lifecycle.state(), State::Running);
+/// lifecycle.observe(Observation::NotReady);
+/// assert_eq!(lifecycle.state(), State::Unhealthy);
+/// lifecycle.observe(Observation::Down);
+/// assert_eq!(lifecycle.state(), State::Crashed);
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub struct Lifecycle {
+    /// Current state
+    state: State,
+    /// When the current state was entered
+    since: DateTime<Utc>,
+    /// Last time every state was entered
+    transitions: BTreeMap<State, DateTime<Utc>>,
+    /// How long [`State::Starting`] may last before the server counts as crashed
+    #[serde(skip)]
+    ready_timeout: Duration,
+}
+
+impl Lifecycle {
+    /// Returns a lifecycle in [`State::Unknown`]
+    ///
+    /// A server that isn't ready `ready_timeout` after starting is [`State::Crashed`]
+    #[must_use]
+    pub fn new(ready_timeout: Duration) -> Self {
+        let now = Utc::now();
+        Self {
+            state: State::Unknown,
+            since: now,
+            transitions: BTreeMap::from([(State::Unknown, now)]),
+            ready_timeout,
+        }
+    }
+
+    /// Returns the current state
+    #[must_use]
+    pub const fn state(&self) -> State {
+        self.state
+    }
+
+    /// Returns when the current state was entered
+    #[must_use]
+    pub const fn since(&self) -> DateTime<Utc> {
+        self.since
+    }
+
+    /// Moves to [`State::Starting`] before the start script runs
+    ///
+    /// # Errors
+    /// Returns an [`InvalidTransition`] unless the server is stopped, crashed or unknown
+    pub fn begin_start(&mut self) -> Result<(), InvalidTransition> {
+        match self.state {
+            State::Stopped | State::Crashed | State::Unknown => {
+                self.enter(State::Starting);
+                Ok(())
+            }
+            from => Err(InvalidTransition {
+                from,
+                action: "start",
+            }),
+        }
+    }
+
+    /// Moves to [`State::Stopping`] before the stop script runs
+    ///
+    /// # Errors
+    /// Returns an [`InvalidTransition`] if the server is stopped or still starting
+    pub fn begin_stop(&mut self) -> Result<(), InvalidTransition> {
+        match self.state {
+            // stopping again is fine, the first stop script may not have done it
+            State::Running
+            | State::Unhealthy
+            | State::Stopping
+            | State::Crashed
+            | State::Unknown => {
+                self.enter(State::Stopping);
+                Ok(())
+            }
+            from => Err(InvalidTransition {
+                from,
+                action: "stop",
+            }),
+        }
+    }
+
+    /// Checks that the server isn't starting or stopping, its files may be changed then
+    ///
+    /// # Errors
+    /// Returns an [`InvalidTransition`] for `action` while the server is starting or stopping
+    pub const fn check_settled(&self, action: &'static str) -> Result<(), InvalidTransition> {
+        match self.state {
+            State::Starting | State::Stopping => Err(InvalidTransition {
+                from: self.state,
+                action,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records that a lifecycle script failed, nobody knows what state the server is in then
+    pub fn script_failed(&mut self) {
+        self.enter(State::Unknown);
+    }
+
+    /// Moves to [`State::Crashed`] although the process is still there, like when it stays unhealthy
+    ///
+    /// It stays crashed until it is ready again, stopped or restarted
+    pub fn crash(&mut self) {
+        if self.state != State::Crashed {
+            self.enter(State::Crashed);
+        }
+    }
+
+    /// Feeds in what a status check saw
+    pub fn observe(&mut self, observation: Observation) {
+        let next = match (self.state, observation) {
+            (State::Starting, Observation::Ready) => State::Running,
+            (State::Starting, _) if self.since_elapsed() > self.ready_timeout => State::Crashed,
+            // still booting or shutting down, or already known to be broken
+            (State::Starting, _)
+            | (
+                State::Stopping,
+                Observation::NotReady | Observation::Ready | Observation::Unknown,
+            )
+            | (State::Crashed, Observation::Down | Observation::NotReady) => return,
+            (_, Observation::Ready) => State::Running,
+            (State::Running | State::Unhealthy, Observation::NotReady) => State::Unhealthy,
+            (_, Observation::NotReady) => State::Starting,
+            (State::Running | State::Unhealthy, Observation::Down) => State::Crashed,
+            (_, Observation::Down) => State::Stopped,
+            (_, Observation::Unknown) => State::Unknown,
+        };
+        if next != self.state {
+            self.enter(next);
+        }
+    }
+
+    /// Switches to `state` and notes the time
+    fn enter(&mut self, state: State) {
+        let now = Utc::now();
+        self.state = state;
+        self.since = now;
+        self.transitions.insert(state, now);
+    }
+
+    /// Time spent in the current state
+    fn since_elapsed(&self) -> Duration {
+        (Utc::now() - self.since).to_std().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InvalidTransition, Lifecycle, Observation, State};
+    use std::{thread, time::Duration};
+
+    /// Every state
+    const STATES: [State; 7] = [
+        State::Stopped,
+        State::Starting,
+        State::Running,
+        State::Unhealthy,
+        State::Stopping,
+        State::Crashed,
+        State::Unknown,
+    ];
+
+    /// A lifecycle in `state` that doesn't time out while starting
+    fn in_state(state: State) -> Lifecycle {
+        let mut lifecycle = Lifecycle::new(Duration::from_hours(1));
+        lifecycle.enter(state);
+        lifecycle
+    }
+
+    #[test]
+    fn starts_are_only_allowed_when_not_running() {
+        for from in STATES {
+            let mut lifecycle = in_state(from);
+            let allowed = matches!(from, State::Stopped | State::Crashed | State::Unknown);
+            if allowed {
+                assert_eq!(lifecycle.begin_start(), Ok(()), "from {from}");
+                assert_eq!(lifecycle.state(), State::Starting);
+            } else {
+                assert_eq!(
+                    lifecycle.begin_start(),
+                    Err(InvalidTransition {
+                        from,
+                        action: "start"
+                    })
+                );
+                assert_eq!(lifecycle.state(), from, "refused starts change nothing");
+            }
+        }
+    }
+
+    #[test]
+    fn stops_are_refused_while_stopped_or_starting() {
+        for from in STATES {
+            let mut lifecycle = in_state(from);
+            let refused = matches!(from, State::Stopped | State::Starting);
+            if refused {
+                assert_eq!(
+                    lifecycle.begin_stop(),
+                    Err(InvalidTransition {
+                        from,
+                        action: "stop"
+                    })
+                );
+                assert_eq!(lifecycle.state(), from, "refused stops change nothing");
+            } else {
+                assert_eq!(lifecycle.begin_stop(), Ok(()), "from {from}");
+                assert_eq!(lifecycle.state(), State::Stopping);
+            }
+        }
+        assert_eq!(
+            in_state(State::Starting)
+                .begin_stop()
+                .unwrap_err()
+                .to_string(),
+            "Can't stop a server that is Starting"
+        );
+    }
+
+    #[test]
+    fn only_settled_servers_may_be_changed() {
+        for from in STATES {
+            let settled = in_state(from).check_settled("change the mods of");
+            if matches!(from, State::Starting | State::Stopping) {
+                assert_eq!(
+                    settled.unwrap_err().to_string(),
+                    format!("Can't change the mods of a server that is {from}")
+                );
+            } else {
+                assert_eq!(settled, Ok(()), "{from}");
+            }
+        }
+    }
+
+    #[test]
+    fn observations_move_the_state() {
+        use Observation::{Down, NotReady, Ready, Unknown};
+        // next state after Down, NotReady, Ready and Unknown
+        let table = [
+            (
+                State::Stopped,
+                [
+                    State::Stopped,
+                    State::Starting,
+                    State::Running,
+                    State::Unknown,
+                ],
+            ),
+            (
+                State::Starting,
+                [
+                    State::Starting,
+                    State::Starting,
+                    State::Running,
+                    State::Starting,
+                ],
+            ),
+            (
+                State::Running,
+                [
+                    State::Crashed,
+                    State::Unhealthy,
+                    State::Running,
+                    State::Unknown,
+                ],
+            ),
+            (
+                State::Unhealthy,
+                [
+                    State::Crashed,
+                    State::Unhealthy,
+                    State::Running,
+                    State::Unknown,
+                ],
+            ),
+            (
+                State::Stopping,
+                [
+                    State::Stopped,
+                    State::Stopping,
+                    State::Stopping,
+                    State::Stopping,
+                ],
+            ),
+            (
+                State::Crashed,
+                [
+                    State::Crashed,
+                    State::Crashed,
+                    State::Running,
+                    State::Unknown,
+                ],
+            ),
+            (
+                State::Unknown,
+                [
+                    State::Stopped,
+                    State::Starting,
+                    State::Running,
+                    State::Unknown,
+                ],
+            ),
+        ];
+        for (from, nexts) in table {
+            for (observation, next) in [Down, NotReady, Ready, Unknown].into_iter().zip(nexts) {
+                let mut lifecycle = in_state(from);
+                lifecycle.observe(observation);
+                assert_eq!(lifecycle.state(), next, "{from} after {observation:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn servers_that_dont_get_ready_crash() {
+        for observation in [
+            Observation::Down,
+            Observation::NotReady,
+            Observation::Unknown,
+        ] {
+            let mut lifecycle = Lifecycle::new(Duration::from_millis(1));
+            lifecycle.begin_start().unwrap();
+            thread::sleep(Duration::from_millis(5));
+            lifecycle.observe(observation);
+            assert_eq!(lifecycle.state(), State::Crashed, "after {observation:?}");
+        }
+    }
+
+    #[test]
+    fn since_only_changes_with_the_state() {
+        let mut lifecycle = in_state(State::Running);
+        let since = lifecycle.since();
+        thread::sleep(Duration::from_millis(2));
+        lifecycle.observe(Observation::Ready);
+        assert_eq!(lifecycle.since(), since);
+
+        lifecycle.crash();
+        let crashed = lifecycle.since();
+        assert!(crashed > since);
+        thread::sleep(Duration::from_millis(2));
+        lifecycle.crash();
+        assert_eq!(lifecycle.since(), crashed, "crashing twice is one crash");
+        lifecycle.observe(Observation::NotReady);
+        assert_eq!(lifecycle.state(), State::Crashed);
+
+        lifecycle.script_failed();
+        assert_eq!(lifecycle.state(), State::Unknown);
+        assert_eq!(
+            lifecycle.transitions.keys().copied().collect::<Vec<_>>(),
+            [State::Running, State::Crashed, State::Unknown]
+        );
+    }
+}
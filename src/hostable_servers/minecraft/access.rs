@@ -0,0 +1,445 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/minecraft/access.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Reads and writes the whitelist, operators and bans of a Minecraft server
+//! =============================================================
+
+use crate::hostable_servers::{
+    process::Process, CommandFailure, ListAddition, ListEntry, PlayerList,
+};
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use std::{fmt::Write as _, fs, io::ErrorKind, net::IpAddr, path::Path, time::Duration};
+
+/// Where [`MojangApi`] looks names up, the name goes at the end
+const PROFILE_API: &str = "https://api.mojang.com/users/profiles/minecraft/";
+/// Reason of a ban nobody gave a reason for, the one the server uses
+const DEFAULT_REASON: &str = "Banned by an operator.";
+
+/// Account of a player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// Id of the account, with dashes
+    pub uuid: String,
+    /// Name of the player, spelled like the account
+    pub name: String,
+}
+
+/// Finds the account of a player, servers in online mode know players by its UUID
+///
+/// Only needed to change the lists of a stopped server, a running one looks the
+/// players up itself
+pub trait UuidResolver: Send {
+    /// Returns the account called `name`, `None` if there is none
+    ///
+    /// # Errors
+    /// Returns why the name couldn't be looked up, the offline UUID is used then
+    fn resolve(&self, name: &str) -> Result<Option<Profile>, String>;
+}
+
+/// Looks names up with the Mojang API, using curl
+///
+/// `curl` has to be installed, see [`MojangApi::check`]
+#[derive(Debug, Clone)]
+pub struct MojangApi {
+    /// URL the name is added to
+    url: String,
+    /// How long the lookup may take
+    timeout: Duration,
+}
+impl MojangApi {
+    /// Returns a resolver asking `api.mojang.com`, giving up after `timeout`
+    #[must_use]
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            url: PROFILE_API.to_owned(),
+            timeout,
+        }
+    }
+    /// Asks `url` instead, which has to answer like the Mojang API, the name goes at the end
+    #[must_use]
+    pub fn with_url(mut self, url: String) -> Self {
+        self.url = url;
+        self
+    }
+    /// Checks that `curl` can be run, the offline UUID is used for every name otherwise
+    ///
+    /// # Errors
+    /// Returns why `curl --version` failed
+    pub fn check(&self) -> Result<(), CommandFailure> {
+        Process::new("curl")
+            .arg("--version")
+            .timeout(self.timeout)
+            .run()
+            .map(drop)
+    }
+}
+impl UuidResolver for MojangApi {
+    fn resolve(&self, name: &str) -> Result<Option<Profile>, String> {
+        let output = Process::new("curl")
+            .args(["--silent", "--show-error", "--write-out", "\n%{http_code}"])
+            .arg(format!("{}{name}", self.url))
+            .timeout(self.timeout)
+            .run()
+            .map_err(|e| e.to_string())?;
+        let (body, status) = output.stdout.rsplit_once('\n').unwrap_or_default();
+        match status.trim() {
+            "200" => parse_profile(body).map(Some),
+            // unknown names, older versions of the API answer with no content
+            "204" | "404" => Ok(None),
+            status => Err(format!("{} answered with {status}", self.url)),
+        }
+    }
+}
+
+/// Answer of the Mojang API
+#[derive(Deserialize)]
+struct ApiProfile {
+    /// UUID without dashes
+    id: String,
+    /// Name of the player
+    name: String,
+}
+
+/// Reads an answer of the Mojang API
+fn parse_profile(body: &str) -> Result<Profile, String> {
+    let profile: ApiProfile = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    let uuid = hyphenate(&profile.id).ok_or_else(|| format!("{:?} isn't a UUID", profile.id))?;
+    Ok(Profile {
+        uuid,
+        name: profile.name,
+    })
+}
+
+/// Adds the dashes to a UUID written as 32 hex digits
+fn hyphenate(id: &str) -> Option<String> {
+    if id.len() != 32 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(
+        format!(
+            "{}-{}-{}-{}-{}",
+            &id[..8],
+            &id[8..12],
+            &id[12..16],
+            &id[16..20],
+            &id[20..]
+        )
+        .to_ascii_lowercase(),
+    )
+}
+
+/// Returns the UUID a server in offline mode gives the player `name`
+///
+/// The name based UUID of `OfflinePlayer:<name>`, like Java's `UUID.nameUUIDFromBytes`
+///
+/// # Example
+/// ```
+/// use web_server::hostable_servers::minecraft::access::offline_uuid;
+///
+/// assert_eq!(offline_uuid("Notch"), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+/// ```
+#[must_use]
+pub fn offline_uuid(name: &str) -> String {
+    let mut hash: [u8; 16] = Md5::digest(format!("OfflinePlayer:{name}")).into();
+    // version 3, variant 1
+    hash[6] = (hash[6] & 0x0f) | 0x30;
+    hash[8] = (hash[8] & 0x3f) | 0x80;
+    let hex = hash.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    });
+    hyphenate(&hex).unwrap_or(hex)
+}
+
+/// Returns the file the server keeps `list` in, inside of its directory
+#[must_use]
+pub const fn file_name(list: PlayerList) -> &'static str {
+    match list {
+        PlayerList::Whitelist => "whitelist.json",
+        PlayerList::Ops => "ops.json",
+        PlayerList::Bans => "banned-players.json",
+        PlayerList::IpBans => "banned-ips.json",
+    }
+}
+
+/// Reads the list at `path`, it is empty if the server hasn't written it yet
+///
+/// # Errors
+/// Returns [`CommandFailure::Unreadable`] if the file can't be read or isn't a list
+pub fn load(path: &Path) -> Result<Vec<ListEntry>, CommandFailure> {
+    let unreadable = |reason: String| CommandFailure::Unreadable {
+        file: path.display().to_string(),
+        reason,
+    };
+    match fs::read_to_string(path) {
+        Ok(text) if text.trim().is_empty() => Ok(Vec::new()),
+        Ok(text) => serde_json::from_str(&text).map_err(|e| unreadable(e.to_string())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(unreadable(e.to_string())),
+    }
+}
+
+/// Writes `entries` to `path` like the server does, through a temporary file
+///
+/// # Errors
+/// Returns [`CommandFailure::Unwritable`] if the file can't be written
+pub fn save(path: &Path, entries: &[ListEntry]) -> Result<(), CommandFailure> {
+    let temporary = path.with_extension("json.tmp");
+    serde_json::to_string_pretty(entries)
+        .map_err(std::io::Error::from)
+        .and_then(|text| fs::write(&temporary, text))
+        .and_then(|()| fs::rename(&temporary, path))
+        .map_err(|e| CommandFailure::Unwritable {
+            file: path.display().to_string(),
+            reason: e.to_string(),
+        })
+}
+
+/// Checks that `addition` fits `list`
+///
+/// Names and reasons end up in console commands, so they can't hold line breaks or the
+/// `^` and `\` a screen session reads as keys
+///
+/// # Errors
+/// Returns what doesn't fit
+pub fn check(list: PlayerList, addition: &ListAddition) -> Result<(), String> {
+    check_name(list, &addition.name)?;
+    if let Some(reason) = &addition.reason {
+        if !matches!(list, PlayerList::Bans | PlayerList::IpBans) {
+            return Err("Only bans have a reason".to_owned());
+        }
+        if reason.chars().any(char::is_control) {
+            return Err("The reason has to be a single line".to_owned());
+        }
+        if reason.contains(['^', '\\']) {
+            return Err("The reason can't contain ^ or \\".to_owned());
+        }
+    }
+    if let Some(level) = addition.level {
+        if list != PlayerList::Ops {
+            return Err("Only operators have a level".to_owned());
+        }
+        if !(1..=4).contains(&level) {
+            return Err("The level of an operator is 1 to 4".to_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `name` is a player name, or an address for [`PlayerList::IpBans`]
+///
+/// # Errors
+/// Returns what is wrong with it
+pub fn check_name(list: PlayerList, name: &str) -> Result<(), String> {
+    if list == PlayerList::IpBans {
+        return name
+            .parse::<IpAddr>()
+            .map(drop)
+            .map_err(|_| format!("{name:?} isn't an IP address"));
+    }
+    if name.is_empty()
+        || name.len() > 16
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(format!("{name:?} isn't a player name"));
+    }
+    Ok(())
+}
+
+/// Returns the console command adding `addition` to `list`, see [`check`]
+#[must_use]
+pub fn add_command(list: PlayerList, addition: &ListAddition) -> String {
+    let name = &addition.name;
+    let command = match list {
+        PlayerList::Whitelist => return format!("whitelist add {name}"),
+        PlayerList::Ops => return format!("op {name}"),
+        PlayerList::Bans => format!("ban {name}"),
+        PlayerList::IpBans => format!("ban-ip {name}"),
+    };
+    match &addition.reason {
+        Some(reason) => format!("{command} {reason}"),
+        None => command,
+    }
+}
+
+/// Returns the console command removing the player or address `name` from `list`
+#[must_use]
+pub fn remove_command(list: PlayerList, name: &str) -> String {
+    match list {
+        PlayerList::Whitelist => format!("whitelist remove {name}"),
+        PlayerList::Ops => format!("deop {name}"),
+        PlayerList::Bans => format!("pardon {name}"),
+        PlayerList::IpBans => format!("pardon-ip {name}"),
+    }
+}
+
+/// Returns the entry the server would write for `addition`
+///
+/// `profile` is the account of the player, there is none for [`PlayerList::IpBans`].
+/// Operators get `default_level` unless the addition has one
+#[must_use]
+pub fn new_entry(
+    list: PlayerList,
+    addition: &ListAddition,
+    profile: Option<Profile>,
+    default_level: u8,
+) -> ListEntry {
+    let (uuid, name) = profile.map_or((None, None), |profile| {
+        (Some(profile.uuid), Some(profile.name))
+    });
+    let mut entry = ListEntry {
+        uuid,
+        name,
+        ..ListEntry::default()
+    };
+    match list {
+        PlayerList::Whitelist => {}
+        PlayerList::Ops => {
+            entry.level = Some(addition.level.unwrap_or(default_level));
+            entry.bypasses_player_limit = Some(false);
+        }
+        PlayerList::Bans | PlayerList::IpBans => {
+            if list == PlayerList::IpBans {
+                entry.ip = Some(addition.name.clone());
+            }
+            entry.created = Some(
+                chrono::Local::now()
+                    .format("%Y-%m-%d %H:%M:%S %z")
+                    .to_string(),
+            );
+            entry.source = Some("Server".to_owned());
+            entry.expires = Some("forever".to_owned());
+            entry.reason = Some(
+                addition
+                    .reason
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_REASON.to_owned()),
+            );
+        }
+    }
+    entry
+}
+
+/// Returns true if `entry` is about the player or address `name`, names ignore case
+fn is_about(entry: &ListEntry, name: &str) -> bool {
+    entry
+        .name
+        .as_deref()
+        .is_some_and(|own| own.eq_ignore_ascii_case(name))
+        || entry.ip.as_deref() == Some(name)
+}
+
+/// Adds `entry` to `entries`, returns false if the player or address is on them already
+pub fn add(entries: &mut Vec<ListEntry>, entry: ListEntry) -> bool {
+    let duplicate = entries.iter().any(|other| {
+        (entry.uuid.is_some() && other.uuid == entry.uuid)
+            || entry
+                .name
+                .as_deref()
+                .is_some_and(|name| is_about(other, name))
+            || entry.ip.as_deref().is_some_and(|ip| is_about(other, ip))
+    });
+    if !duplicate {
+        entries.push(entry);
+    }
+    !duplicate
+}
+
+/// Removes the player or address `name` from `entries`, returns false if it wasn't on them
+pub fn remove(entries: &mut Vec<ListEntry>, name: &str) -> bool {
+    let before = entries.len();
+    entries.retain(|entry| !is_about(entry, name));
+    entries.len() != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add, check, new_entry, parse_profile, remove, Profile};
+    use crate::hostable_servers::{ListAddition, ListEntry, PlayerList};
+
+    /// Addition of `name` without a reason or level
+    fn addition(name: &str) -> ListAddition {
+        ListAddition {
+            name: name.to_owned(),
+            reason: None,
+            level: None,
+        }
+    }
+
+    #[test]
+    fn reads_the_mojang_api() {
+        let profile =
+            parse_profile(r#"{"id":"069A79F444E94726A5BEFCA90E38AAF5","name":"Notch"}"#).unwrap();
+        assert_eq!(
+            profile,
+            Profile {
+                uuid: "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_owned(),
+                name: "Notch".to_owned(),
+            }
+        );
+        assert!(parse_profile(r#"{"id":"nope","name":"Notch"}"#).is_err());
+    }
+
+    #[test]
+    fn keeps_the_format_of_the_server() {
+        let text = r#"[
+  {
+    "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+    "name": "Notch",
+    "level": 4,
+    "bypassesPlayerLimit": false
+  }
+]"#;
+        let entries: Vec<ListEntry> = serde_json::from_str(text).unwrap();
+        assert_eq!(entries[0].level, Some(4));
+        assert_eq!(serde_json::to_string_pretty(&entries).unwrap(), text);
+
+        let ban = new_entry(PlayerList::IpBans, &addition("10.0.0.1"), None, 4);
+        let ban = serde_json::to_value(ban).unwrap();
+        assert_eq!(ban["ip"], "10.0.0.1");
+        assert_eq!(ban["reason"], "Banned by an operator.");
+        assert!(ban.get("uuid").is_none());
+    }
+
+    #[test]
+    fn adds_every_player_once() {
+        let profile = Profile {
+            uuid: "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_owned(),
+            name: "Notch".to_owned(),
+        };
+        let mut entries = Vec::new();
+        let entry = new_entry(PlayerList::Whitelist, &addition("notch"), Some(profile), 4);
+        assert!(add(&mut entries, entry.clone()));
+        assert!(!add(&mut entries, entry));
+        assert_eq!(entries[0].name.as_deref(), Some("Notch"));
+
+        assert!(!remove(&mut entries, "Alex"));
+        assert!(remove(&mut entries, "NOTCH"));
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn refuses_what_could_break_a_command() {
+        assert!(check(PlayerList::Whitelist, &addition("Alex_01")).is_ok());
+        assert!(check(PlayerList::Whitelist, &addition("Alex\nstop")).is_err());
+        assert!(check(PlayerList::Whitelist, &addition("")).is_err());
+        assert!(check(PlayerList::IpBans, &addition("Alex")).is_err());
+        assert!(check(PlayerList::IpBans, &addition("::1")).is_ok());
+
+        let mut ban = addition("Alex");
+        ban.reason = Some("Griefing\nop Alex".to_owned());
+        assert!(check(PlayerList::Bans, &ban).is_err());
+        // `stuff` types ^M as enter
+        ban.reason = Some("x^Mop Evil".to_owned());
+        assert!(check(PlayerList::Bans, &ban).is_err());
+        ban.reason = Some("x\\015op Evil".to_owned());
+        assert!(check(PlayerList::Bans, &ban).is_err());
+        ban.reason = Some("Griefing the spawn".to_owned());
+        assert!(check(PlayerList::Bans, &ban).is_ok());
+        ban.level = Some(4);
+        ban.reason = None;
+        assert!(check(PlayerList::Bans, &ban).is_err());
+    }
+}
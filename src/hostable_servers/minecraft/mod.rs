@@ -0,0 +1,884 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/minecraft/mod.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Implements [`crate::hostable_servers::HostableServer`] for minecraft
+//! =============================================================
+
+use crate::hostable_servers::{
+    process::CancelToken,
+    supervisor::{Exit, SupervisorStatus},
+    watchdog::WatchdogSettings,
+    CommandFailure, HostableServer, ListAddition, ListEntry, PlayerList,
+};
+use access::{MojangApi, Profile, UuidResolver};
+use properties::{Properties, HIDDEN, MANAGED, SECRETS};
+use query::{FullStat, Query};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use super::{
+    backend::{self, ProcessBackend, Scripts},
+    backend_name,
+    lifecycle::{Lifecycle, Observation, State},
+    probe::{self, Probe, ProbeResult},
+    rcon::{RconClient, RconLogin},
+    Players, Settings, CONSOLE_LINES, RCON_TIMEOUT,
+};
+
+pub mod access;
+pub mod properties;
+pub mod query;
+pub mod slp;
+
+/// How long a Server List Ping may take
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long every UDP Query answer is waited for
+const QUERY_TIMEOUT: Duration = Duration::from_secs(1);
+/// How often a lost UDP Query request is sent again
+const QUERY_RETRIES: u32 = 2;
+/// How long looking up the account of a player may take
+const PROFILE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where a [`Server`] gets its players from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusSource {
+    /// The answer to `list` in the log, the status script makes the server print it
+    Log,
+    /// `list` over RCON, see [`Server::with_rcon`]
+    Rcon,
+    /// The Server List Ping, which names at most 12 players, see [`Server::with_ping`]
+    Ping,
+    /// The UDP Query with every player's name, see [`Server::with_query`]
+    Query,
+}
+
+/// Minecraft Server with the State and number of Players
+#[derive(Serialize)]
+pub struct Server {
+    /// Path, name and scripts of the Server
+    #[serde(flatten)]
+    settings: Settings,
+    /// Runs the process of the Server
+    #[serde(rename = "backend", serialize_with = "backend_name")]
+    backend: Box<dyn ProcessBackend>,
+    /// Process id and last exit, if the backend knows them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process: Option<SupervisorStatus>,
+    /// Screen log the answer to `list` is read from without RCON
+    #[serde(skip)]
+    log_file: PathBuf,
+    /// `server.properties` of the server
+    #[serde(skip)]
+    properties_file: PathBuf,
+    /// Whether `server.properties` changed since the server started
+    restart_required: bool,
+    /// Finds the accounts of players added to the lists of a stopped server
+    #[serde(skip)]
+    resolver: Box<dyn UuidResolver>,
+    /// RCON interface used for `list`, `stop` and admin commands
+    #[serde(skip)]
+    rcon: Option<RconClient>,
+    /// `host:port` the Server List Ping is sent to
+    #[serde(skip)]
+    ping_address: Option<String>,
+    /// What the last Server List Ping answered, version, MOTD and latency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ping: Option<slp::Status>,
+    /// `host:port` of the UDP Query, `query.port` in `server.properties`
+    #[serde(skip)]
+    query_address: Option<String>,
+    /// Socket of the UDP Query with its challenge token
+    #[serde(skip)]
+    query_socket: Option<Query>,
+    /// What the last UDP Query answered, plugins, map and every player
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query: Option<FullStat>,
+    /// Where the players come from, picked from what is set up if `None`
+    #[serde(skip)]
+    status_source: Option<StatusSource>,
+    /// Lifecycle state of the Server and when it changed
+    #[serde(flatten)]
+    lifecycle: Lifecycle,
+    /// Results of the last readiness probes
+    probes: Vec<ProbeResult>,
+    /// Number of players and their nametags
+    players: Players,
+}
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    /// Creates a new turned off minecraft server
+    ///
+    /// The scripts are in `./minecraft/`, the log is read from `Minecraft/screenlog.0`
+    /// and the properties from `Minecraft/server.properties`
+    #[must_use]
+    pub fn new() -> Self {
+        let mut settings = Settings::new("minecraft");
+        settings.scripts.status = Some(PathBuf::from("./minecraft/status.sh"));
+        Self::with_settings(settings, PathBuf::from("Minecraft/screenlog.0"))
+    }
+    /// Creates a new turned off minecraft server with custom scripts and log file
+    ///
+    /// `settings.scripts.status` has to make the server print the answer to `list`
+    /// into `log_file`
+    #[must_use]
+    pub fn with_settings(settings: Settings, log_file: PathBuf) -> Self {
+        Self {
+            lifecycle: settings.lifecycle(),
+            settings,
+            backend: Box::new(Scripts),
+            process: None,
+            log_file,
+            properties_file: PathBuf::from("Minecraft/server.properties"),
+            restart_required: false,
+            resolver: Box::new(MojangApi::new(PROFILE_TIMEOUT)),
+            rcon: None,
+            ping_address: None,
+            ping: None,
+            query_address: None,
+            query_socket: None,
+            query: None,
+            status_source: None,
+            probes: Vec::new(),
+            players: Players::new(),
+        }
+    }
+    /// Runs the process with `backend` instead of the start and stop scripts
+    ///
+    /// Without a status script, `list` is typed into the console for the log
+    #[must_use]
+    pub fn with_backend(mut self, backend: Box<dyn ProcessBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+    /// Reads and changes the `server.properties` at `properties_file`
+    #[must_use]
+    pub fn with_properties_file(mut self, properties_file: PathBuf) -> Self {
+        self.properties_file = properties_file;
+        self
+    }
+    /// Finds the accounts of players with `resolver` instead of the Mojang API
+    ///
+    /// Only used for the lists of a stopped server in online mode
+    #[must_use]
+    pub fn with_resolver(mut self, resolver: Box<dyn UuidResolver>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+    /// Talks to the server over RCON instead of the status script and the screen log
+    ///
+    /// `login` has the port and password of `rcon.port` and `rcon.password` in
+    /// `server.properties`. `stop` is sent over RCON as well, the stop script only
+    /// runs if that doesn't work
+    #[must_use]
+    pub fn with_rcon(mut self, login: RconLogin) -> Self {
+        self.rcon = Some(RconClient::new(login, RCON_TIMEOUT));
+        self
+    }
+    /// Asks the server at `address` for its status like the multiplayer menu does
+    ///
+    /// Needs no password, the players come from the ping unless there is RCON or the
+    /// UDP Query as well. Servers before 1.7 only send the number of players, not their names
+    #[must_use]
+    pub fn with_ping(mut self, address: String) -> Self {
+        self.ping_address = Some(address);
+        self
+    }
+    /// Asks the UDP Query at `address` for the players, `enable-query=true` in `server.properties`
+    ///
+    /// Used instead of the Server List Ping and the log if there is no RCON
+    #[must_use]
+    pub fn with_query(mut self, address: String) -> Self {
+        self.query_address = Some(address);
+        self
+    }
+    /// Reads the players from `source` instead of the one picked from what is set up
+    ///
+    /// The source has to be set up with [`Server::with_rcon`], [`Server::with_ping`]
+    /// or [`Server::with_query`], otherwise the server never counts as ready
+    #[must_use]
+    pub const fn with_status_source(mut self, source: StatusSource) -> Self {
+        self.status_source = Some(source);
+        self
+    }
+    /// Returns where the players come from: the chosen source, otherwise RCON, the
+    /// UDP Query, the Server List Ping and the log, whichever is set up first
+    #[must_use]
+    pub const fn status_source(&self) -> StatusSource {
+        match self.status_source {
+            Some(source) => source,
+            None if self.rcon.is_some() => StatusSource::Rcon,
+            None if self.query_address.is_some() => StatusSource::Query,
+            None if self.ping_address.is_some() => StatusSource::Ping,
+            None => StatusSource::Log,
+        }
+    }
+    /// Runs `command` over RCON and returns the answer
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] without RCON and
+    /// [`CommandFailure::Remote`] if the server can't be reached or refuses
+    pub fn rcon_command(&mut self, command: &str) -> Result<String, CommandFailure> {
+        let Some(rcon) = &mut self.rcon else {
+            return Err(CommandFailure::Unsupported {
+                action: "RCON".to_owned(),
+            });
+        };
+        rcon.run(command)
+    }
+    /// Broadcasts `message` to every player
+    ///
+    /// # Errors
+    /// See [`Server::rcon_command`]
+    pub fn say(&mut self, message: &str) -> Result<(), CommandFailure> {
+        self.rcon_command(&format!("say {message}")).map(drop)
+    }
+    /// Writes the world to disk
+    ///
+    /// # Errors
+    /// See [`Server::rcon_command`]
+    pub fn save_all(&mut self) -> Result<(), CommandFailure> {
+        self.rcon_command("save-all").map(drop)
+    }
+    /// Saves the world, sends `stop` and waits for the process to go away
+    ///
+    /// Returns false if it is still there after the stop timeout
+    fn stop_over_rcon(&mut self) -> Result<bool, CommandFailure> {
+        self.save_all()?;
+        // the server may close the connection before it answers
+        if let Err(e) = self.rcon_command("stop") {
+            if !matches!(e, CommandFailure::Remote { .. }) {
+                return Err(e);
+            }
+        }
+        self.disconnect_rcon();
+        backend::wait_until_gone(&*self.backend, &self.settings)
+    }
+    /// Closes the RCON connection, the server is gone or going
+    fn disconnect_rcon(&mut self) {
+        if let Some(rcon) = &mut self.rcon {
+            rcon.disconnect();
+        }
+    }
+    /// Returns the file of `list`, next to `server.properties`
+    fn list_file(&self, list: PlayerList) -> PathBuf {
+        self.properties_file
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(access::file_name(list))
+    }
+    /// Returns true if the lists are changed with commands, the server is running then
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::InvalidTransition`] while the server is starting or
+    /// stopping, it reads or writes the files then
+    fn lists_are_live(&self) -> Result<bool, CommandFailure> {
+        self.lifecycle.check_settled("change the player lists of")?;
+        Ok(!matches!(
+            self.lifecycle.state(),
+            State::Stopped | State::Crashed
+        ))
+    }
+    /// Runs `command` on the running server, over RCON or typed into its console
+    fn run_live(&mut self, command: &str) -> Result<String, CommandFailure> {
+        if self.rcon.is_some() {
+            return self.rcon_command(command);
+        }
+        self.backend.send_input(&self.settings, command)?;
+        Ok(format!("Sent `{command}` to the console"))
+    }
+    /// Finds the account of the player `name` for the lists of a stopped server
+    ///
+    /// Servers in offline mode use the offline UUID, the others only if the resolver fails
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Rejected`] if there is no account called `name`
+    fn profile(
+        &self,
+        name: &str,
+        properties: Option<&Properties>,
+    ) -> Result<Profile, CommandFailure> {
+        let online =
+            properties.and_then(|properties| properties.get("online-mode")) != Some("false");
+        if online {
+            match self.resolver.resolve(name) {
+                Ok(Some(profile)) => return Ok(profile),
+                Ok(None) => {
+                    return Err(CommandFailure::Rejected {
+                        reason: format!("There is no Minecraft account called {name}"),
+                    })
+                }
+                Err(e) => eprintln!(
+                    "\x1b[31m{}: Couldn't look {name} up, using the offline UUID: {e}\x1b[39m",
+                    self.settings.path
+                ),
+            }
+        }
+        Ok(Profile {
+            uuid: access::offline_uuid(name),
+            name: name.to_owned(),
+        })
+    }
+    /// Sets `self` to default
+    fn set_default(&mut self) {
+        self.lifecycle.observe(Observation::Down);
+        self.disconnect_rcon();
+        self.ping = None;
+        self.query = None;
+        self.restart_required = false;
+        self.probes = Vec::new();
+        self.players = Players {
+            count: 0,
+            name_tags: Vec::new(),
+        };
+    }
+    /// Updates self
+    ///
+    /// Sends the Server List Ping if there is an address, then reads the players from
+    /// the [`StatusSource`]. With the log the status script has to print the answer to
+    /// `list` into it. Without probes the server counts as ready once it answers
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure`] if the status script fails
+    fn update_players(&mut self) -> Result<Observation, CommandFailure> {
+        let pinged = self.update_ping();
+        let answered = match self.status_source() {
+            StatusSource::Log => return self.read_log(),
+            StatusSource::Rcon => match self.rcon_command("list") {
+                Ok(answer) => match parse_list(&answer) {
+                    Some(players) => {
+                        self.players = players;
+                        true
+                    }
+                    None => return Ok(Observation::Unknown),
+                },
+                Err(e) => {
+                    // RCON only opens once the world is loaded
+                    eprintln!("\x1b[31m{}: {e}\x1b[39m", self.settings.path);
+                    false
+                }
+            },
+            StatusSource::Ping => {
+                if let Some(status) = &self.ping {
+                    self.players = Players {
+                        count: status.online,
+                        name_tags: status.sample.clone(),
+                    };
+                }
+                pinged
+            }
+            StatusSource::Query => self.update_query(),
+        };
+
+        // a server answering the ping is up, even if the source isn't yet
+        Ok(if answered || pinged {
+            Observation::Ready
+        } else {
+            Observation::NotReady
+        })
+    }
+    /// Runs the status script, or types `list` without one, and reads the last answer
+    /// to `list` out of the log
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure`] if the status script fails
+    fn read_log(&mut self) -> Result<Observation, CommandFailure> {
+        let players = {
+            match &self.settings.scripts.status {
+                Some(status) => drop(self.settings.run(status, self.settings.timeouts.status)?),
+                // the answer is there for the next update
+                None => self.backend.send_input(&self.settings, "list")?,
+            }
+            let output = self.log(None).unwrap_or_else(|e| {
+                eprintln!("\x1b[31mCouldn't read the Minecraft log file: {e}\x1b[39m");
+                Vec::new()
+            });
+            output.iter().rev().find_map(|line| parse_list(line))
+        };
+
+        Ok(players.map_or(Observation::Unknown, |players| {
+            self.players = players;
+            Observation::Ready
+        }))
+    }
+    /// Returns the last `lines` lines of the log file, every line without a limit
+    ///
+    /// Without the file the output the backend keeps is used, `direct` servers have one
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unreadable`] if the file can't be read
+    fn log(&self, lines: Option<usize>) -> Result<Vec<String>, CommandFailure> {
+        let log = match std::fs::read(&self.log_file) {
+            Ok(log) => log,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return self
+                    .backend
+                    .read_output(&self.settings, lines.unwrap_or(CONSOLE_LINES));
+            }
+            Err(e) => {
+                return Err(CommandFailure::Unreadable {
+                    file: self.log_file.display().to_string(),
+                    reason: e.to_string(),
+                })
+            }
+        };
+        let log = String::from_utf8_lossy(&log);
+        let all: Vec<&str> = log.lines().collect();
+        let skip = lines.map_or(0, |lines| all.len().saturating_sub(lines));
+        Ok(all[skip..].iter().map(|line| (*line).to_owned()).collect())
+    }
+    /// Sends the Server List Ping, returns true if the server answered
+    fn update_ping(&mut self) -> bool {
+        let Some(address) = &self.ping_address else {
+            return false;
+        };
+        match slp::ping(address, PING_TIMEOUT) {
+            Ok(status) => {
+                self.ping = Some(status);
+                true
+            }
+            Err(e) => {
+                // the port only opens once the world is loaded
+                eprintln!(
+                    "\x1b[31m{}: Server List Ping to {address} failed: {e}\x1b[39m",
+                    self.settings.path
+                );
+                self.ping = None;
+                false
+            }
+        }
+    }
+    /// Asks the UDP Query for the full stat, returns true if the server answered
+    ///
+    /// The socket is kept, its challenge token stays valid for a while
+    fn update_query(&mut self) -> bool {
+        let Some(address) = &self.query_address else {
+            return false;
+        };
+        let stat = match &mut self.query_socket {
+            Some(query) => query.full_stat(),
+            None => Query::connect(address, QUERY_TIMEOUT, QUERY_RETRIES)
+                .and_then(|query| self.query_socket.insert(query).full_stat()),
+        };
+        match stat {
+            Ok(stat) => {
+                self.players = Players {
+                    count: stat.online,
+                    name_tags: stat.players.clone(),
+                };
+                self.query = Some(stat);
+                true
+            }
+            Err(e) => {
+                // the port only opens once the world is loaded
+                eprintln!(
+                    "\x1b[31m{}: UDP Query to {address} failed: {e}\x1b[39m",
+                    self.settings.path
+                );
+                self.query = None;
+                false
+            }
+        }
+    }
+}
+
+impl HostableServer for Server {
+    fn get_path(&self) -> &str {
+        &self.settings.path
+    }
+    fn get_name(&self) -> &str {
+        &self.settings.name
+    }
+    fn start(&mut self) -> Result<(), CommandFailure> {
+        self.lifecycle.begin_start()?;
+        self.settings.probes.iter_mut().for_each(Probe::mark_start);
+        // the server reads server.properties now
+        self.restart_required = false;
+        if let Err(e) = self.backend.launch(&self.settings) {
+            self.lifecycle.script_failed();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Stops the server over RCON if it can, with the backend otherwise
+    fn stop(&mut self) -> Result<(), CommandFailure> {
+        self.lifecycle.begin_stop()?;
+        if self.rcon.is_some() {
+            match self.stop_over_rcon() {
+                Ok(true) => return self.update_status(),
+                Ok(false) => eprintln!(
+                    "\x1b[31m{}: still running {}s after `stop`, stopping it with the {} backend\x1b[39m",
+                    self.settings.path,
+                    self.settings.timeouts.stop.as_secs(),
+                    self.backend.name()
+                ),
+                Err(e) => eprintln!(
+                    "\x1b[31m{}: {e}, stopping it with the {} backend\x1b[39m",
+                    self.settings.path,
+                    self.backend.name()
+                ),
+            }
+        }
+        if let Err(e) = self.backend.terminate(&self.settings) {
+            self.lifecycle.script_failed();
+            return Err(e);
+        }
+
+        self.update_status()
+    }
+
+    fn update_status(&mut self) -> Result<(), CommandFailure> {
+        let alive = match self.backend.is_alive(&self.settings) {
+            Ok(alive) => alive,
+            Err(e) => {
+                self.lifecycle.observe(Observation::Unknown);
+                return Err(e);
+            }
+        };
+        self.process = self.backend.process();
+
+        if alive {
+            let answered_list = self.update_players()?;
+            self.probes = self.check_probes();
+            let observation = if self.probes.is_empty() {
+                answered_list
+            } else {
+                Observation::from_probes(true, probe::all_ok(&self.probes))
+            };
+            self.lifecycle.observe(observation);
+        } else {
+            self.set_default();
+        }
+
+        Ok(())
+    }
+
+    fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&self)
+    }
+
+    fn cancel_token(&self) -> Option<CancelToken> {
+        Some(self.settings.cancel.clone())
+    }
+
+    fn probes(&self) -> &[Probe] {
+        &self.settings.probes
+    }
+
+    fn lifecycle_mut(&mut self) -> Option<&mut Lifecycle> {
+        Some(&mut self.lifecycle)
+    }
+
+    fn last_exit(&self) -> Option<Exit> {
+        self.process
+            .as_ref()
+            .and_then(|process| process.last_exit.clone())
+    }
+
+    fn watchdog(&self) -> WatchdogSettings {
+        self.settings.watchdog
+    }
+
+    /// Sends the line over RCON, or types it into the console without RCON
+    ///
+    /// Answers over RCON aren't in the log
+    fn send_console(&mut self, line: &str) -> Result<(), CommandFailure> {
+        self.run_live(line).map(drop)
+    }
+
+    fn command(&mut self, command: &str) -> Result<String, CommandFailure> {
+        self.rcon_command(command)
+    }
+
+    /// Every key of `server.properties`, passwords like `rcon.password` are hidden
+    fn properties(&self) -> Result<BTreeMap<String, String>, CommandFailure> {
+        let properties = Properties::load(&self.properties_file)?;
+        Ok(properties
+            .iter()
+            .map(|(key, value)| {
+                let value = if SECRETS.contains(&key) {
+                    HIDDEN
+                } else {
+                    value
+                };
+                (key.to_owned(), value.to_owned())
+            })
+            .collect())
+    }
+
+    /// Writes the changes into `server.properties`, keeping its comments and order
+    ///
+    /// Refused while the server is starting or stopping, it reads or writes the file then.
+    /// A running server has to be restarted to see them. The RCON keys can't be changed,
+    /// the web server would lose its connection, a hidden password is left as it is
+    fn set_properties(&mut self, changes: BTreeMap<String, String>) -> Result<(), CommandFailure> {
+        self.lifecycle.check_settled("change the properties of")?;
+        let mut properties = Properties::load(&self.properties_file)?;
+        for (key, value) in &changes {
+            if SECRETS.contains(&key.as_str()) && value == HIDDEN {
+                continue;
+            }
+            if MANAGED.contains(&key.as_str()) && properties.get(key) != Some(value.as_str()) {
+                return Err(CommandFailure::Rejected {
+                    reason: format!(
+                        "{key} has to match the rcon settings of the web server config, \
+                         change it there and in server.properties by hand"
+                    ),
+                });
+            }
+            properties
+                .set(key, value)
+                .map_err(|reason| CommandFailure::Rejected { reason })?;
+        }
+        properties.save(&self.properties_file)?;
+
+        if self.lifecycle.state() != State::Stopped {
+            self.restart_required = true;
+        }
+        Ok(())
+    }
+
+    /// The file of the list, a running server writes it after every change
+    fn player_list(&self, list: PlayerList) -> Result<Vec<ListEntry>, CommandFailure> {
+        access::load(&self.list_file(list))
+    }
+
+    /// Runs the command on a running server and changes the file of a stopped one
+    ///
+    /// The accounts of the players are looked up for the file, operators get
+    /// `op-permission-level` unless the addition has a level
+    fn add_to_list(
+        &mut self,
+        list: PlayerList,
+        addition: ListAddition,
+    ) -> Result<String, CommandFailure> {
+        access::check(list, &addition).map_err(|reason| CommandFailure::Rejected { reason })?;
+        if self.lists_are_live()? {
+            if addition.level.is_some() {
+                return Err(CommandFailure::Rejected {
+                    reason:
+                        "The level of an operator can only be chosen while the server is stopped"
+                            .to_owned(),
+                });
+            }
+            return self.run_live(&access::add_command(list, &addition));
+        }
+
+        let properties = Properties::load(&self.properties_file).ok();
+        let profile = match list {
+            PlayerList::IpBans => None,
+            _ => Some(self.profile(&addition.name, properties.as_ref())?),
+        };
+        let default_level = properties
+            .as_ref()
+            .and_then(|properties| properties.get("op-permission-level")?.parse().ok())
+            .unwrap_or(4);
+        let path = self.list_file(list);
+        let mut entries = access::load(&path)?;
+        let file = access::file_name(list);
+        if !access::add(
+            &mut entries,
+            access::new_entry(list, &addition, profile, default_level),
+        ) {
+            return Ok(format!("{} is in {file} already", addition.name));
+        }
+        access::save(&path, &entries)?;
+        Ok(format!("Added {} to {file}", addition.name))
+    }
+
+    /// Runs the command on a running server and changes the file of a stopped one
+    fn remove_from_list(&mut self, list: PlayerList, name: &str) -> Result<String, CommandFailure> {
+        access::check_name(list, name).map_err(|reason| CommandFailure::Rejected { reason })?;
+        if self.lists_are_live()? {
+            return self.run_live(&access::remove_command(list, name));
+        }
+
+        let path = self.list_file(list);
+        let mut entries = access::load(&path)?;
+        let file = access::file_name(list);
+        if !access::remove(&mut entries, name) {
+            return Err(CommandFailure::Rejected {
+                reason: format!("{name} isn't in {file}"),
+            });
+        }
+        access::save(&path, &entries)?;
+        Ok(format!("Removed {name} from {file}"))
+    }
+
+    /// The end of the screen log, or of the output the backend keeps without one
+    fn console_output(&self) -> Result<Vec<String>, CommandFailure> {
+        self.log(Some(CONSOLE_LINES))
+    }
+}
+
+/// Reads the players out of the answer to `list`, `None` if it isn't one
+///
+/// Knows the answers of 1.13 and later, `There are 2 of a max of 20 players online: Alex, Steve`,
+/// and of older versions, `There are 2/20 players online:Alex, Steve`. Lines of the log
+/// with a time stamp in front and formatting codes work as well
+fn parse_list(answer: &str) -> Option<Players> {
+    let text = strip_formatting(answer);
+    let (_, rest) = text.rsplit_once("There are ")?;
+    let count = rest
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()?;
+    let (_, names) = rest.split_once("online:").unwrap_or_default();
+    Some(Players {
+        count,
+        name_tags: names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(ToOwned::to_owned)
+            .collect(),
+    })
+}
+
+/// Removes colour and formatting codes, `§` followed by a character
+fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{a7}' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_list, Server};
+    use crate::hostable_servers::{
+        lifecycle::State, supervisor::Supervisor, CommandFailure, HostableServer, Settings,
+    };
+    use std::{collections::BTreeMap, fs, thread, time::Duration};
+
+    #[test]
+    fn parses_list_answers() {
+        let players =
+            parse_list("There are 3 of a max of 150 players online: Alex, Steve, Notch").unwrap();
+        assert_eq!(players.count, 3);
+        assert_eq!(players.name_tags, ["Alex", "Steve", "Notch"]);
+
+        let players = parse_list("There are 120 of a max of 150 players online: ").unwrap();
+        assert_eq!(players.count, 120);
+        assert!(players.name_tags.is_empty());
+    }
+
+    #[test]
+    fn parses_old_and_formatted_answers() {
+        let players = parse_list("There are 2/20 players online:Alex, Steve").unwrap();
+        assert_eq!(players.count, 2);
+        assert_eq!(players.name_tags, ["Alex", "Steve"]);
+
+        let players = parse_list(
+            "[12:00:00] [Server thread/INFO]: \u{a7}6There are \u{a7}c1\u{a7}6 of a max of \u{a7}c20\u{a7}6 players online: \u{a7}fAlex",
+        )
+        .unwrap();
+        assert_eq!(players.count, 1);
+        assert_eq!(players.name_tags, ["Alex"]);
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert!(parse_list("[12:00:00] [Server thread/INFO]: Done (3.2s)! For help").is_none());
+        assert!(parse_list("There are no players").is_none());
+    }
+
+    #[test]
+    fn rcon_settings_stay_hidden_and_unchanged() {
+        let directory = std::env::temp_dir().join("web_server_minecraft_properties");
+        fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("server.properties");
+        fs::write(
+            &file,
+            "enable-rcon=true\nrcon.password=hunter2\nrcon.port=25575\nmotd=Hi\n",
+        )
+        .unwrap();
+        let mut server = Server::new().with_properties_file(file.clone());
+
+        let properties = server.properties().unwrap();
+        assert_eq!(properties["rcon.password"], "********");
+        assert_eq!(properties["motd"], "Hi");
+
+        // the page sends back what it was shown
+        let mut changes = properties;
+        changes.insert("motd".to_owned(), "Welcome".to_owned());
+        server.set_properties(changes).unwrap();
+        let text = fs::read_to_string(&file).unwrap();
+        assert!(text.contains("rcon.password=hunter2"), "{text}");
+        assert!(text.contains("motd=Welcome"), "{text}");
+
+        for (key, value) in [
+            ("rcon.password", "secret"),
+            ("rcon.port", "25576"),
+            ("enable-rcon", "false"),
+        ] {
+            let failure = server
+                .set_properties(BTreeMap::from([(key.to_owned(), value.to_owned())]))
+                .unwrap_err();
+            assert!(
+                matches!(&failure, CommandFailure::Rejected { reason } if reason.starts_with(key)),
+                "{failure:?}"
+            );
+        }
+        assert_eq!(fs::read_to_string(&file).unwrap(), text);
+    }
+
+    /// Updates the status of `server` until `done` or a few seconds have passed
+    fn update_until(server: &mut Server, done: impl Fn(&Server) -> bool) {
+        for _ in 0..50 {
+            server.update_status().unwrap();
+            if done(server) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        panic!("the server didn't get there: {}", server.to_json().unwrap());
+    }
+
+    #[test]
+    fn direct_servers_answer_list_and_report_their_exit() {
+        let directory = std::env::temp_dir().join("web_server_minecraft_direct");
+        fs::create_dir_all(&directory).unwrap();
+        let script = "while read line; do case $line in \
+            list) echo 'There are 1 of a max of 20 players online: Alex';; \
+            crash) exit 3;; esac; done";
+        let mut server = Server::with_settings(
+            Settings::new("minecraft_direct"),
+            directory.join("no_screenlog.0"),
+        )
+        .with_backend(Box::new(Supervisor::new(["sh", "-c", script])));
+
+        server.start().unwrap();
+        // without a status script `list` is typed into the console
+        update_until(&mut server, |server| server.players.count == 1);
+        assert_eq!(server.players.name_tags, ["Alex"]);
+        assert_eq!(server.lifecycle.state(), State::Running);
+        assert!(server.to_json().unwrap().contains(r#""backend":"direct""#));
+
+        server.send_console("crash").unwrap();
+        update_until(&mut server, |server| {
+            server.lifecycle.state() == State::Crashed
+        });
+        assert_eq!(server.last_exit().unwrap().code, Some(3));
+    }
+}
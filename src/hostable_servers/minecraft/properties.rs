@@ -0,0 +1,487 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/minecraft/properties.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Reads and writes `server.properties` without losing its comments or order
+//! =============================================================
+
+use crate::hostable_servers::CommandFailure;
+use std::{fmt::Write as _, fs, path::Path};
+
+/// What a known key holds
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// `true` or `false`
+    Bool,
+    /// Whole number between both, inclusive
+    Int(i64, i64),
+    /// One of the names, older servers use the numbers
+    Choice(&'static [&'static str]),
+    /// Anything
+    Text,
+}
+
+/// Keys holding passwords, [`HIDDEN`] is shown instead of their values
+pub const SECRETS: &[&str] = &["rcon.password"];
+
+/// Shown instead of the value of a key in [`SECRETS`], sending it back changes nothing
+pub const HIDDEN: &str = "********";
+
+/// Keys the RCON settings of the web server config have to match, they are changed there
+pub const MANAGED: &[&str] = &["enable-rcon", "rcon.password", "rcon.port"];
+
+/// Keys of the vanilla server and what they hold, sorted like the server writes them
+const KNOWN: &[(&str, Kind)] = &[
+    ("accepts-transfers", Kind::Bool),
+    ("allow-flight", Kind::Bool),
+    ("allow-nether", Kind::Bool),
+    ("broadcast-console-to-ops", Kind::Bool),
+    ("broadcast-rcon-to-ops", Kind::Bool),
+    (
+        "difficulty",
+        Kind::Choice(&["peaceful", "easy", "normal", "hard", "0", "1", "2", "3"]),
+    ),
+    ("enable-command-block", Kind::Bool),
+    ("enable-jmx-monitoring", Kind::Bool),
+    ("enable-query", Kind::Bool),
+    ("enable-rcon", Kind::Bool),
+    ("enable-status", Kind::Bool),
+    ("enforce-secure-profile", Kind::Bool),
+    ("enforce-whitelist", Kind::Bool),
+    ("entity-broadcast-range-percentage", Kind::Int(10, 1000)),
+    ("force-gamemode", Kind::Bool),
+    ("function-permission-level", Kind::Int(1, 4)),
+    (
+        "gamemode",
+        Kind::Choice(&[
+            "survival",
+            "creative",
+            "adventure",
+            "spectator",
+            "0",
+            "1",
+            "2",
+            "3",
+        ]),
+    ),
+    ("generate-structures", Kind::Bool),
+    ("generator-settings", Kind::Text),
+    ("hardcore", Kind::Bool),
+    ("hide-online-players", Kind::Bool),
+    ("initial-disabled-packs", Kind::Text),
+    ("initial-enabled-packs", Kind::Text),
+    ("level-name", Kind::Text),
+    ("level-seed", Kind::Text),
+    ("level-type", Kind::Text),
+    ("log-ips", Kind::Bool),
+    (
+        "max-chained-neighbor-updates",
+        Kind::Int(-1, i32::MAX as i64),
+    ),
+    ("max-players", Kind::Int(0, i32::MAX as i64)),
+    ("max-tick-time", Kind::Int(-1, i64::MAX)),
+    ("max-world-size", Kind::Int(1, 29_999_984)),
+    ("motd", Kind::Text),
+    (
+        "network-compression-threshold",
+        Kind::Int(-1, i32::MAX as i64),
+    ),
+    ("online-mode", Kind::Bool),
+    ("op-permission-level", Kind::Int(0, 4)),
+    ("player-idle-timeout", Kind::Int(0, i32::MAX as i64)),
+    ("prevent-proxy-connections", Kind::Bool),
+    ("pvp", Kind::Bool),
+    ("query.port", Kind::Int(1, 65535)),
+    ("rate-limit", Kind::Int(0, i32::MAX as i64)),
+    ("rcon.password", Kind::Text),
+    ("rcon.port", Kind::Int(1, 65535)),
+    (
+        "region-file-compression",
+        Kind::Choice(&["deflate", "lz4", "none"]),
+    ),
+    ("require-resource-pack", Kind::Bool),
+    ("resource-pack", Kind::Text),
+    ("resource-pack-id", Kind::Text),
+    ("resource-pack-prompt", Kind::Text),
+    ("resource-pack-sha1", Kind::Text),
+    ("server-ip", Kind::Text),
+    ("server-port", Kind::Int(1, 65535)),
+    ("simulation-distance", Kind::Int(3, 32)),
+    ("spawn-animals", Kind::Bool),
+    ("spawn-monsters", Kind::Bool),
+    ("spawn-npcs", Kind::Bool),
+    ("spawn-protection", Kind::Int(0, i32::MAX as i64)),
+    ("sync-chunk-writes", Kind::Bool),
+    ("text-filtering-config", Kind::Text),
+    ("use-native-transport", Kind::Bool),
+    ("view-distance", Kind::Int(3, 32)),
+    ("white-list", Kind::Bool),
+];
+
+/// Line of the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// Comment or blank line, kept as it is
+    Other(String),
+    /// `key=value`, possibly continued over several lines
+    Entry {
+        /// Key without escapes
+        key: String,
+        /// Value without escapes
+        value: String,
+        /// Text in the file, `None` once the value was changed
+        raw: Option<String>,
+    },
+}
+
+/// Content of a `server.properties`
+///
+/// Unchanged lines are written back exactly as they were read,
+/// changed values are written in the place of the old ones and new keys at the end
+///
+/// # Example
+/// ```
+/// use web_server::hostable_servers::minecraft::properties::Properties;
+///
+/// let mut properties = Properties::parse("#Minecraft server properties\ndifficulty=easy\nmotd=A Minecraft Server\n");
+/// properties.set("difficulty", "hard").unwrap();
+/// properties.set("pvp", "false").unwrap();
+/// assert!(properties.set("max-players", "lots").is_err());
+///
+/// assert_eq!(properties.get("difficulty"), Some("hard"));
+/// assert_eq!(
+///     properties.render(),
+///     "#Minecraft server properties\ndifficulty=hard\nmotd=A Minecraft Server\npvp=false\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Properties {
+    /// Every line in order
+    lines: Vec<Line>,
+}
+
+impl Properties {
+    /// Parses the text of a properties file, like `java.util.Properties` does
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        let mut lines = Vec::new();
+        let mut physical = text.lines();
+        while let Some(line) = physical.next() {
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with(['#', '!']) {
+                lines.push(Line::Other(line.to_owned()));
+                continue;
+            }
+
+            // an odd number of backslashes at the end continues the line
+            let mut raw = line.to_owned();
+            let mut logical = line.to_owned();
+            while continues(&logical) {
+                logical.pop();
+                let Some(next) = physical.next() else {
+                    break;
+                };
+                raw.push('\n');
+                raw.push_str(next);
+                logical.push_str(next.trim_start());
+            }
+            let (key, value) = split_entry(logical.trim_start());
+            lines.push(Line::Entry {
+                key,
+                value,
+                raw: Some(raw),
+            });
+        }
+        Self { lines }
+    }
+
+    /// Reads and parses the file at `path`
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unreadable`] if it can't be read
+    pub fn load(path: &Path) -> Result<Self, CommandFailure> {
+        fs::read_to_string(path)
+            .map(|text| Self::parse(&text))
+            .map_err(|e| CommandFailure::Unreadable {
+                file: path.display().to_string(),
+                reason: e.to_string(),
+            })
+    }
+
+    /// Writes the file to `path`, through a temporary file so the server never reads half of it
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unwritable`] if it can't be written
+    pub fn save(&self, path: &Path) -> Result<(), CommandFailure> {
+        let temporary = path.with_extension("properties.tmp");
+        fs::write(&temporary, self.render())
+            .and_then(|()| fs::rename(&temporary, path))
+            .map_err(|e| CommandFailure::Unwritable {
+                file: path.display().to_string(),
+                reason: e.to_string(),
+            })
+    }
+
+    /// Returns the value of `key`, the last one if it is in the file more than once
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter()
+            .filter(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+            .last()
+    }
+
+    /// Returns every key and value in the order of the file
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry { key, value, .. } => Some((key.as_str(), value.as_str())),
+            Line::Other(_) => None,
+        })
+    }
+
+    /// Sets `key` to `value`, adding it at the end if it isn't in the file yet
+    ///
+    /// # Errors
+    /// Returns why the value doesn't fit a key the vanilla server knows, or the key is empty
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        validate(key, value)?;
+        let mut found = false;
+        for line in &mut self.lines {
+            if let Line::Entry {
+                key: name,
+                value: old,
+                raw,
+            } = line
+            {
+                if name == key {
+                    found = true;
+                    if old != value {
+                        value.clone_into(old);
+                        *raw = None;
+                    }
+                }
+            }
+        }
+        if !found {
+            self.lines.push(Line::Entry {
+                key: key.to_owned(),
+                value: value.to_owned(),
+                raw: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the text of the file
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        for line in &self.lines {
+            match line {
+                Line::Other(line)
+                | Line::Entry {
+                    raw: Some(line), ..
+                } => text.push_str(line),
+                Line::Entry {
+                    key,
+                    value,
+                    raw: None,
+                } => {
+                    let _ = write!(text, "{}={}", escape(key, true), escape(value, false));
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Checks that `value` fits `key` if the vanilla server knows it
+///
+/// # Errors
+/// Returns what is wrong, like `"max-players" has to be a number between 0 and 2147483647`
+pub fn validate(key: &str, value: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("Keys can't be empty".to_owned());
+    }
+    let Some((_, kind)) = KNOWN.iter().find(|(name, _)| *name == key) else {
+        // mods and plugins have keys of their own
+        return Ok(());
+    };
+    match kind {
+        Kind::Bool if value != "true" && value != "false" => {
+            Err(format!("{key:?} has to be true or false"))
+        }
+        Kind::Int(min, max)
+            if !value
+                .parse()
+                .is_ok_and(|number: i64| (*min..=*max).contains(&number)) =>
+        {
+            Err(format!(
+                "{key:?} has to be a number between {min} and {max}"
+            ))
+        }
+        Kind::Choice(choices) if !choices.contains(&value) => Err(format!(
+            "{key:?} has to be one of {}",
+            choices
+                .iter()
+                .filter(|choice| !choice.starts_with(|c: char| c.is_ascii_digit()))
+                .copied()
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns true if the line ends with an odd number of backslashes
+fn continues(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+/// Splits a logical line into its key and value, both unescaped
+///
+/// The key ends at the first unescaped `=`, `:` or whitespace
+fn split_entry(line: &str) -> (String, String) {
+    let mut escaped = false;
+    let end = line
+        .char_indices()
+        .find(|(_, c)| {
+            let separator = !escaped && (*c == '=' || *c == ':' || c.is_whitespace());
+            escaped = !escaped && *c == '\\';
+            separator
+        })
+        .map_or(line.len(), |(i, _)| i);
+    let (key, rest) = line.split_at(end);
+
+    let rest = rest.trim_start_matches([' ', '\t', '\x0c']);
+    let rest = rest.strip_prefix(['=', ':']).unwrap_or(rest);
+    let value = rest.trim_start_matches([' ', '\t', '\x0c']);
+    (unescape(key), unescape(value))
+}
+
+/// Resolves `\t`, `\n`, `\uXXXX` and the other escapes
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('f') => unescaped.push('\x0c'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    unescaped.push(c);
+                } else {
+                    unescaped.push_str("\\u");
+                    unescaped.push_str(&hex);
+                }
+            }
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Escapes `text` for the file, keys have their separators escaped as well
+fn escape(text: &str, key: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\x0c' => escaped.push_str("\\f"),
+            // leading spaces of a value would be lost otherwise
+            ' ' if key || i == 0 => escaped.push_str("\\ "),
+            '=' | ':' | '#' | '!' if key => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Properties;
+
+    /// Start of a file as the server writes it, with a comment of the admin
+    const FILE: &str = "#Minecraft server properties
+#Sat Oct 18 10:00:00 CEST 2026
+allow-flight=false
+difficulty=easy
+
+# keep it friendly
+motd=\\u00A76Nacor's \\u00A7lServer
+level-seed=
+max-players=20
+";
+
+    #[test]
+    fn unchanged_files_stay_the_same() {
+        let properties = Properties::parse(FILE);
+        assert_eq!(properties.render(), FILE);
+        assert_eq!(properties.get("motd"), Some("\u{a7}6Nacor's \u{a7}lServer"));
+        assert_eq!(properties.get("level-seed"), Some(""));
+        assert_eq!(properties.iter().count(), 5);
+    }
+
+    #[test]
+    fn changes_stay_in_place() {
+        let mut properties = Properties::parse(FILE);
+        properties.set("difficulty", "hard").unwrap();
+        properties
+            .set("motd", "  Spaces, \\ and\nnew lines")
+            .unwrap();
+        properties.set("white-list", "true").unwrap();
+
+        let text = properties.render();
+        assert!(text.starts_with("#Minecraft server properties\n#Sat Oct 18"));
+        assert!(text.contains("allow-flight=false\ndifficulty=hard\n\n# keep it friendly\n"));
+        assert!(text.ends_with("max-players=20\nwhite-list=true\n"));
+
+        let again = Properties::parse(&text);
+        assert_eq!(again.get("motd"), Some("  Spaces, \\ and\nnew lines"));
+        assert_eq!(again.get("white-list"), Some("true"));
+    }
+
+    #[test]
+    fn known_keys_are_checked() {
+        let mut properties = Properties::parse(FILE);
+        assert!(properties.set("pvp", "yes").is_err());
+        assert!(properties.set("view-distance", "64").is_err());
+        assert!(properties.set("gamemode", "hardcore").is_err());
+        assert!(properties.set("", "value").is_err());
+        assert!(properties.set("some-plugin-setting", "anything").is_ok());
+        assert!(properties.set("gamemode", "1").is_ok());
+        assert_eq!(properties.get("difficulty"), Some("easy"));
+    }
+
+    #[test]
+    fn reads_what_java_writes() {
+        let properties = Properties::parse(
+            "key\\ with\\ spaces = value\n  indented:colon\nlong=first \\\n    second\nalone\n",
+        );
+        let entries: Vec<(&str, &str)> = properties.iter().collect();
+        assert_eq!(
+            entries,
+            [
+                ("key with spaces", "value"),
+                ("indented", "colon"),
+                ("long", "first second"),
+                ("alone", ""),
+            ]
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/minecraft/query.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! The UDP Query protocol of `enable-query=true`, with every player's name
+//! =============================================================
+
+use serde::Serialize;
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// First two bytes of every request
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+/// Packet type of the handshake asking for a challenge token
+const HANDSHAKE: u8 = 9;
+/// Packet type of a stat request
+const STAT: u8 = 0;
+/// What the full stat answer starts with after the session id
+const STAT_PADDING: &[u8] = b"splitnum\0\x80\0";
+/// What separates the key values from the players in the full stat answer
+const PLAYERS_PADDING: &[u8] = b"\x01player_\0\0";
+/// Challenge tokens are valid for 30 seconds, a new one is asked for a bit before that
+const TOKEN_LIFETIME: Duration = Duration::from_secs(25);
+
+/// Everything the full stat answer tells
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FullStat {
+    /// `motd` of `server.properties`, without formatting codes
+    pub motd: String,
+    /// Always `SMP`
+    pub game_type: String,
+    /// Always `MINECRAFT`
+    pub game_id: String,
+    /// Version of the server, like `1.20.4`
+    pub version: String,
+    /// Server software of servers with plugins, like `Paper on Bukkit 1.20.4-R0.1-SNAPSHOT`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_mod: Option<String>,
+    /// Plugins with their version, like `WorldEdit 7.2.15`
+    pub plugins: Vec<String>,
+    /// Name of the world
+    pub map: String,
+    /// Players online
+    pub online: usize,
+    /// Player slots
+    pub max: usize,
+    /// Game port
+    pub host_port: Option<u16>,
+    /// Address the game port is bound to
+    pub host_ip: String,
+    /// Name of every player online
+    pub players: Vec<String>,
+}
+
+/// Why a query failed
+#[derive(Debug)]
+pub enum QueryError {
+    /// The socket couldn't be opened, sent or read from
+    Io(io::Error),
+    /// Nothing came back, not even after the retries
+    NoAnswer {
+        /// Number of requests sent
+        attempts: u32,
+    },
+    /// The server sent something that isn't a query answer
+    Protocol(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::NoAnswer { attempts } => write!(
+                f,
+                "no answer to {attempts} requests, is `enable-query=true` set?"
+            ),
+            Self::Protocol(reason) => write!(f, "unexpected answer: {reason}"),
+        }
+    }
+}
+impl std::error::Error for QueryError {}
+impl From<io::Error> for QueryError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A UDP socket asking one server for its full stat
+///
+/// The challenge token is kept between requests and asked for again when it
+/// ran out. UDP packets get lost, every request is sent again up to `retries` times
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use web_server::hostable_servers::minecraft::query::Query;
+///
+/// let mut query = Query::connect("127.0.0.1:25565", Duration::from_secs(1), 2).unwrap();
+/// println!("{:?}", query.full_stat().unwrap().players);
+/// ```
+#[derive(Debug)]
+pub struct Query {
+    /// Socket connected to the query port
+    socket: UdpSocket,
+    /// Id the server puts into its answers
+    session: i32,
+    /// Challenge token and when it was received
+    token: Option<(i32, Instant)>,
+    /// Number of requests sent again when the answer doesn't come
+    retries: u32,
+}
+
+impl Query {
+    /// Opens a socket sending to the query port at `address`
+    ///
+    /// `timeout` is how long every answer is waited for
+    ///
+    /// # Errors
+    /// Returns [`QueryError::Io`] if the address doesn't resolve or no socket can be opened
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        timeout: Duration,
+        retries: u32,
+    ) -> Result<Self, QueryError> {
+        let address: SocketAddr = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "the address didn't resolve"))?;
+        let local: SocketAddr = if address.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 16], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(address)?;
+        socket.set_read_timeout(Some(timeout))?;
+
+        // the server only looks at the lower 4 bits of every byte
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        let session = i32::from_be_bytes(nanos.to_be_bytes()) & 0x0F0F_0F0F;
+        Ok(Self {
+            socket,
+            session,
+            token: None,
+            retries,
+        })
+    }
+
+    /// Asks for the full stat, with a new challenge token if needed
+    ///
+    /// A stat request without an answer may have had a token the server already
+    /// forgot, so the token is asked for again before the next try
+    ///
+    /// # Errors
+    /// Returns a [`QueryError`] if no answer came after the retries or it wasn't a full stat
+    pub fn full_stat(&mut self) -> Result<FullStat, QueryError> {
+        let mut attempts = 0;
+        while attempts <= self.retries {
+            let token = match self.token {
+                Some((token, received)) if received.elapsed() < TOKEN_LIFETIME => token,
+                _ => {
+                    attempts += 1;
+                    match self.request(HANDSHAKE, &[])? {
+                        Some(answer) => {
+                            let token = parse_token(&answer)?;
+                            self.token = Some((token, Instant::now()));
+                            token
+                        }
+                        None => continue,
+                    }
+                }
+            };
+
+            attempts += 1;
+            let mut payload = token.to_be_bytes().to_vec();
+            // the padding makes it a full stat instead of the basic one
+            payload.extend_from_slice(&[0; 4]);
+            match self.request(STAT, &payload)? {
+                Some(answer) => return parse_full_stat(&answer),
+                None => self.token = None,
+            }
+        }
+        Err(QueryError::NoAnswer { attempts })
+    }
+
+    /// Sends a request and returns the answer after the session id, `None` after a timeout
+    fn request(&self, kind: u8, payload: &[u8]) -> Result<Option<Vec<u8>>, QueryError> {
+        let mut packet = MAGIC.to_vec();
+        packet.push(kind);
+        packet.extend_from_slice(&self.session.to_be_bytes());
+        packet.extend_from_slice(payload);
+        self.socket.send(&packet)?;
+
+        let mut buffer = vec![0; 65_536];
+        loop {
+            let length = match self.socket.recv(&mut buffer) {
+                Ok(length) => length,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                // an ICMP port unreachable from an earlier request, nobody listens
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            // late answers to requests that timed out are skipped
+            if length >= 5 && buffer[0] == kind && buffer[1..5] == self.session.to_be_bytes() {
+                return Ok(Some(buffer[5..length].to_vec()));
+            }
+        }
+    }
+}
+
+/// Reads the challenge token of a handshake answer, a number as text
+fn parse_token(answer: &[u8]) -> Result<i32, QueryError> {
+    let text = answer.split(|&byte| byte == 0).next().unwrap_or_default();
+    std::str::from_utf8(text)
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .ok_or_else(|| QueryError::Protocol("the challenge token isn't a number".to_owned()))
+}
+
+/// Reads a full stat answer: padding, key value pairs, padding, player names
+fn parse_full_stat(answer: &[u8]) -> Result<FullStat, QueryError> {
+    let rest = answer
+        .strip_prefix(STAT_PADDING)
+        .ok_or_else(|| QueryError::Protocol("not a full stat".to_owned()))?;
+    let mut strings = rest.split(|&byte| byte == 0);
+    let mut next = || {
+        strings
+            .next()
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+    };
+
+    let mut values = std::collections::HashMap::new();
+    loop {
+        let key =
+            next().ok_or_else(|| QueryError::Protocol("the key values end early".to_owned()))?;
+        if key.is_empty() {
+            break;
+        }
+        values.insert(key, next().unwrap_or_default());
+    }
+    // the split leaves the padding as `\x01player_` and an empty string
+    if next().as_deref().map(str::as_bytes) != Some(&PLAYERS_PADDING[..PLAYERS_PADDING.len() - 2])
+        || next().as_deref() != Some("")
+    {
+        return Err(QueryError::Protocol("no player list".to_owned()));
+    }
+    let players = std::iter::from_fn(next)
+        .take_while(|name| !name.is_empty())
+        .collect();
+
+    let mut value = |key: &str| values.remove(key).unwrap_or_default();
+    let (server_mod, plugins) = parse_plugins(&value("plugins"));
+    Ok(FullStat {
+        motd: super::strip_formatting(&value("hostname")),
+        game_type: value("gametype"),
+        game_id: value("game_id"),
+        version: value("version"),
+        server_mod,
+        plugins,
+        map: value("map"),
+        online: value("numplayers").parse().unwrap_or_default(),
+        max: value("maxplayers").parse().unwrap_or_default(),
+        host_port: value("hostport").parse().ok(),
+        host_ip: value("hostip"),
+        players,
+    })
+}
+
+/// Splits the `plugins` value, `Paper on Bukkit 1.20.4: WorldEdit 7.2.15; Essentials 2.20.1`,
+/// into the server software and its plugins. Vanilla servers leave it empty
+fn parse_plugins(value: &str) -> (Option<String>, Vec<String>) {
+    if value.trim().is_empty() {
+        return (None, Vec::new());
+    }
+    let (server_mod, plugins) = value.split_once(':').unwrap_or((value, ""));
+    (
+        Some(server_mod.trim().to_owned()),
+        plugins
+            .split(';')
+            .map(str::trim)
+            .filter(|plugin| !plugin.is_empty())
+            .map(ToOwned::to_owned)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_plugins, Query, QueryError, HANDSHAKE, MAGIC, PLAYERS_PADDING, STAT, STAT_PADDING,
+    };
+    use std::{
+        net::{SocketAddr, UdpSocket},
+        thread,
+        time::Duration,
+    };
+
+    /// How long the client under test waits for every answer
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// A full stat answer of a Paper server, after the session id
+    fn full_stat() -> Vec<u8> {
+        let mut answer = STAT_PADDING.to_vec();
+        for (key, value) in [
+            ("hostname", "\u{a7}aA Minecraft Server"),
+            ("gametype", "SMP"),
+            ("game_id", "MINECRAFT"),
+            ("version", "1.20.4"),
+            (
+                "plugins",
+                "Paper on Bukkit 1.20.4: WorldEdit 7.2.15; Essentials 2.20.1",
+            ),
+            ("map", "world"),
+            ("numplayers", "3"),
+            ("maxplayers", "20"),
+            ("hostport", "25565"),
+            ("hostip", "0.0.0.0"),
+        ] {
+            answer.extend_from_slice(key.as_bytes());
+            answer.push(0);
+            answer.extend_from_slice(value.as_bytes());
+            answer.push(0);
+        }
+        answer.push(0);
+        answer.extend_from_slice(PLAYERS_PADDING);
+        for name in ["Alex", "Steve", "Notch"] {
+            answer.extend_from_slice(name.as_bytes());
+            answer.push(0);
+        }
+        answer.push(0);
+        answer
+    }
+
+    /// Answers queries like a Minecraft server
+    ///
+    /// Every handshake gets a new token and only the newest is accepted. `drop`
+    /// decides for the n-th packet whether it gets lost, `rotate` whether the
+    /// token changes after a stat was answered
+    fn responder(drop: fn(usize) -> bool, rotate: bool) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").expect("a free port");
+        let address = socket.local_addr().expect("a bound address");
+        thread::spawn(move || {
+            let mut token = 9_513_307_i32;
+            let mut buffer = [0; 1500];
+            for n in 0.. {
+                let Ok((length, client)) = socket.recv_from(&mut buffer) else {
+                    return;
+                };
+                let packet = &buffer[..length];
+                if drop(n) || packet[..2] != MAGIC {
+                    continue;
+                }
+                let mut answer = vec![packet[2]];
+                answer.extend_from_slice(&packet[3..7]);
+                match packet[2] {
+                    HANDSHAKE => {
+                        token += 1;
+                        answer.extend_from_slice(token.to_string().as_bytes());
+                        answer.push(0);
+                    }
+                    STAT if packet[7..11] == token.to_be_bytes() && length == 15 => {
+                        answer.extend_from_slice(&full_stat());
+                        if rotate {
+                            token += 1;
+                        }
+                    }
+                    _ => continue,
+                }
+                socket
+                    .send_to(&answer, client)
+                    .expect("the client is listening");
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn reads_the_full_stat() {
+        let address = responder(|_| false, false);
+        let mut query = Query::connect(address, TIMEOUT, 0).unwrap();
+        let stat = query.full_stat().unwrap();
+        assert_eq!(stat.motd, "A Minecraft Server");
+        assert_eq!(
+            (stat.game_type.as_str(), stat.game_id.as_str()),
+            ("SMP", "MINECRAFT")
+        );
+        assert_eq!(stat.version, "1.20.4");
+        assert_eq!(stat.server_mod.as_deref(), Some("Paper on Bukkit 1.20.4"));
+        assert_eq!(stat.plugins, ["WorldEdit 7.2.15", "Essentials 2.20.1"]);
+        assert_eq!(stat.map, "world");
+        assert_eq!((stat.online, stat.max), (3, 20));
+        assert_eq!(stat.host_port, Some(25565));
+        assert_eq!(stat.players, ["Alex", "Steve", "Notch"]);
+
+        // the token is kept
+        assert_eq!(query.full_stat().unwrap(), stat);
+    }
+
+    #[test]
+    fn retries_lost_packets() {
+        // the first handshake and the first stat request get lost
+        let address = responder(|n| n == 0 || n == 2, false);
+        let mut query = Query::connect(address, TIMEOUT, 3).unwrap();
+        assert_eq!(query.full_stat().unwrap().players.len(), 3);
+    }
+
+    #[test]
+    fn asks_for_a_new_token_after_it_ran_out() {
+        let address = responder(|_| false, true);
+        let mut query = Query::connect(address, TIMEOUT, 2).unwrap();
+        query.full_stat().unwrap();
+        // the server forgot the token, the request with it isn't answered
+        assert_eq!(query.full_stat().unwrap().map, "world");
+    }
+
+    #[test]
+    fn gives_up_without_answers() {
+        let address = responder(|_| true, false);
+        let mut query = Query::connect(address, TIMEOUT, 2).unwrap();
+        assert!(matches!(
+            query.full_stat(),
+            Err(QueryError::NoAnswer { attempts: 3 })
+        ));
+    }
+
+    #[test]
+    fn splits_plugins() {
+        assert_eq!(parse_plugins(""), (None, vec![]));
+        assert_eq!(
+            parse_plugins("CraftBukkit on Bukkit 1.7.10"),
+            (Some("CraftBukkit on Bukkit 1.7.10".to_owned()), vec![])
+        );
+        assert_eq!(
+            parse_plugins("Paper: LuckPerms 5.4.102"),
+            (
+                Some("Paper".to_owned()),
+                vec!["LuckPerms 5.4.102".to_owned()]
+            )
+        );
+    }
+}
@@ -0,0 +1,511 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/minecraft/slp.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Server List Ping, what the multiplayer menu asks a server
+//! =============================================================
+
+use super::strip_formatting;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+/// Largest status answer accepted, favicons make it big
+const MAX_PACKET: usize = 1024 * 1024;
+
+/// What a server tells the multiplayer menu
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Status {
+    /// Version name, like `1.20.4` or `Paper 1.20.4`
+    pub version: String,
+    /// Protocol number, old servers don't always send one
+    pub protocol: Option<i32>,
+    /// Message of the day without formatting codes
+    pub motd: String,
+    /// Players online
+    pub online: usize,
+    /// Player slots
+    pub max: usize,
+    /// Some of the online players, servers send at most 12
+    pub sample: Vec<String>,
+    /// Server icon as a `data:image/png;base64,` URI
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    /// Time the server took to answer, in milliseconds
+    pub latency_ms: u64,
+    /// True if the server only understood the ping of versions before 1.7
+    pub legacy: bool,
+}
+
+/// Why a ping failed
+#[derive(Debug)]
+pub enum SlpError {
+    /// Connecting, reading or writing failed
+    Io(io::Error),
+    /// The server sent something that isn't a status
+    Protocol(String),
+}
+
+impl fmt::Display for SlpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Protocol(reason) => write!(f, "unexpected answer: {reason}"),
+        }
+    }
+}
+impl std::error::Error for SlpError {}
+impl From<io::Error> for SlpError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Asks the server at `address` for its status, the way of 1.7 and later first
+///
+/// Servers that don't understand that are asked again the way of older versions
+///
+/// # Errors
+/// Returns what the legacy ping got if it wasn't a kick message, the error of the
+/// modern ping otherwise
+pub fn ping(address: &str, timeout: Duration) -> Result<Status, SlpError> {
+    let socket = resolve(address)?;
+    ping_modern(socket, address, timeout).or_else(|e| {
+        ping_legacy(socket, timeout).map_err(|legacy| match legacy {
+            SlpError::Protocol(_) => legacy,
+            SlpError::Io(_) => e,
+        })
+    })
+}
+
+/// Handshake, status request and ping of Minecraft 1.7 and later
+///
+/// `host` is sent in the handshake, proxies pick the server by it
+///
+/// # Errors
+/// Returns an [`SlpError`] if the server doesn't answer with a status
+pub fn ping_modern(address: SocketAddr, host: &str, timeout: Duration) -> Result<Status, SlpError> {
+    let mut stream = connect(address, timeout)?;
+    let host = host.rsplit_once(':').map_or(host, |(host, _)| host);
+
+    let mut handshake = Vec::new();
+    write_var_int(&mut handshake, 0x00);
+    // -1, the server answers with the version it speaks
+    write_var_int(&mut handshake, -1);
+    write_var_int(
+        &mut handshake,
+        i32::try_from(host.len()).unwrap_or_default(),
+    );
+    handshake.extend_from_slice(host.as_bytes());
+    handshake.extend_from_slice(&address.port().to_be_bytes());
+    // next state: status
+    write_var_int(&mut handshake, 1);
+    write_packet(&mut stream, &handshake)?;
+
+    let requested = Instant::now();
+    write_packet(&mut stream, &[0x00])?;
+    let packet = read_packet(&mut stream)?;
+    let mut latency = requested.elapsed();
+    let mut reader = packet.as_slice();
+    if read_var_int(&mut reader)? != 0x00 {
+        return Err(SlpError::Protocol("expected a status packet".to_owned()));
+    }
+    let length = usize::try_from(read_var_int(&mut reader)?)
+        .ok()
+        .filter(|length| *length <= reader.len())
+        .ok_or_else(|| SlpError::Protocol("status length out of range".to_owned()))?;
+    let response: Response =
+        serde_json::from_slice(&reader[..length]).map_err(|e| SlpError::Protocol(e.to_string()))?;
+
+    // some servers close the connection instead of answering the ping
+    let mut ping = vec![0x01];
+    let payload = 0x5350_4c50_i64; // "SPLP"
+    ping.extend_from_slice(&payload.to_be_bytes());
+    let pinged = Instant::now();
+    if write_packet(&mut stream, &ping).is_ok() {
+        if let Ok(pong) = read_packet(&mut stream) {
+            if pong == ping {
+                latency = pinged.elapsed();
+            }
+        }
+    }
+
+    let players = response.players.unwrap_or_default();
+    Ok(Status {
+        version: response
+            .version
+            .as_ref()
+            .map_or_else(String::new, |v| v.name.clone()),
+        protocol: response.version.map(|v| v.protocol),
+        motd: strip_formatting(
+            &response
+                .description
+                .as_ref()
+                .map_or_else(String::new, text_of),
+        ),
+        online: players.online,
+        max: players.max,
+        sample: players
+            .sample
+            .into_iter()
+            .map(|player| player.name)
+            // fake entries some servers use for messages
+            .filter(|name| !name.contains('\u{a7}'))
+            .collect(),
+        favicon: response.favicon,
+        latency_ms: u64::try_from(latency.as_millis()).unwrap_or(u64::MAX),
+        legacy: false,
+    })
+}
+
+/// Ping of Minecraft 1.6 and before, answered with a kick message
+///
+/// # Errors
+/// Returns an [`SlpError`] if the server doesn't answer with a status
+pub fn ping_legacy(address: SocketAddr, timeout: Duration) -> Result<Status, SlpError> {
+    let mut stream = connect(address, timeout)?;
+    let requested = Instant::now();
+    stream.write_all(&[0xFE, 0x01])?;
+
+    let mut head = [0; 3];
+    stream.read_exact(&mut head)?;
+    if head[0] != 0xFF {
+        return Err(SlpError::Protocol("expected a kick packet".to_owned()));
+    }
+    let mut text = vec![0; usize::from(u16::from_be_bytes([head[1], head[2]])) * 2];
+    stream.read_exact(&mut text)?;
+    let latency = requested.elapsed();
+    let text: Vec<u16> = text
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    let text = String::from_utf16(&text).map_err(|e| SlpError::Protocol(e.to_string()))?;
+
+    let mut status = parse_legacy(&text)
+        .ok_or_else(|| SlpError::Protocol(format!("{text:?} isn't a status")))?;
+    status.latency_ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+    Ok(status)
+}
+
+/// Reads the kick message of a legacy ping
+///
+/// 1.4 to 1.6 send `§1`, protocol, version, MOTD, online and max players separated by
+/// `\0`, older versions only MOTD, online and max separated by `§`
+fn parse_legacy(text: &str) -> Option<Status> {
+    let (protocol, version, motd, online, max) =
+        if let Some(fields) = text.strip_prefix("\u{a7}1\0") {
+            let fields: Vec<&str> = fields.split('\0').collect();
+            let [protocol, version, motd, online, max] = fields[..] else {
+                return None;
+            };
+            (protocol.parse().ok(), version.to_owned(), motd, online, max)
+        } else {
+            let mut fields = text.rsplitn(3, '\u{a7}');
+            let (max, online, motd) = (fields.next()?, fields.next()?, fields.next()?);
+            (None, String::new(), motd, online, max)
+        };
+    Some(Status {
+        version,
+        protocol,
+        motd: strip_formatting(motd),
+        online: online.parse().ok()?,
+        max: max.parse().ok()?,
+        sample: Vec::new(),
+        favicon: None,
+        latency_ms: 0,
+        legacy: true,
+    })
+}
+
+/// Status answer of the modern ping
+#[derive(Deserialize)]
+struct Response {
+    /// Version the server speaks
+    version: Option<Version>,
+    /// Online players
+    players: Option<Players>,
+    /// MOTD, a string or a chat component
+    description: Option<serde_json::Value>,
+    /// Server icon
+    favicon: Option<String>,
+}
+
+/// `version` of a [`Response`]
+#[derive(Deserialize)]
+struct Version {
+    /// Like `1.20.4`
+    name: String,
+    /// Protocol number
+    protocol: i32,
+}
+
+/// `players` of a [`Response`]
+#[derive(Default, Deserialize)]
+struct Players {
+    /// Player slots
+    max: usize,
+    /// Players online
+    online: usize,
+    /// Some of the online players
+    #[serde(default)]
+    sample: Vec<Sample>,
+}
+
+/// One entry of [`Players::sample`]
+#[derive(Deserialize)]
+struct Sample {
+    /// Name of the player
+    name: String,
+}
+
+/// Text of a chat component, with the text of its `extra` parts
+fn text_of(component: &serde_json::Value) -> String {
+    match component {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(parts) => parts.iter().map(text_of).collect(),
+        serde_json::Value::Object(fields) => {
+            let mut text = fields.get("text").map(text_of).unwrap_or_default();
+            if let Some(extra) = fields.get("extra") {
+                text.push_str(&text_of(extra));
+            }
+            text
+        }
+        _ => String::new(),
+    }
+}
+
+/// Returns the first address `address` resolves to
+fn resolve(address: &str) -> Result<SocketAddr, SlpError> {
+    address.to_socket_addrs()?.next().ok_or_else(|| {
+        SlpError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            "the address didn't resolve",
+        ))
+    })
+}
+
+/// Connects to `address`, every read and write may take up to `timeout`
+fn connect(address: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+/// Writes `data` with its length in front
+fn write_packet(stream: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+    let mut packet = Vec::with_capacity(data.len() + 5);
+    write_var_int(&mut packet, i32::try_from(data.len()).unwrap_or(i32::MAX));
+    packet.extend_from_slice(data);
+    stream.write_all(&packet)
+}
+
+/// Reads a packet, without its length
+fn read_packet(stream: &mut TcpStream) -> Result<Vec<u8>, SlpError> {
+    let length = usize::try_from(read_var_int(stream)?)
+        .ok()
+        .filter(|length| *length <= MAX_PACKET)
+        .ok_or_else(|| SlpError::Protocol("packet length out of range".to_owned()))?;
+    let mut packet = vec![0; length];
+    stream.read_exact(&mut packet).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            // old servers kick right away, anything else isn't Minecraft
+            SlpError::Protocol("the answer ended early".to_owned())
+        } else {
+            SlpError::Io(e)
+        }
+    })?;
+    Ok(packet)
+}
+
+/// Writes `value` as a `VarInt`, 7 bits per byte with the high bit set on all but the last
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+fn write_var_int(buffer: &mut Vec<u8>, value: i32) {
+    // negative numbers take all 5 bytes
+    let mut value = value as u32;
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            buffer.push(byte);
+            return;
+        }
+        buffer.push(byte | 0x80);
+    }
+}
+
+/// Reads a `VarInt`, see [`write_var_int`]
+#[allow(clippy::cast_possible_wrap)]
+fn read_var_int(reader: &mut impl Read) -> Result<i32, SlpError> {
+    let mut value = 0_u32;
+    for i in 0..5 {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= u32::from(byte[0] & 0x7F) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(SlpError::Protocol(
+        "`VarInt` longer than 5 bytes".to_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ping, read_var_int, write_var_int, SlpError};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    /// Timeout of the pings under test
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Status a 1.20 server sends
+    const STATUS: &str = r#"{
+        "version": {"name": "1.20.4", "protocol": 765},
+        "players": {"max": 20, "online": 2, "sample": [
+            {"name": "Alex", "id": "00000000-0000-0000-0000-000000000001"},
+            {"name": "Steve", "id": "00000000-0000-0000-0000-000000000002"}
+        ]},
+        "description": {"text": "A ", "extra": [{"text": "§aMinecraft"}, " Server"]},
+        "favicon": "data:image/png;base64,AAAA"
+    }"#;
+
+    /// Reads a length prefixed packet like a modern server
+    fn read_packet(stream: &mut TcpStream) -> Option<Vec<u8>> {
+        let length = usize::try_from(read_var_int(stream).ok()?).ok()?;
+        let mut packet = vec![0; length];
+        stream.read_exact(&mut packet).ok()?;
+        Some(packet)
+    }
+
+    /// Writes a length prefixed packet like a modern server
+    fn write_packet(stream: &mut TcpStream, packet: &[u8]) {
+        let mut bytes = Vec::new();
+        write_var_int(
+            &mut bytes,
+            i32::try_from(packet.len()).expect("short packet"),
+        );
+        bytes.extend_from_slice(packet);
+        stream.write_all(&bytes).expect("the client is listening");
+    }
+
+    /// Answers the modern ping with [`STATUS`] and echoes the ping
+    fn modern(mut stream: TcpStream) {
+        let handshake = read_packet(&mut stream).expect("a handshake");
+        assert_eq!(handshake[0], 0x00);
+        assert_eq!(handshake.last(), Some(&1), "asks for the status");
+        assert_eq!(read_packet(&mut stream).expect("a request"), [0x00]);
+
+        let mut status = vec![0x00];
+        write_var_int(&mut status, i32::try_from(STATUS.len()).expect("short"));
+        status.extend_from_slice(STATUS.as_bytes());
+        write_packet(&mut stream, &status);
+
+        let ping = read_packet(&mut stream).expect("a ping");
+        assert_eq!(ping[0], 0x01);
+        write_packet(&mut stream, &ping);
+    }
+
+    /// Answers like a 1.6 server, which kicks everybody who doesn't start with 0xFE
+    fn legacy(mut stream: TcpStream, text: &str) {
+        let mut request = [0; 2];
+        if stream.read_exact(&mut request).is_err() || request != [0xFE, 0x01] {
+            return;
+        }
+        let text: Vec<u16> = text.encode_utf16().collect();
+        let mut kick = vec![0xFF];
+        kick.extend_from_slice(&u16::try_from(text.len()).expect("short").to_be_bytes());
+        for unit in text {
+            kick.extend_from_slice(&unit.to_be_bytes());
+        }
+        stream.write_all(&kick).expect("the client is listening");
+    }
+
+    /// Starts a responder on a free port, `answer` handles every connection
+    fn responder(answer: impl Fn(TcpStream) + Send + 'static) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("a free port");
+        let address = listener.local_addr().expect("a bound address").to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                answer(stream);
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn pings_modern_servers() {
+        let address = responder(modern);
+        let status = ping(&address, TIMEOUT).unwrap();
+        assert_eq!(status.version, "1.20.4");
+        assert_eq!(status.protocol, Some(765));
+        assert_eq!(status.motd, "A Minecraft Server");
+        assert_eq!((status.online, status.max), (2, 20));
+        assert_eq!(status.sample, ["Alex", "Steve"]);
+        assert_eq!(
+            status.favicon.as_deref(),
+            Some("data:image/png;base64,AAAA")
+        );
+        assert!(!status.legacy);
+    }
+
+    #[test]
+    fn falls_back_to_the_1_6_ping() {
+        let address = responder(|stream| {
+            legacy(
+                stream,
+                "\u{a7}1\u{0}74\u{0}1.6.4\u{0}\u{a7}cOld\u{a7}r Server\u{0}3\u{0}10",
+            );
+        });
+        let status = ping(&address, TIMEOUT).unwrap();
+        assert!(status.legacy);
+        assert_eq!(status.version, "1.6.4");
+        assert_eq!(status.protocol, Some(74));
+        assert_eq!(status.motd, "Old Server");
+        assert_eq!((status.online, status.max), (3, 10));
+        assert!(status.sample.is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_the_beta_ping() {
+        let address = responder(|stream| legacy(stream, "A Beta Server\u{a7}0\u{a7}8"));
+        let status = ping(&address, TIMEOUT).unwrap();
+        assert!(status.legacy);
+        assert_eq!(status.motd, "A Beta Server");
+        assert_eq!((status.online, status.max), (0, 8));
+        assert_eq!(status.protocol, None);
+    }
+
+    #[test]
+    fn reports_servers_that_answer_nonsense() {
+        let address = responder(|mut stream| {
+            let _ = stream.read(&mut [0; 512]);
+            let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n");
+        });
+        assert!(matches!(
+            ping(&address, TIMEOUT),
+            Err(SlpError::Protocol(_))
+        ));
+    }
+
+    #[test]
+    fn round_trips_var_ints() {
+        for value in [0, 1, 127, 128, 25565, i32::MAX, -1, i32::MIN] {
+            let mut bytes = Vec::new();
+            write_var_int(&mut bytes, value);
+            assert_eq!(read_var_int(&mut bytes.as_slice()).unwrap(), value);
+        }
+        let mut bytes = Vec::new();
+        write_var_int(&mut bytes, -1);
+        assert_eq!(bytes, [0xFF, 0xFF, 0xFF, 0xFF, 0x0F]);
+    }
+}
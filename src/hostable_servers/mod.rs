@@ -1,12 +1,134 @@
 //! =============================================================
-//! Rust Game Hosting Server - hostable_servers/mod.rs
+//! Rust Game Hosting Server - `hostable_servers/mod.rs`
 //!
 //! STATUS: Project is in limbo and may not work on newer Rust versions.
 //! Creates an interface for servers that are supposed to be hosted <3
 //! =============================================================
 
-use std::fmt;
-use std::process::Command;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub mod a2s;
+pub mod arma;
+pub mod backend;
+pub mod console;
+pub mod lifecycle;
+pub mod minecraft;
+pub mod probe;
+pub mod process;
+pub mod rcon;
+pub mod supervisor;
+pub mod watchdog;
+
+use backend::ProcessBackend;
+use lifecycle::{Lifecycle, Observation};
+use probe::{Probe, ProbeResult};
+pub use process::CommandFailure;
+use process::{CancelToken, Output, Process};
+use rcon::{RconClient, RconLogin};
+use supervisor::{Exit, SupervisorStatus};
+use watchdog::WatchdogSettings;
+
+/// Lines of output [`GeneralBashServer::console_output`] returns
+const CONSOLE_LINES: usize = 200;
+/// How long connecting to RCON and every answer may take
+const RCON_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Things to choose from, like the missions of a server, and the chosen ones in their order
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Selection {
+    /// Everything that can be chosen
+    pub available: Vec<String>,
+    /// What is chosen
+    pub selected: Vec<String>,
+}
+
+/// Lists of players a server keeps, like the whitelist of Minecraft
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerList {
+    /// Players allowed to join
+    Whitelist,
+    /// Operators, players allowed to run commands
+    Ops,
+    /// Banned players
+    Bans,
+    /// Banned addresses
+    IpBans,
+}
+impl PlayerList {
+    /// Every list, in the order the web interface shows them
+    pub const ALL: [Self; 4] = [Self::Whitelist, Self::Ops, Self::Bans, Self::IpBans];
+
+    /// Returns the list called `name` in the URLs, like `ip-bans`
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|list| list.name() == name)
+    }
+
+    /// Name of the list in the URLs and messages
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Whitelist => "whitelist",
+            Self::Ops => "ops",
+            Self::Bans => "bans",
+            Self::IpBans => "ip-bans",
+        }
+    }
+}
+
+/// Entry of a [`PlayerList`], the fields a list doesn't have are left out
+///
+/// Serializes like the entries of the Minecraft files, `whitelist.json` and the others
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListEntry {
+    /// Id of the player's account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    /// Name of the player
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Banned address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    /// Permission level of an operator, 1 to 4
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u8>,
+    /// Whether an operator may join a full server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bypasses_player_limit: Option<bool>,
+    /// When the ban was made, like `2024-01-31 18:00:00 +0100`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Who made the ban
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// When the ban ends, `forever` if it doesn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    /// Why the player or address is banned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Player or address added to a [`PlayerList`], like `{"name": "Alex", "reason": "Griefing"}`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListAddition {
+    /// Name of the player, the address for [`PlayerList::IpBans`]
+    #[serde(alias = "ip")]
+    pub name: String,
+    /// Why the player or address is banned, only for bans
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Permission level of an operator, only for ops
+    #[serde(default)]
+    pub level: Option<u8>,
+}
 
 /// Represents a server that can be hosted
 ///
@@ -14,12 +136,18 @@ use std::process::Command;
 /// Doesn't work if there isn't an update.js file in `path_home`.
 /// This file should have update_{`path_home`} function which is called periodically
 /// to update the state in the website
-pub trait HostableServer {
+///
+/// The [`crate::WebServer`] serves requests from several threads, so implementations have to be [`Send`]
+pub trait HostableServer: Send {
     /// Returns the Path to where the server is stored at
     ///
     /// The folder needs to contain an update.js file that has a update_{path} function
     /// to update the client side
-    fn get_path(&self) -> &'static str;
+    fn get_path(&self) -> &str;
+    /// Returns the name shown to people, defaults to [`HostableServer::get_path`]
+    fn get_name(&self) -> &str {
+        self.get_path()
+    }
     /// Starts the Server
     /// # Errors
     /// Errors if the start.sh doesn't work.
@@ -52,70 +180,175 @@ pub trait HostableServer {
     /// Serialization can fail if Self's implementation of Serialize decides to fail,
     /// or if Self contains a map with non-string keys.
     fn to_json(&self) -> Result<String, serde_json::Error>;
-}
-
-/// Failure of a [`HostableServer`] command
-pub struct CommandFailure(String);
-// Implement std::fmt::Display for CommandFailure
-impl fmt::Display for CommandFailure {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "An Error With Requested Command Occurred: {}", self.0) // user-facing output
+    /// Returns the readiness probes of the server, see [`Probe`]
+    fn probes(&self) -> &[Probe] {
+        &[]
     }
-}
-// Implement std::fmt::Debug for CommandFailure
-impl fmt::Debug for CommandFailure {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{{ file: {}, line: {} }}\n{}", file!(), line!(), self.0) // programmer-facing output
+    /// Runs every probe of [`HostableServer::probes`]
+    ///
+    /// Implementations call it from [`HostableServer::update_status`] and decide
+    /// between starting, running and unhealthy with the results
+    fn check_probes(&self) -> Vec<ProbeResult> {
+        self.probes().iter().map(Probe::check).collect()
+    }
+    /// Returns the token cancelling whatever command is running, `None` if they can't be cancelled
+    ///
+    /// The token is taken once when the server is added, so cancelling doesn't need
+    /// the lock a running command holds
+    fn cancel_token(&self) -> Option<CancelToken> {
+        None
+    }
+    /// Writes `line` to the console of the server
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] unless the server owns the stdin of its process,
+    /// [`CommandFailure::NotRunning`] if there is no process to write to
+    fn send_console(&mut self, _line: &str) -> Result<(), CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "console".to_owned(),
+        })
+    }
+    /// Runs an admin command, like `whitelist add Alex`, and returns the answer
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] unless the server has a way to answer commands
+    fn command(&mut self, _command: &str) -> Result<String, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "admin commands".to_owned(),
+        })
+    }
+    /// Returns the missions the server has and its mission rotation
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] unless the server plays missions
+    fn missions(&self) -> Result<Selection, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "missions".to_owned(),
+        })
+    }
+    /// Sets the mission rotation used from the next start on
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Rejected`] for missions the server doesn't have,
+    /// [`CommandFailure::Unsupported`] unless the server plays missions
+    fn select_missions(&mut self, _missions: Vec<String>) -> Result<(), CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "missions".to_owned(),
+        })
+    }
+    /// Returns the mods the server can load and the ones it loads
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] unless the server has mods to choose from
+    fn mods(&self) -> Result<Selection, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "mods".to_owned(),
+        })
+    }
+    /// Sets the mods loaded from the next start on
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Rejected`] for mods the server can't load,
+    /// [`CommandFailure::Unsupported`] unless the server has mods to choose from
+    fn select_mods(&mut self, _mods: Vec<String>) -> Result<(), CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "mods".to_owned(),
+        })
+    }
+    /// Returns the settings of the game, like the `server.properties` of Minecraft
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] unless the server has a settings file
+    fn properties(&self) -> Result<BTreeMap<String, String>, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "properties".to_owned(),
+        })
+    }
+    /// Changes the settings in `changes`, the game reads them on its next start
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Rejected`] for values that don't fit, [`CommandFailure::InvalidTransition`]
+    /// while the server is starting or stopping and [`CommandFailure::Unsupported`]
+    /// unless the server has a settings file
+    fn set_properties(&mut self, _changes: BTreeMap<String, String>) -> Result<(), CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "properties".to_owned(),
+        })
+    }
+    /// Returns the entries of `list`
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unreadable`] if the list can't be read and
+    /// [`CommandFailure::Unsupported`] unless the server keeps player lists
+    fn player_list(&self, _list: PlayerList) -> Result<Vec<ListEntry>, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "player lists".to_owned(),
+        })
+    }
+    /// Adds a player or address to `list`, right away if the server is running, and
+    /// returns what happened
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Rejected`] for names that don't fit, [`CommandFailure::InvalidTransition`]
+    /// while the server is starting or stopping and [`CommandFailure::Unsupported`]
+    /// unless the server keeps player lists
+    fn add_to_list(
+        &mut self,
+        _list: PlayerList,
+        _addition: ListAddition,
+    ) -> Result<String, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "player lists".to_owned(),
+        })
+    }
+    /// Removes the player or address `name` from `list` and returns what happened
+    ///
+    /// # Errors
+    /// See [`HostableServer::add_to_list`]
+    fn remove_from_list(
+        &mut self,
+        _list: PlayerList,
+        _name: &str,
+    ) -> Result<String, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "player lists".to_owned(),
+        })
+    }
+    /// Returns the lifecycle of the server, the [`watchdog::Watchdog`] needs it to notice crashes
+    fn lifecycle_mut(&mut self) -> Option<&mut Lifecycle> {
+        None
+    }
+    /// Returns how the last process of the server ended, if its backend knows
+    fn last_exit(&self) -> Option<Exit> {
+        None
+    }
+    /// Returns how the [`watchdog::Watchdog`] watches the server
+    fn watchdog(&self) -> WatchdogSettings {
+        WatchdogSettings::default()
+    }
+    /// Returns the last lines the server printed, oldest first
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::Unsupported`] unless the server can read its console
+    fn console_output(&self) -> Result<Vec<String>, CommandFailure> {
+        Err(CommandFailure::Unsupported {
+            action: "console".to_owned(),
+        })
     }
 }
 
 // Generic Helper Functions <3
 
-/// Executes the `command` and possibly parses the error into [`CommandFailure`].
-///
-/// `command` is formated as it would be when passed into the shell, arguments seperated by a space
-fn exec_and_parse_command(command: &str) -> Result<(), CommandFailure> {
-    let mut command_split = command.split(' ');
-    let program: &str = command_split.next().expect("Invalid Command");
-    let arguments: Vec<&str> = command_split.collect();
-
-    let output = Command::new(program).args(arguments).output();
-
-    match output {
-        Err(e) => Err(CommandFailure(e.to_string())),
-        Ok(_) => Ok(()),
-    }
-}
-
 /// Returns the screen sessions
 #[must_use]
 pub fn get_screen_sessions() -> String {
-    let screen_server_list = Command::new("screen").arg("-list").output();
-
-    match screen_server_list {
-        Ok(screen_server_list) => std::str::from_utf8(&screen_server_list.stdout)
-            .unwrap_or("Unrecognizible screen -list output")
-            .to_string(),
-        Err(e) => {
-            format!("Error with the screen -list command: \r\n{e:#?}")
-        }
-    }
-}
-
-/// Status of a minecraft server
-#[derive(serde::Serialize)]
-enum State {
-    /// Turned On
-    On,
-    /// Turned Off
-    Off,
-    /// Unknown
-    Unknown,
-}
-impl State {
-    /// Returns a new instance of `State`
-    pub const fn new() -> Self {
-        Self::Off
+    match Process::new("screen").arg("-list").run() {
+        Ok(screen_server_list) => screen_server_list.stdout,
+        // screen exits with 1 when there are no sessions
+        Err(e) => e.output().map_or_else(
+            || format!("Error with the screen -list command: \r\n{e}"),
+            |screen_server_list| screen_server_list.stdout.clone(),
+        ),
     }
 }
 
@@ -137,58 +370,277 @@ impl Players {
     }
 }
 
+/// Scripts controlling a server, they are run with `sh`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scripts {
+    /// Starts the server
+    pub start: PathBuf,
+    /// Stops the server
+    pub stop: PathBuf,
+    /// Asks the server for its status, not every server needs one
+    pub status: Option<PathBuf>,
+}
+impl Scripts {
+    /// The scripts as they are laid out in this repository: `./{path}/start.sh` and `./{path}/stop.sh`
+    #[must_use]
+    pub fn in_directory(path: &str) -> Self {
+        let directory = Path::new(".").join(path);
+        Self {
+            start: directory.join("start.sh"),
+            stop: directory.join("stop.sh"),
+            status: None,
+        }
+    }
+}
+
+/// How long each lifecycle script may run before it is killed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Limit for the start script
+    pub start: Duration,
+    /// Limit for the stop script
+    pub stop: Duration,
+    /// Limit for the status script
+    pub status: Duration,
+    /// Time a started server has to become ready before it counts as crashed
+    pub ready: Duration,
+}
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            start: Duration::from_mins(2),
+            stop: Duration::from_mins(2),
+            status: Duration::from_secs(15),
+            ready: Duration::from_mins(5),
+        }
+    }
+}
+
+/// Where a server lives and how it is called and controlled
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Settings {
+    /// Identifier used in links, `/{path}/start`, and for the screen session `{path}_server`
+    pub path: String,
+    /// Name shown to people
+    pub name: String,
+    /// Directory the scripts are run in, the current directory if `None`
+    #[serde(skip)]
+    pub working_dir: Option<PathBuf>,
+    /// Lifecycle scripts
+    #[serde(skip)]
+    pub scripts: Scripts,
+    /// Limits for the lifecycle scripts
+    #[serde(skip)]
+    pub timeouts: Timeouts,
+    /// Cancels the script that is running, see [`HostableServer::cancel_token`]
+    #[serde(skip)]
+    pub cancel: CancelToken,
+    /// Tell whether the server is ready, see [`HostableServer::probes`]
+    #[serde(skip)]
+    pub probes: Vec<Probe>,
+    /// Crash detection and restarts, see [`HostableServer::watchdog`]
+    #[serde(skip)]
+    pub watchdog: WatchdogSettings,
+}
+impl Settings {
+    /// Default settings for a server whose scripts are in `./{path}/`
+    #[must_use]
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            name: path.to_owned(),
+            working_dir: None,
+            scripts: Scripts::in_directory(path),
+            timeouts: Timeouts::default(),
+            cancel: CancelToken::new(),
+            probes: Vec::new(),
+            watchdog: WatchdogSettings::default(),
+        }
+    }
+    /// Returns a lifecycle that gives the server [`Timeouts::ready`] to become ready
+    fn lifecycle(&self) -> Lifecycle {
+        Lifecycle::new(self.timeouts.ready)
+    }
+    /// Runs `script` with `sh` in the working directory, killing it after `timeout`
+    fn run(&self, script: &Path, timeout: Duration) -> Result<Output, CommandFailure> {
+        let label = script.file_name().map_or_else(
+            || script.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let mut process = Process::new("sh")
+            .arg(script)
+            .label(label)
+            .timeout(timeout)
+            .cancel_on(&self.cancel);
+        if let Some(working_dir) = &self.working_dir {
+            process = process.current_dir(working_dir);
+        }
+        let output = process.run()?;
+        println!(
+            "\x1b[36m{}: {} finished in {:?}\x1b[39m",
+            self.path,
+            script.display(),
+            output.duration
+        );
+        Ok(output)
+    }
+}
+
 /// Basic implementation for the [`HostableServer`] Trait.
 ///
-/// The Server is contolled by start.sh and stop.sh scripts that are located in path.
+/// The Server is contolled by start.sh and stop.sh scripts that are located in path,
+/// they are responsible for creating a screen session with the name {path}_server.
 ///
-/// For now the bash scripts are responsible for creating a screen session with the name {path}_server
+/// Other [`ProcessBackend`]s run a command in screen, tmux, a systemd unit or the web server itself
 #[derive(serde::Serialize)]
 pub struct GeneralBashServer {
-    /// Path to the home directory of the Server
-    path: &'static str,
-    /// State of the Server {On, Off, Unknown}
-    state: State,
+    /// Path, name and scripts of the Server
+    #[serde(flatten)]
+    settings: Settings,
+    /// Runs the process of the Server
+    #[serde(rename = "backend", serialize_with = "backend_name")]
+    backend: Box<dyn ProcessBackend>,
+    /// Process id and last exit, if the backend knows them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process: Option<SupervisorStatus>,
+    /// Lifecycle state of the Server and when it changed
+    #[serde(flatten)]
+    lifecycle: Lifecycle,
+    /// Results of the last readiness probes
+    probes: Vec<ProbeResult>,
     /// Number of Players and their name tags
     players: Players,
+    /// RCON interface for admin commands and stopping
+    #[serde(skip)]
+    rcon: Option<RconClient>,
+    /// Commands sent over RCON to stop the Server
+    #[serde(skip)]
+    rcon_stop: Vec<String>,
 }
 
 impl GeneralBashServer {
     /// Returns a new Instance of `Server`
     #[must_use]
-    pub const fn new(path: &'static str) -> Self {
+    pub fn new(path: &str) -> Self {
+        Self::with_settings(Settings::new(path))
+    }
+    /// Returns a new Instance of `Server` with custom scripts, name or working directory
+    #[must_use]
+    pub fn with_settings(settings: Settings) -> Self {
+        Self::with_backend(settings, Box::new(backend::Scripts))
+    }
+    /// Returns a new Instance of `Server` whose process is run by `backend`
+    #[must_use]
+    pub fn with_backend(settings: Settings, backend: Box<dyn ProcessBackend>) -> Self {
         Self {
-            path,
-            state: State::new(),
+            lifecycle: settings.lifecycle(),
+            settings,
+            backend,
+            process: None,
+            probes: Vec::new(),
             players: Players::new(),
+            rcon: None,
+            rcon_stop: Vec::new(),
         }
     }
+    /// Runs admin commands over the Source RCON at `login`
+    ///
+    /// With `stop_commands`, like `["save", "quit"]`, the Server is stopped by sending
+    /// them instead of by its backend, which only stops it if that doesn't work
+    #[must_use]
+    pub fn with_rcon(mut self, login: RconLogin, stop_commands: Vec<String>) -> Self {
+        self.rcon = Some(RconClient::new(login, RCON_TIMEOUT));
+        self.rcon_stop = stop_commands;
+        self
+    }
+    /// Sends the stop commands over RCON and waits for the process to go away
+    ///
+    /// Returns false if there are none or it is still there after the stop timeout
+    fn stop_over_rcon(&mut self) -> Result<bool, CommandFailure> {
+        let Some(rcon) = &mut self.rcon else {
+            return Ok(false);
+        };
+        for (i, command) in self.rcon_stop.iter().enumerate() {
+            match rcon.run(command) {
+                // the server may close the connection before it answers the last one
+                Err(CommandFailure::Remote { .. }) if i + 1 == self.rcon_stop.len() => {}
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            }
+        }
+        rcon.disconnect();
+        if self.rcon_stop.is_empty() {
+            return Ok(false);
+        }
+        backend::wait_until_gone(&*self.backend, &self.settings)
+    }
+}
+
+/// Serializes a [`ProcessBackend`] as its name
+#[allow(clippy::borrowed_box)] // serde hands over a reference to the field
+fn backend_name<S: serde::Serializer>(
+    backend: &Box<dyn ProcessBackend>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(backend.name())
 }
 
 impl HostableServer for GeneralBashServer {
     fn start(&mut self) -> Result<(), CommandFailure> {
-        let state = exec_and_parse_command(&format!("sh ./{}/start.sh", self.path));
-
-        if state.is_ok() {
-            self.state = State::Unknown;
-        };
+        self.lifecycle.begin_start()?;
+        self.settings.probes.iter_mut().for_each(Probe::mark_start);
+        if let Err(e) = self.backend.launch(&self.settings) {
+            self.lifecycle.script_failed();
+            return Err(e);
+        }
 
-        state
+        // Running once the backend sees the process
+        self.update_status()
     }
 
+    /// Stops the Server with the RCON stop commands if there are any, with the backend otherwise
     fn stop(&mut self) -> Result<(), CommandFailure> {
-        exec_and_parse_command(&format!("sh ./{}/stop.sh", self.path))?;
+        self.lifecycle.begin_stop()?;
+        if !self.rcon_stop.is_empty() {
+            match self.stop_over_rcon() {
+                Ok(true) => return self.update_status(),
+                Ok(false) => eprintln!(
+                    "\x1b[31m{}: still running {}s after the RCON stop commands, stopping it\x1b[39m",
+                    self.settings.path,
+                    self.settings.timeouts.stop.as_secs()
+                ),
+                Err(e) => eprintln!(
+                    "\x1b[31m{}: {e}, stopping it with the {} backend\x1b[39m",
+                    self.settings.path,
+                    self.backend.name()
+                ),
+            }
+        }
+        if let Err(e) = self.backend.terminate(&self.settings) {
+            self.lifecycle.script_failed();
+            return Err(e);
+        }
 
         self.update_status()
     }
 
     fn update_status(&mut self) -> Result<(), CommandFailure> {
-        let sessions = get_screen_sessions();
+        let alive = match self.backend.is_alive(&self.settings) {
+            Ok(alive) => alive,
+            Err(e) => {
+                self.lifecycle.observe(Observation::Unknown);
+                return Err(e);
+            }
+        };
+        self.process = self.backend.process();
 
-        if sessions.contains(&format!(".{}_server\t", self.path)) {
-            self.state = State::On;
+        self.probes = if alive {
+            self.check_probes()
         } else {
-            self.state = State::Off;
-        }
+            Vec::new()
+        };
+        self.lifecycle
+            .observe(Observation::from_probes(alive, probe::all_ok(&self.probes)));
 
         Ok(())
     }
@@ -197,7 +649,53 @@ impl HostableServer for GeneralBashServer {
         serde_json::to_string(&self)
     }
 
-    fn get_path(&self) -> &'static str {
-        self.path
+    fn get_path(&self) -> &str {
+        &self.settings.path
+    }
+
+    fn get_name(&self) -> &str {
+        &self.settings.name
+    }
+
+    fn cancel_token(&self) -> Option<CancelToken> {
+        Some(self.settings.cancel.clone())
+    }
+
+    fn probes(&self) -> &[Probe] {
+        &self.settings.probes
+    }
+
+    fn lifecycle_mut(&mut self) -> Option<&mut Lifecycle> {
+        Some(&mut self.lifecycle)
+    }
+
+    fn last_exit(&self) -> Option<Exit> {
+        self.process
+            .as_ref()
+            .and_then(|process| process.last_exit.clone())
+    }
+
+    fn watchdog(&self) -> WatchdogSettings {
+        self.settings.watchdog
+    }
+
+    fn send_console(&mut self, line: &str) -> Result<(), CommandFailure> {
+        self.backend.send_input(&self.settings, line)
+    }
+
+    /// Runs the command over RCON
+    fn command(&mut self, command: &str) -> Result<String, CommandFailure> {
+        self.rcon.as_mut().map_or_else(
+            || {
+                Err(CommandFailure::Unsupported {
+                    action: "admin commands".to_owned(),
+                })
+            },
+            |rcon| rcon.run(command),
+        )
+    }
+
+    fn console_output(&self) -> Result<Vec<String>, CommandFailure> {
+        self.backend.read_output(&self.settings, CONSOLE_LINES)
     }
 }
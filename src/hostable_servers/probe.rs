@@ -0,0 +1,424 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/probe.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Checks whether a hosted server is actually ready for players
+//! =============================================================
+
+use super::process::Process;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    net::{SocketAddr, TcpStream, UdpSocket},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// How long a single probe may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Longest unfinished line a [`Probe::Log`] keeps, only its end is kept of a longer one
+const MAX_LINE: usize = 64 * 1024;
+
+/// One way of telling whether a server is ready
+///
+/// In the config every probe is a table with a `kind`:
+/// ```toml
+/// probes = [
+///     { kind = "tcp", address = "127.0.0.1:25565" },
+///     { kind = "udp", address = "127.0.0.1:2303", payload = "ffffffff54536f7572636520456e67696e6520517565727900" },
+///     { kind = "log", file = "Minecraft/logs/latest.log", pattern = 'Done \(.*\)! For help' },
+///     { kind = "command", command = ["sh", "arma/ready.sh"] },
+/// ]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Probe {
+    /// Something accepts TCP connections on `address`
+    Tcp {
+        /// `ip:port` to connect to
+        address: SocketAddr,
+    },
+    /// Something answers a UDP datagram sent to `address`
+    Udp {
+        /// `ip:port` to send to
+        address: SocketAddr,
+        /// Datagram to send, written as hex in the config, like a query request
+        #[serde(default, deserialize_with = "bytes_from_hex")]
+        payload: Vec<u8>,
+    },
+    /// A line written to the log file since the server was started matches `pattern`
+    Log {
+        /// Log file of the server
+        file: PathBuf,
+        /// Regular expression a line has to match
+        #[serde(deserialize_with = "regex_from_str")]
+        pattern: Regex,
+        /// How far the log was read since the server was started, see [`Probe::mark_start`]
+        #[serde(skip)]
+        read: LogCursor,
+    },
+    /// A command exits with 0, it is killed after the probe timeout
+    Command {
+        /// Program followed by its arguments
+        command: Vec<String>,
+        /// Directory to run in, the server's working directory when loaded from the config
+        #[serde(skip)]
+        working_dir: Option<PathBuf>,
+    },
+}
+
+/// What a [`Probe`] found out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeResult {
+    /// The probe, like `tcp 127.0.0.1:25565`
+    pub probe: String,
+    /// Whether it passed
+    pub ok: bool,
+    /// Why it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// When it was checked
+    pub checked: DateTime<Utc>,
+}
+
+impl Probe {
+    /// Runs the probe
+    ///
+    /// # Example
+    /// ```
+    /// use std::net::TcpListener;
+    /// use web_server::hostable_servers::probe::Probe;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let probe = Probe::Tcp { address: listener.local_addr().unwrap() };
+    /// assert!(probe.check().ok);
+    ///
+    /// drop(listener);
+    /// assert!(!probe.check().ok);
+    /// ```
+    #[must_use]
+    pub fn check(&self) -> ProbeResult {
+        let outcome = match self {
+            Self::Tcp { address } => TcpStream::connect_timeout(address, PROBE_TIMEOUT)
+                .map(drop)
+                .map_err(|e| e.to_string()),
+            Self::Udp { address, payload } => Self::check_udp(*address, payload),
+            Self::Log {
+                file,
+                pattern,
+                read,
+            } => match read.advance(file, pattern) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err("no matching line yet".to_owned()),
+                Err(e) => Err(format!("{}: {e}", file.display())),
+            },
+            Self::Command {
+                command,
+                working_dir,
+            } => {
+                let Some((program, args)) = command.split_first() else {
+                    return self.result(Err("empty command".to_owned()));
+                };
+                let mut process = Process::new(program).args(args).timeout(PROBE_TIMEOUT);
+                if let Some(working_dir) = working_dir {
+                    process = process.current_dir(working_dir);
+                }
+                process.run().map(drop).map_err(|e| e.to_string())
+            }
+        };
+        self.result(outcome)
+    }
+
+    /// Remembers where the log of a [`Probe::Log`] ends, called right before the server starts
+    ///
+    /// A `Done` line of the last run doesn't count then. A log that is shorter later on
+    /// was cleared or rotated by the start script and is read from its beginning
+    pub fn mark_start(&mut self) {
+        if let Self::Log { file, read, .. } = self {
+            *read = LogCursor::at(fs::metadata(file).map_or(0, |metadata| metadata.len()));
+        }
+    }
+
+    /// Sends the payload to `address` and waits for any answer
+    fn check_udp(address: SocketAddr, payload: &[u8]) -> Result<(), String> {
+        let local: SocketAddr = if address.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 16], 0).into()
+        };
+        let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+        socket
+            .set_read_timeout(Some(PROBE_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        socket
+            .send_to(payload, address)
+            .map_err(|e| e.to_string())?;
+        let mut answer = [0; 1500];
+        socket
+            .recv_from(&mut answer)
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Wraps the outcome of checking `self`
+    fn result(&self, outcome: Result<(), String>) -> ProbeResult {
+        ProbeResult {
+            probe: self.to_string(),
+            ok: outcome.is_ok(),
+            detail: outcome.err(),
+            checked: Utc::now(),
+        }
+    }
+}
+
+/// Where a [`Probe::Log`] stopped reading, so every check only reads what was added
+///
+/// Once a line matched the probe passes without reading until the next start
+#[derive(Debug, Default)]
+pub struct LogCursor(Mutex<Cursor>);
+
+/// Content of a [`LogCursor`]
+#[derive(Debug, Clone, Default)]
+struct Cursor {
+    /// Bytes of the file already read
+    offset: u64,
+    /// Start of the last line, which isn't finished yet
+    partial: Vec<u8>,
+    /// Whether a line matched
+    matched: bool,
+}
+
+impl LogCursor {
+    /// Returns a cursor that starts reading after the first `offset` bytes
+    fn at(offset: u64) -> Self {
+        Self(Mutex::new(Cursor {
+            offset,
+            ..Cursor::default()
+        }))
+    }
+
+    /// Reads the lines added to `file`, returns true once one of them matched `pattern`
+    ///
+    /// A file shorter than what was read was cleared or rotated and is read from its beginning
+    fn advance(&self, file: &Path, pattern: &Regex) -> io::Result<bool> {
+        let mut cursor = self.lock();
+        if cursor.matched {
+            return Ok(true);
+        }
+        let mut log = File::open(file)?;
+        if log.metadata()?.len() < cursor.offset {
+            cursor.offset = 0;
+            cursor.partial.clear();
+        }
+        log.seek(SeekFrom::Start(cursor.offset))?;
+        let mut added = Vec::new();
+        log.read_to_end(&mut added)?;
+        cursor.offset += added.len() as u64;
+        cursor.partial.extend_from_slice(&added);
+
+        let finished = cursor
+            .partial
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |end| end + 1);
+        let lines: Vec<u8> = cursor.partial.drain(..finished).collect();
+        let surplus = cursor.partial.len().saturating_sub(MAX_LINE);
+        cursor.partial.drain(..surplus);
+        cursor.matched = String::from_utf8_lossy(&lines)
+            .lines()
+            .any(|line| pattern.is_match(line));
+        Ok(cursor.matched)
+    }
+
+    /// Locks the cursor, a panic in another thread doesn't lose it
+    fn lock(&self) -> MutexGuard<'_, Cursor> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for LogCursor {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.lock().clone()))
+    }
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Tcp { address } => write!(f, "tcp {address}"),
+            Self::Udp { address, .. } => write!(f, "udp {address}"),
+            Self::Log { file, pattern, .. } => write!(f, "log {} /{pattern}/", file.display()),
+            Self::Command { command, .. } => write!(f, "command {}", command.join(" ")),
+        }
+    }
+}
+
+/// Returns true if every probe passed, `None` if there were none to ask
+#[must_use]
+pub fn all_ok(results: &[ProbeResult]) -> Option<bool> {
+    if results.is_empty() {
+        None
+    } else {
+        Some(results.iter().all(|result| result.ok))
+    }
+}
+
+/// Compiles a [`Probe::Log`] pattern while the config is parsed
+fn regex_from_str<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(de::Error::custom)
+}
+
+/// Decodes a [`Probe::Udp`] payload written as hex
+fn bytes_from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 {
+        return Err(de::Error::custom(
+            "the payload needs two hex digits per byte",
+        ));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| de::Error::custom(format!("{hex:?} isn't hex")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogCursor, Probe};
+    use regex::Regex;
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+        net::TcpListener,
+        path::PathBuf,
+    };
+
+    /// Returns an empty directory `name` for a test
+    fn directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("web_server_probe_{name}"));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// A log probe waiting for the `Done` line of a Minecraft server in `file`
+    fn done(file: PathBuf) -> Probe {
+        Probe::Log {
+            file,
+            pattern: Regex::new(r"Done \(.*\)! For help").unwrap(),
+            read: LogCursor::default(),
+        }
+    }
+
+    #[test]
+    fn tcp_needs_a_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let probe = Probe::Tcp {
+            address: listener.local_addr().unwrap(),
+        };
+        let result = probe.check();
+        assert!(result.ok);
+        assert_eq!(result.detail, None);
+        assert_eq!(
+            result.probe,
+            format!("tcp {}", listener.local_addr().unwrap())
+        );
+
+        drop(listener);
+        let result = probe.check();
+        assert!(!result.ok);
+        assert!(result.detail.is_some());
+    }
+
+    #[test]
+    fn log_only_counts_lines_since_the_start() {
+        let directory = directory("log");
+        let file = directory.join("latest.log");
+        let mut probe = done(file.clone());
+        assert!(!probe.check().ok, "there is no log yet");
+
+        fs::write(&file, "[12:00:00] Done (3.1s)! For help, type \"help\"\n").unwrap();
+        assert!(probe.check().ok);
+        // the server is started again, the line is from the last run
+        probe.mark_start();
+        let result = probe.check();
+        assert!(!result.ok);
+        assert_eq!(result.detail.as_deref(), Some("no matching line yet"));
+
+        let mut log = fs::read_to_string(&file).unwrap();
+        log.push_str("[12:05:00] Starting minecraft server\n");
+        fs::write(&file, &log).unwrap();
+        assert!(!probe.check().ok);
+        log.push_str("[12:05:04] Done (4.0s)! For help, type \"help\"\n");
+        fs::write(&file, &log).unwrap();
+        assert!(probe.check().ok);
+
+        // a start script clearing the log
+        probe.mark_start();
+        fs::write(&file, "[12:10:02] Done (2.0s)! For help, type \"help\"\n").unwrap();
+        assert!(probe.check().ok, "a shorter log is read from its beginning");
+    }
+
+    #[test]
+    fn log_only_reads_what_was_added() {
+        let directory = directory("log_added");
+        let file = directory.join("latest.log");
+        fs::write(&file, "[12:00:00] Starting minecraft server\n").unwrap();
+        let mut probe = done(file.clone());
+        probe.mark_start();
+        let append = |text: &str| {
+            OpenOptions::new()
+                .append(true)
+                .open(&file)
+                .unwrap()
+                .write_all(text.as_bytes())
+                .unwrap();
+        };
+
+        // the line is written in two parts, half of it doesn't count yet
+        append("[12:00:04] Done (4.0s)! For");
+        assert!(!probe.check().ok);
+        append(" help, type \"help\"\n");
+        assert!(probe.check().ok);
+
+        // it passes until the next start, even if the log is gone
+        fs::remove_file(&file).unwrap();
+        assert!(probe.check().ok);
+        probe.mark_start();
+        assert!(!probe.check().ok);
+    }
+
+    #[test]
+    fn command_needs_exit_code_zero() {
+        let directory = directory("command");
+        let probe = |command: &[&str]| Probe::Command {
+            command: command.iter().map(|&part| part.to_owned()).collect(),
+            working_dir: Some(directory.clone()),
+        };
+
+        assert!(probe(&["true"]).check().ok);
+        let result = probe(&["false"]).check();
+        assert!(!result.ok);
+        assert_eq!(result.probe, "command false");
+        assert!(!probe(&["test", "-f", "ready"]).check().ok);
+        fs::write(directory.join("ready"), "").unwrap();
+        assert!(
+            probe(&["test", "-f", "ready"]).check().ok,
+            "runs in working_dir"
+        );
+
+        assert_eq!(probe(&[]).check().detail.as_deref(), Some("empty command"));
+        let result = probe(&["sleep", "10"]).check();
+        assert!(!result.ok, "killed after the probe timeout");
+    }
+}
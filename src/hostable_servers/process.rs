@@ -0,0 +1,764 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/process.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Runs the scripts and programs controlling a server and reports how it went
+//! =============================================================
+
+use super::lifecycle::InvalidTransition;
+use serde::{Serialize, Serializer};
+use std::{
+    ffi::OsString,
+    fmt,
+    io::Read,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How often a process with a timeout or a [`CancelToken`] is checked on
+pub(super) const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long the output of an exited process is read, a child it left behind may keep the pipes
+const DRAIN_GRACE: Duration = Duration::from_secs(1);
+
+/// Lets another thread cancel the processes run with [`Process::cancel_on`]
+///
+/// Clones share the same state, so the token can be handed out while the
+/// server running the process is locked
+///
+/// # Example
+/// ```
+/// use std::{thread, time::Duration};
+/// use web_server::hostable_servers::process::{CancelToken, CommandFailure, Process};
+///
+/// let token = CancelToken::new();
+/// assert!(!token.cancel(), "nothing is running yet");
+///
+/// let canceller = token.clone();
+/// thread::spawn(move || {
+///     while !canceller.cancel() {
+///         thread::sleep(Duration::from_millis(10));
+///     }
+/// });
+///
+/// let failure = Process::new("sleep").arg("10").cancel_on(&token).run().unwrap_err();
+/// assert!(matches!(failure, CommandFailure::Cancelled(_)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    /// Number of [`CancelToken::cancel`] calls, a process is cancelled if it changes while it runs
+    cancels: Arc<AtomicUsize>,
+    /// Number of processes currently watching the token
+    running: Arc<AtomicUsize>,
+}
+
+impl CancelToken {
+    /// Returns a token nobody has cancelled
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Kills the processes currently running with this token
+    ///
+    /// Returns false if none are running, a later process isn't affected
+    #[must_use]
+    pub fn cancel(&self) -> bool {
+        if self.running.load(Ordering::SeqCst) == 0 {
+            return false;
+        }
+        self.cancels.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    /// Returns true if a process is currently running with this token
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst) > 0
+    }
+
+    /// Marks a process as running until the returned guard is dropped
+    ///
+    /// Several processes may watch the same token, starting one doesn't undo a cancel
+    /// meant for another
+    pub(super) fn enter(&self) -> CancelGuard<'_> {
+        self.running.fetch_add(1, Ordering::SeqCst);
+        CancelGuard {
+            token: self,
+            cancels: self.cancels.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Running process watching a [`CancelToken`]
+pub(super) struct CancelGuard<'a> {
+    /// The token watched
+    token: &'a CancelToken,
+    /// [`CancelToken::cancels`] when the process started
+    cancels: usize,
+}
+impl CancelGuard<'_> {
+    /// Returns true if [`CancelToken::cancel`] was called since the process started
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.token.cancels.load(Ordering::SeqCst) != self.cancels
+    }
+}
+impl Drop for CancelGuard<'_> {
+    fn drop(&mut self) {
+        self.token.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// How a process ended
+enum Ending {
+    /// On its own
+    Exited(ExitStatus),
+    /// Killed because of [`Process::timeout`]
+    TimedOut,
+    /// Killed because of [`CancelToken::cancel`]
+    Cancelled,
+}
+
+/// A program with its arguments, working directory and environment
+///
+/// Nothing goes through a shell, so arguments and paths may contain spaces
+///
+/// # Example
+/// ```
+/// use web_server::hostable_servers::process::{CommandFailure, Process};
+///
+/// let output = Process::new("sh").args(["-c", "echo hello $NAME"]).env("NAME", "world").run().unwrap();
+/// assert_eq!(output.stdout, "hello world\n");
+///
+/// let failure = Process::new("sh").args(["-c", "echo broken >&2; exit 3"]).run().unwrap_err();
+/// assert!(matches!(failure, CommandFailure::NonZeroExit(ref output) if output.code == Some(3)));
+/// assert_eq!(failure.to_string(), "sh exited with 3: broken");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Process {
+    /// Program to run, looked up in `PATH` if it has no directory
+    program: PathBuf,
+    /// Arguments, passed as they are
+    args: Vec<OsString>,
+    /// Directory to run in, the current one if `None`
+    working_dir: Option<PathBuf>,
+    /// Variables added to the inherited environment
+    env: Vec<(OsString, OsString)>,
+    /// Time after which the process is killed
+    timeout: Option<Duration>,
+    /// Token that kills the process when cancelled
+    cancel: Option<CancelToken>,
+    /// Name used in messages instead of the program
+    label: Option<String>,
+}
+
+impl Process {
+    /// Returns a process running `program` without arguments
+    #[must_use]
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            working_dir: None,
+            env: Vec::new(),
+            timeout: None,
+            cancel: None,
+            label: None,
+        }
+    }
+
+    /// Adds an argument
+    #[must_use]
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds several arguments
+    #[must_use]
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Runs the process in `working_dir`
+    #[must_use]
+    pub fn current_dir(mut self, working_dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    }
+
+    /// Sets an environment variable, everything else is inherited
+    #[must_use]
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Kills the process if it is still running after `timeout`
+    ///
+    /// On unix the whole process group is killed, so children of a script go with it
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Kills the process when `token` is cancelled
+    #[must_use]
+    pub fn cancel_on(mut self, token: &CancelToken) -> Self {
+        self.cancel = Some(token.clone());
+        self
+    }
+
+    /// Names the process in messages, like `stop.sh` instead of `sh`
+    #[must_use]
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Runs the process to completion and captures what it printed
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure`] if it can't be started, doesn't exit with 0,
+    /// is killed by a signal, runs into the timeout or is cancelled
+    pub fn run(&self) -> Result<Output, CommandFailure> {
+        let running = self.cancel.as_ref().map(CancelToken::enter);
+        let started = Instant::now();
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(working_dir) = &self.working_dir {
+            command.current_dir(working_dir);
+        }
+        // its own group, so a timeout can kill whatever the script started as well
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        let mut child = command.spawn().map_err(|e| CommandFailure::SpawnFailed {
+            program: self.name(),
+            reason: e.to_string(),
+        })?;
+        // drained on their own threads, a chatty process could block on a full pipe otherwise
+        let stdout = Capture::start(child.stdout.take());
+        let stderr = Capture::start(child.stderr.take());
+
+        let ending = self
+            .wait(&mut child, started, running.as_ref())
+            .map_err(|e| CommandFailure::SpawnFailed {
+                program: self.name(),
+                reason: e.to_string(),
+            })?;
+        let status = match ending {
+            Ending::Exited(status) => Some(status),
+            Ending::TimedOut | Ending::Cancelled => None,
+        };
+        // children the script left behind, like a server started with `&`, still hold the
+        // pipes, so they are only read for a moment
+        let drained = self.timeout.map_or(DRAIN_GRACE, |timeout| {
+            timeout
+                .saturating_sub(started.elapsed())
+                .clamp(POLL_INTERVAL, DRAIN_GRACE)
+        });
+        let drain_until = Instant::now() + drained;
+        let collect = |capture: Capture| {
+            if status.is_some() {
+                capture.finish(drain_until)
+            } else {
+                capture.snapshot()
+            }
+        };
+
+        let output = Output {
+            program: self.name(),
+            code: status.and_then(|status| status.code()),
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+            duration: started.elapsed(),
+        };
+
+        match ending {
+            Ending::TimedOut => Err(CommandFailure::TimedOut(output)),
+            Ending::Cancelled => Err(CommandFailure::Cancelled(output)),
+            Ending::Exited(status) if status.success() => Ok(output),
+            Ending::Exited(status) => match signal_of(status) {
+                Some(signal) => Err(CommandFailure::Signalled { signal, output }),
+                None => Err(CommandFailure::NonZeroExit(output)),
+            },
+        }
+    }
+
+    /// Waits for `child` to exit, kills it when the timeout runs out or it is cancelled
+    fn wait(
+        &self,
+        child: &mut Child,
+        started: Instant,
+        running: Option<&CancelGuard>,
+    ) -> std::io::Result<Ending> {
+        if self.timeout.is_none() && running.is_none() {
+            return child.wait().map(Ending::Exited);
+        }
+
+        let deadline = self.timeout.map(|timeout| started + timeout);
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(Ending::Exited(status));
+            }
+            let ending = if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                Ending::TimedOut
+            } else if running.is_some_and(CancelGuard::is_cancelled) {
+                Ending::Cancelled
+            } else {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+
+            kill_group(child);
+            child.wait()?;
+            return Ok(ending);
+        }
+    }
+
+    /// Name of the process shown in messages, the program without its directory by default
+    fn name(&self) -> String {
+        if let Some(label) = &self.label {
+            return label.clone();
+        }
+        self.program.file_name().map_or_else(
+            || self.program.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+/// Output of a process stream, collected while the process runs
+struct Capture {
+    /// Everything read so far
+    buffer: Arc<Mutex<Vec<u8>>>,
+    /// Reading thread, done once the stream is closed
+    reader: Option<JoinHandle<()>>,
+}
+
+impl Capture {
+    /// Starts reading `stream` on a new thread
+    fn start(stream: Option<impl Read + Send + 'static>) -> Self {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let reader = stream.map(|mut stream| {
+            let buffer = Arc::clone(&buffer);
+            thread::spawn(move || {
+                let mut chunk = [0; 4096];
+                while let Ok(read @ 1..) = stream.read(&mut chunk) {
+                    buffer
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .extend_from_slice(&chunk[..read]);
+                }
+            })
+        });
+        Self { buffer, reader }
+    }
+
+    /// Waits for the stream to close, at most until `deadline`, and returns what was written
+    ///
+    /// A reader still blocked after that is left behind, it ends with the stream
+    fn finish(mut self, deadline: Instant) -> String {
+        if let Some(reader) = self.reader.take() {
+            while !reader.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(5));
+            }
+            if reader.is_finished() {
+                let _ = reader.join();
+            }
+        }
+        self.snapshot()
+    }
+
+    /// Returns what was written so far
+    fn snapshot(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.lock().unwrap_or_else(PoisonError::into_inner))
+            .into_owned()
+    }
+}
+
+/// Kills `child` and everything else in its process group
+#[cfg(unix)]
+fn kill_group(child: &mut Child) {
+    // the group was created with the child, so its id is the child's pid
+    if !send_signal(child.id(), "KILL", true) {
+        let _ = child.kill();
+    }
+}
+/// Kills `child`, there are no process groups outside of unix
+#[cfg(not(unix))]
+fn kill_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Sends `signal`, like `TERM`, to the process `pid`, or to the group it leads if `group`
+///
+/// Returns false if that didn't work, like when the process is already gone
+#[cfg(unix)]
+#[must_use]
+pub fn send_signal(pid: u32, signal: &str, group: bool) -> bool {
+    let target = if group {
+        format!("-{pid}")
+    } else {
+        pid.to_string()
+    };
+    Command::new("kill")
+        .args([&format!("-{signal}"), "--", &target])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+/// Signals only exist on unix
+#[cfg(not(unix))]
+#[must_use]
+pub const fn send_signal(_pid: u32, _signal: &str, _group: bool) -> bool {
+    false
+}
+
+/// Returns the signal that killed the process
+#[cfg(unix)]
+fn signal_of(status: ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(&status)
+}
+/// Processes can't be killed by signals outside of unix
+#[cfg(not(unix))]
+const fn signal_of(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+/// What a finished process did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Output {
+    /// Name of the program
+    pub program: String,
+    /// Exit code, `None` if the process was killed
+    pub code: Option<i32>,
+    /// Everything it printed to stdout
+    pub stdout: String,
+    /// Everything it printed to stderr
+    pub stderr: String,
+    /// Time from starting to exiting
+    #[serde(rename = "duration_ms", serialize_with = "as_millis")]
+    pub duration: Duration,
+}
+
+impl Output {
+    /// Last non empty line of stderr, or of stdout if stderr is empty
+    #[must_use]
+    pub fn last_line(&self) -> Option<&str> {
+        [&self.stderr, &self.stdout]
+            .into_iter()
+            .find_map(|text| text.lines().rev().find(|line| !line.trim().is_empty()))
+            .map(str::trim)
+    }
+}
+
+/// Serializes a [`Duration`] as whole milliseconds
+fn as_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// Failure of a [`crate::hostable_servers::HostableServer`] command
+///
+/// Serializes with an `error` field naming the variant, for the web interface
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum CommandFailure {
+    /// The program couldn't be started at all
+    SpawnFailed {
+        /// Name of the program
+        program: String,
+        /// Why, as reported by the operating system
+        reason: String,
+    },
+    /// The program exited with something other than 0
+    NonZeroExit(Output),
+    /// The program was killed after running for too long
+    TimedOut(Output),
+    /// The program was killed by [`CancelToken::cancel`]
+    Cancelled(Output),
+    /// The program was killed by a signal
+    Signalled {
+        /// Number of the signal
+        signal: i32,
+        /// What it did until then
+        output: Output,
+    },
+    /// The server isn't in a state the command can run in, nothing was run
+    InvalidTransition(InvalidTransition),
+    /// The command needs the process of the server, which isn't running
+    NotRunning {
+        /// Name of the program
+        program: String,
+    },
+    /// A file the command needs couldn't be read
+    Unreadable {
+        /// Path of the file
+        file: String,
+        /// Why, as reported by the operating system
+        reason: String,
+    },
+    /// A file the command changes couldn't be written
+    Unwritable {
+        /// Path of the file
+        file: String,
+        /// Why, as reported by the operating system
+        reason: String,
+    },
+    /// The command was asked for something that can't be, like a mission that doesn't exist
+    Rejected {
+        /// What is wrong with it
+        reason: String,
+    },
+    /// A service the command talks to, like RCON, couldn't be reached or refused
+    Remote {
+        /// What was talked to, like `RCON at 127.0.0.1:25575`
+        service: String,
+        /// What went wrong
+        reason: String,
+    },
+    /// The server can't do that at all, like a console without a process owning stdin
+    Unsupported {
+        /// What was asked for, like `console`
+        action: String,
+    },
+}
+
+impl CommandFailure {
+    /// Returns what the program did, if it ran at all
+    #[must_use]
+    pub const fn output(&self) -> Option<&Output> {
+        match self {
+            Self::SpawnFailed { .. }
+            | Self::InvalidTransition(_)
+            | Self::NotRunning { .. }
+            | Self::Unreadable { .. }
+            | Self::Unwritable { .. }
+            | Self::Rejected { .. }
+            | Self::Remote { .. }
+            | Self::Unsupported { .. } => None,
+            Self::NonZeroExit(output)
+            | Self::TimedOut(output)
+            | Self::Cancelled(output)
+            | Self::Signalled { output, .. } => Some(output),
+        }
+    }
+}
+
+impl fmt::Display for CommandFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::SpawnFailed { program, reason } => write!(f, "Couldn't run {program}: {reason}")?,
+            Self::NonZeroExit(output) => write!(
+                f,
+                "{} exited with {}",
+                output.program,
+                output.code.unwrap_or_default()
+            )?,
+            Self::TimedOut(output) => write!(
+                f,
+                "{} didn't finish within {}s",
+                output.program,
+                output.duration.as_secs()
+            )?,
+            Self::Cancelled(output) => write!(f, "{} was cancelled", output.program)?,
+            Self::InvalidTransition(e) => write!(f, "{e}")?,
+            Self::NotRunning { program } => write!(f, "{program} isn't running")?,
+            Self::Unreadable { file, reason } => write!(f, "Couldn't read {file}: {reason}")?,
+            Self::Unwritable { file, reason } => write!(f, "Couldn't write {file}: {reason}")?,
+            Self::Rejected { reason } => write!(f, "{reason}")?,
+            Self::Remote { service, reason } => write!(f, "{service} failed: {reason}")?,
+            Self::Unsupported { action } => write!(f, "The server doesn't support {action}")?,
+            Self::Signalled { signal, output } => {
+                write!(f, "{} was killed by signal {signal}", output.program)?;
+            }
+        }
+        self.output()
+            .and_then(Output::last_line)
+            .map_or(Ok(()), |line| write!(f, ": {line}"))
+    }
+}
+impl std::error::Error for CommandFailure {}
+impl From<InvalidTransition> for CommandFailure {
+    fn from(e: InvalidTransition) -> Self {
+        Self::InvalidTransition(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{send_signal, CancelToken, CommandFailure, Process};
+    use std::{
+        fs, thread,
+        time::{Duration, Instant},
+    };
+
+    /// Returns true once the process `pid` is gone or only waits to be reaped
+    fn is_gone(pid: u32) -> bool {
+        fs::read_to_string(format!("/proc/{pid}/stat")).map_or(true, |stat| stat.contains(") Z "))
+    }
+
+    #[test]
+    fn timeouts_kill_the_process() {
+        let started = Instant::now();
+        let failure = Process::new("sh")
+            .args(["-c", "echo booting; sleep 10"])
+            .label("start.sh")
+            .timeout(Duration::from_millis(200))
+            .run()
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let CommandFailure::TimedOut(output) = &failure else {
+            panic!("expected a timeout, got {failure:?}");
+        };
+        assert_eq!(output.code, None);
+        assert_eq!(output.stdout, "booting\n", "output until then is kept");
+        assert_eq!(
+            failure.to_string(),
+            "start.sh didn't finish within 0s: booting"
+        );
+
+        let output = Process::new("true")
+            .timeout(Duration::from_secs(5))
+            .run()
+            .unwrap();
+        assert_eq!(output.code, Some(0));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn timeouts_kill_the_whole_process_group() {
+        let failure = Process::new("sh")
+            .args(["-c", "sleep 30 & echo $!; wait"])
+            .timeout(Duration::from_millis(300))
+            .run()
+            .unwrap_err();
+        let output = failure.output().unwrap();
+        let pid: u32 = output.stdout.trim().parse().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !is_gone(pid) {
+            assert!(
+                Instant::now() < deadline,
+                "the child of the script survived"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!send_signal(pid, "KILL", true), "the group is gone");
+    }
+
+    #[test]
+    fn cancelling_only_kills_running_processes() {
+        let token = CancelToken::new();
+        assert!(!token.is_running());
+        assert!(!token.cancel());
+
+        let canceller = token.clone();
+        let watcher = thread::spawn(move || {
+            while !canceller.cancel() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+        let failure = Process::new("sleep")
+            .arg("10")
+            .cancel_on(&token)
+            .run()
+            .unwrap_err();
+        watcher.join().unwrap();
+        assert!(
+            matches!(failure, CommandFailure::Cancelled(_)),
+            "{failure:?}"
+        );
+        assert_eq!(failure.to_string(), "sleep was cancelled");
+        assert!(!token.is_running());
+
+        // an earlier cancel doesn't carry over
+        assert!(Process::new("true").cancel_on(&token).run().is_ok());
+    }
+
+    #[test]
+    fn failures_say_what_happened() {
+        let failure = Process::new("/nonexistent/start.sh").run().unwrap_err();
+        assert!(matches!(
+            &failure,
+            CommandFailure::SpawnFailed { program, .. } if program == "start.sh"
+        ));
+        assert_eq!(failure.output(), None);
+
+        let failure = Process::new("sh")
+            .args(["-c", "kill -KILL $$"])
+            .run()
+            .unwrap_err();
+        assert!(
+            matches!(failure, CommandFailure::Signalled { signal: 9, .. }),
+            "{failure:?}"
+        );
+
+        let failure = Process::new("sh")
+            .args(["-c", "echo first; echo last; echo >&2; exit 2"])
+            .run()
+            .unwrap_err();
+        assert_eq!(failure.output().and_then(|o| o.code), Some(2));
+        assert_eq!(failure.to_string(), "sh exited with 2: last");
+    }
+
+    #[test]
+    fn runs_in_its_working_directory() {
+        let output = Process::new("pwd").current_dir("/").run().unwrap();
+        assert_eq!(output.stdout, "/\n");
+    }
+
+    #[test]
+    fn children_left_behind_dont_hold_up_the_script() {
+        for timeout in [Some(Duration::from_secs(2)), None] {
+            let started = Instant::now();
+            let mut process = Process::new("sh").args(["-c", "sleep 4 & echo started"]);
+            if let Some(timeout) = timeout {
+                process = process.timeout(timeout);
+            }
+            let output = process.run().unwrap();
+            assert!(
+                started.elapsed() < Duration::from_secs(3),
+                "took {:?} with {timeout:?}",
+                started.elapsed()
+            );
+            assert_eq!(output.stdout, "started\n");
+        }
+    }
+
+    #[test]
+    fn a_cancel_isnt_undone_by_the_next_process() {
+        let token = CancelToken::new();
+        let first = token.enter();
+        assert!(token.cancel());
+        let second = token.enter();
+        assert!(
+            first.is_cancelled(),
+            "starting another process keeps the cancel"
+        );
+        assert!(!second.is_cancelled(), "it started after the cancel");
+        drop(first);
+        assert!(token.cancel());
+        assert!(second.is_cancelled());
+        drop(second);
+        assert!(!token.cancel());
+    }
+}
@@ -0,0 +1,513 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/rcon.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Valve's Source RCON, spoken by Minecraft, Rust, ARK, CS2, Palworld and more
+//! =============================================================
+
+use super::CommandFailure;
+use std::{
+    fmt,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// Packet type of a login with the password, `SERVERDATA_AUTH`
+const LOGIN: i32 = 3;
+/// Packet type of a command, and of the answer to a login, `SERVERDATA_EXECCOMMAND`
+const COMMAND: i32 = 2;
+/// Packet type of every answer, `SERVERDATA_RESPONSE_VALUE`
+///
+/// Sent empty after a command, servers answer it after every part of the command's
+/// answer: Source servers send it back, Minecraft answers it with `Unknown request 0`
+const RESPONSE: i32 = 0;
+/// Longest command Minecraft accepts, Source servers take a bit more
+pub const MAX_COMMAND: usize = 1446;
+/// Largest packet accepted from the server, answers are split into packets of 4096 bytes
+const MAX_PACKET: usize = 4096 + 14;
+
+/// Why an RCON request failed
+#[derive(Debug)]
+pub enum RconError {
+    /// Connecting, reading or writing failed
+    Io(io::Error),
+    /// The server refused the password
+    AuthFailed,
+    /// The command is longer than [`MAX_COMMAND`]
+    TooLong(usize),
+    /// The server sent something that isn't an RCON packet
+    Protocol(String),
+}
+
+impl fmt::Display for RconError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::AuthFailed => write!(f, "the password was refused"),
+            Self::TooLong(length) => write!(
+                f,
+                "the command is {length} bytes long, at most {MAX_COMMAND} are allowed"
+            ),
+            Self::Protocol(reason) => write!(f, "unexpected answer: {reason}"),
+        }
+    }
+}
+impl std::error::Error for RconError {}
+impl From<io::Error> for RconError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A logged in RCON connection
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use web_server::hostable_servers::rcon::Rcon;
+///
+/// let mut rcon = Rcon::connect("127.0.0.1:25575", "hunter2", Duration::from_secs(5)).unwrap();
+/// println!("{}", rcon.command("list").unwrap());
+/// ```
+#[derive(Debug)]
+pub struct Rcon {
+    /// Connection to the server
+    stream: TcpStream,
+    /// Id of the last request
+    last_id: i32,
+}
+
+impl Rcon {
+    /// Connects to `address` and logs in with `password`
+    ///
+    /// `timeout` limits connecting and every read and write after that
+    ///
+    /// # Errors
+    /// Returns an [`RconError`] if the server can't be reached or refuses the password
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        password: &str,
+        timeout: Duration,
+    ) -> Result<Self, RconError> {
+        let mut last_error = None;
+        let mut stream = None;
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(connected) => {
+                    stream = Some(connected);
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let stream = stream.ok_or_else(|| {
+            last_error.unwrap_or_else(|| {
+                io::Error::new(ErrorKind::NotFound, "the address didn't resolve")
+            })
+        })?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut rcon = Self { stream, last_id: 0 };
+        let id = rcon.send(LOGIN, password)?;
+        loop {
+            // Source servers send an empty answer before the login's, Minecraft doesn't
+            let (answer_id, kind, _) = rcon.receive()?;
+            if kind != COMMAND {
+                continue;
+            }
+            // the login is answered with its own id, or -1 for a wrong password
+            if answer_id == -1 {
+                return Err(RconError::AuthFailed);
+            }
+            if answer_id != id {
+                return Err(RconError::Protocol(format!(
+                    "login {id} was answered as {answer_id}"
+                )));
+            }
+            return Ok(rcon);
+        }
+    }
+
+    /// Runs `command` and returns what the server answered
+    ///
+    /// Answers longer than one packet are put back together: the command is
+    /// followed by an empty answer packet, once that is answered every part of
+    /// the command's answer has arrived. Whatever else a server sends for it,
+    /// like the extra packet of Source servers, is skipped by the next command
+    ///
+    /// # Errors
+    /// Returns an [`RconError`] if the command is too long or the connection broke,
+    /// the connection shouldn't be used any more after an [`RconError::Io`]
+    pub fn command(&mut self, command: &str) -> Result<String, RconError> {
+        if command.len() > MAX_COMMAND {
+            return Err(RconError::TooLong(command.len()));
+        }
+        let id = self.send(COMMAND, command)?;
+        let marker = self.send(RESPONSE, "")?;
+
+        let mut answer = Vec::new();
+        loop {
+            let (answer_id, kind, payload) = self.receive()?;
+            if answer_id == marker {
+                break;
+            }
+            if answer_id < id && answer_id > 0 {
+                // left over from an earlier command
+                continue;
+            }
+            if answer_id != id || kind != RESPONSE {
+                return Err(RconError::Protocol(format!(
+                    "command {id} was answered as {answer_id} with type {kind}"
+                )));
+            }
+            answer.extend_from_slice(&payload);
+        }
+        Ok(String::from_utf8_lossy(&answer).into_owned())
+    }
+
+    /// Writes a packet and returns its id
+    fn send(&mut self, kind: i32, payload: &str) -> Result<i32, RconError> {
+        self.last_id = self.last_id.wrapping_add(1).max(1);
+        let length =
+            i32::try_from(payload.len() + 10).map_err(|_| RconError::TooLong(payload.len()))?;
+
+        let mut packet = Vec::with_capacity(payload.len() + 14);
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(&self.last_id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(payload.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        self.stream.write_all(&packet)?;
+        Ok(self.last_id)
+    }
+
+    /// Reads a packet, returns its id, type and payload
+    fn receive(&mut self) -> Result<(i32, i32, Vec<u8>), RconError> {
+        let mut length = [0; 4];
+        self.stream.read_exact(&mut length)?;
+        let length = usize::try_from(i32::from_le_bytes(length))
+            .ok()
+            .filter(|length| (10..=MAX_PACKET).contains(length))
+            .ok_or_else(|| RconError::Protocol("packet length out of range".to_owned()))?;
+
+        let mut packet = vec![0; length];
+        self.stream.read_exact(&mut packet)?;
+        let id = i32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        let kind = i32::from_le_bytes([packet[4], packet[5], packet[6], packet[7]]);
+        // the payload ends with two zero bytes
+        packet.truncate(length - 2);
+        Ok((id, kind, packet.split_off(8)))
+    }
+}
+
+/// Where the RCON interface of a server listens and its password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconLogin {
+    /// `host:port`
+    pub address: String,
+    /// The password, like `rcon.password` in Minecraft's `server.properties`
+    pub password: String,
+}
+
+/// An [`Rcon`] connection that is made when it is needed and made again when it broke
+///
+/// Servers restart and close idle connections, so a connection that was kept
+/// around may be dead. A command that failed on such a connection because the
+/// server closed it is sent once more over a new one. Commands that timed out
+/// aren't, the server may have run them
+#[derive(Debug)]
+pub struct RconClient {
+    /// Address and password
+    login: RconLogin,
+    /// Limit for connecting and every answer
+    timeout: Duration,
+    /// The connection, if there is one
+    connection: Option<Rcon>,
+}
+
+impl RconClient {
+    /// Returns a client that connects with the first command
+    #[must_use]
+    pub const fn new(login: RconLogin, timeout: Duration) -> Self {
+        Self {
+            login,
+            timeout,
+            connection: None,
+        }
+    }
+
+    /// Returns where the client connects to
+    #[must_use]
+    pub fn address(&self) -> &str {
+        &self.login.address
+    }
+
+    /// Runs `command`, connecting first if needed, and returns the answer
+    ///
+    /// # Errors
+    /// Returns an [`RconError`] if the server can't be reached, refuses or the command is too long
+    pub fn command(&mut self, command: &str) -> Result<String, RconError> {
+        let reused = self.connection.is_some();
+        match self.try_command(command) {
+            Err(RconError::Io(e)) if reused && is_closed(&e) => {
+                println!(
+                    "\x1b[36mRCON at {} was closed, connecting again\x1b[39m",
+                    self.login.address
+                );
+                self.try_command(command)
+            }
+            result => result,
+        }
+    }
+
+    /// Runs `command` like [`RconClient::command`], errors become a [`CommandFailure::Remote`]
+    ///
+    /// # Errors
+    /// Returns a [`CommandFailure::Remote`] naming the address
+    pub fn run(&mut self, command: &str) -> Result<String, CommandFailure> {
+        self.command(command).map_err(|e| CommandFailure::Remote {
+            service: format!("RCON at {}", self.login.address),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Closes the connection, the next command makes a new one
+    pub fn disconnect(&mut self) {
+        self.connection = None;
+    }
+
+    /// Runs `command` once, a broken connection is dropped
+    fn try_command(&mut self, command: &str) -> Result<String, RconError> {
+        let rcon = match &mut self.connection {
+            Some(rcon) => rcon,
+            None => self.connection.insert(Rcon::connect(
+                &self.login.address,
+                &self.login.password,
+                self.timeout,
+            )?),
+        };
+        rcon.command(command).inspect_err(|e| {
+            if !matches!(e, RconError::TooLong(_)) {
+                self.connection = None;
+            }
+        })
+    }
+}
+
+/// Returns true if `e` means the server closed the connection, so it didn't run anything
+fn is_closed(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::UnexpectedEof
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rcon, RconClient, RconError, RconLogin, COMMAND, LOGIN, MAX_COMMAND, RESPONSE};
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, TcpStream},
+        thread::{self, JoinHandle},
+        time::Duration,
+    };
+
+    /// Timeout of the client under test
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// How the fake server behaves
+    #[derive(Clone, Copy)]
+    enum Flavour {
+        /// Answers the login only, and unknown packet types with `Unknown request`
+        Minecraft,
+        /// Sends an empty answer before the login's, mirrors empty answers and
+        /// follows them with a packet containing `0x00000001`
+        Source,
+    }
+
+    /// Reads a packet the way servers do
+    fn read_packet(stream: &mut TcpStream) -> Option<(i32, i32, String)> {
+        let mut length = [0; 4];
+        stream.read_exact(&mut length).ok()?;
+        let mut packet = vec![0; usize::try_from(i32::from_le_bytes(length)).ok()?];
+        stream.read_exact(&mut packet).ok()?;
+        let id = i32::from_le_bytes(packet[0..4].try_into().ok()?);
+        let kind = i32::from_le_bytes(packet[4..8].try_into().ok()?);
+        let payload = String::from_utf8(packet[8..packet.len() - 2].to_vec()).ok()?;
+        Some((id, kind, payload))
+    }
+
+    /// Writes a packet the way servers do
+    fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, payload: &[u8]) {
+        let mut packet = Vec::new();
+        let length = i32::try_from(payload.len() + 10).expect("short payload");
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(payload);
+        packet.extend_from_slice(&[0, 0]);
+        stream.write_all(&packet).expect("the client is listening");
+    }
+
+    /// Serves a client with the password `secret` until it goes away, returns its commands
+    ///
+    /// Commands are answered by `answer`, split into packets of 4096 bytes. A `Source`
+    /// server closes the connection after `limit` commands
+    fn serve(
+        mut stream: TcpStream,
+        flavour: Flavour,
+        answer: fn(&str) -> String,
+        limit: usize,
+    ) -> Vec<String> {
+        let mut commands = Vec::new();
+        while let Some((id, kind, payload)) = read_packet(&mut stream) {
+            match (kind, flavour) {
+                (LOGIN, _) => {
+                    if matches!(flavour, Flavour::Source) {
+                        write_packet(&mut stream, id, RESPONSE, b"");
+                    }
+                    let id = if payload == "secret" { id } else { -1 };
+                    write_packet(&mut stream, id, COMMAND, b"");
+                }
+                (COMMAND, _) => {
+                    let answer = answer(&payload);
+                    commands.push(payload);
+                    for part in answer.as_bytes().chunks(4096) {
+                        write_packet(&mut stream, id, RESPONSE, part);
+                    }
+                    if answer.is_empty() {
+                        write_packet(&mut stream, id, RESPONSE, b"");
+                    }
+                }
+                (RESPONSE, Flavour::Source) => {
+                    write_packet(&mut stream, id, RESPONSE, b"");
+                    write_packet(&mut stream, id, RESPONSE, &[0, 0, 0, 1]);
+                    if commands.len() == limit {
+                        // like a server that restarted
+                        return commands;
+                    }
+                }
+                (kind, _) => write_packet(
+                    &mut stream,
+                    id,
+                    RESPONSE,
+                    format!("Unknown request {kind:x}").as_bytes(),
+                ),
+            }
+        }
+        commands
+    }
+
+    /// Serves one client, see [`serve`]
+    fn fake_server(
+        flavour: Flavour,
+        answer: fn(&str) -> String,
+    ) -> (SocketAddr, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("a free port");
+        let address = listener.local_addr().expect("a bound address");
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("a client");
+            serve(stream, flavour, answer, usize::MAX)
+        });
+        (address, server)
+    }
+
+    /// The answer of Minecraft to `list`
+    fn list(command: &str) -> String {
+        match command {
+            "list" => "There are 2 of a max of 20 players online: Alex, Steve".to_owned(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn runs_commands() {
+        for flavour in [Flavour::Minecraft, Flavour::Source] {
+            let (address, server) = fake_server(flavour, list);
+            let mut rcon = Rcon::connect(address, "secret", TIMEOUT).unwrap();
+            assert_eq!(
+                rcon.command("list").unwrap(),
+                "There are 2 of a max of 20 players online: Alex, Steve"
+            );
+            assert_eq!(rcon.command("say hi").unwrap(), "");
+            drop(rcon);
+            assert_eq!(server.join().unwrap(), ["list", "say hi"]);
+        }
+    }
+
+    #[test]
+    fn puts_split_answers_together() {
+        for flavour in [Flavour::Minecraft, Flavour::Source] {
+            let (address, _server) = fake_server(flavour, |_| "x".repeat(10_000));
+            let mut rcon = Rcon::connect(address, "secret", TIMEOUT).unwrap();
+            assert_eq!(rcon.command("help").unwrap(), "x".repeat(10_000));
+            // the next command still gets its own answer
+            assert_eq!(rcon.command("help").unwrap().len(), 10_000);
+        }
+    }
+
+    #[test]
+    fn refuses_wrong_passwords() {
+        for flavour in [Flavour::Minecraft, Flavour::Source] {
+            let (address, _server) = fake_server(flavour, list);
+            assert!(matches!(
+                Rcon::connect(address, "hunter2", TIMEOUT),
+                Err(RconError::AuthFailed)
+            ));
+        }
+    }
+
+    #[test]
+    fn refuses_long_commands() {
+        let (address, server) = fake_server(Flavour::Minecraft, list);
+        let mut rcon = Rcon::connect(address, "secret", TIMEOUT).unwrap();
+        assert!(matches!(
+            rcon.command(&"a".repeat(MAX_COMMAND + 1)),
+            Err(RconError::TooLong(_))
+        ));
+        drop(rcon);
+        assert!(server.join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_unreachable_servers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        assert!(matches!(
+            Rcon::connect(address, "secret", TIMEOUT),
+            Err(RconError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn connects_again_after_a_restart() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            // the first connection is closed after one command
+            let (stream, _) = listener.accept().unwrap();
+            let mut commands = serve(stream, Flavour::Source, list, 1);
+            let (stream, _) = listener.accept().unwrap();
+            commands.extend(serve(stream, Flavour::Source, list, usize::MAX));
+            commands
+        });
+
+        let mut client = RconClient::new(
+            RconLogin {
+                address: address.to_string(),
+                password: "secret".to_owned(),
+            },
+            TIMEOUT,
+        );
+        assert_eq!(client.command("say one").unwrap(), "");
+        assert!(client.command("list").unwrap().starts_with("There are 2"));
+        client.disconnect();
+        assert_eq!(server.join().unwrap(), ["say one", "list"]);
+    }
+}
@@ -0,0 +1,620 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/supervisor.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Runs a game process directly, without a screen session around it
+//! =============================================================
+
+use super::process::{
+    send_signal, CancelGuard, CancelToken, CommandFailure, Output, POLL_INTERVAL,
+};
+use chrono::{DateTime, Utc};
+use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Lines of output kept for the console
+const OUTPUT_LINES: usize = 1000;
+/// Console lines waiting for a process that doesn't read its input
+const INPUT_LINES: usize = 64;
+/// How long a process gets to go away after being killed
+const KILL_GRACE: Duration = Duration::from_secs(5);
+/// Terminal size of processes running under a pty
+const PTY_SIZE: PtySize = PtySize {
+    rows: 50,
+    cols: 200,
+    pixel_width: 0,
+    pixel_height: 0,
+};
+
+/// A process as spawned, before it is handed to the reading and reaping threads
+type Spawned = (
+    Box<dyn portable_pty::Child + Send + Sync>,
+    Box<dyn Write + Send>,
+    Vec<Box<dyn Read + Send>>,
+    Option<Box<dyn MasterPty + Send>>,
+);
+
+/// How a supervised process ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Exit {
+    /// Exit code, `None` if it was killed by a signal
+    pub code: Option<u32>,
+    /// Signal that killed it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    /// When it ended
+    pub at: DateTime<Utc>,
+}
+
+/// What [`Supervisor::status`] reports
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SupervisorStatus {
+    /// Process id while it runs
+    pub pid: Option<u32>,
+    /// When the running process was started
+    pub started: Option<DateTime<Utc>>,
+    /// How the previous process ended
+    pub last_exit: Option<Exit>,
+}
+impl SupervisorStatus {
+    /// Returns true while the process runs
+    #[must_use]
+    pub const fn is_running(&self) -> bool {
+        self.pid.is_some()
+    }
+}
+
+/// The process the supervisor is running
+struct Running {
+    /// Process id, also the id of its process group
+    pid: u32,
+    /// When it was started
+    started: DateTime<Utc>,
+    /// Lines for the thread writing them to its stdin, or the pty it reads from
+    stdin: SyncSender<String>,
+    /// Master side of the pty, dropping it hangs up the process
+    _pty: Option<Box<dyn MasterPty + Send>>,
+}
+
+/// State shared with the threads reading and reaping the process
+#[derive(Default)]
+struct Shared {
+    /// The process, `None` once it exited
+    running: Option<Running>,
+    /// How the previous process ended
+    last_exit: Option<Exit>,
+    /// Last [`OUTPUT_LINES`] lines of stdout and stderr
+    output: VecDeque<String>,
+}
+
+/// Runs a game server process and owns its stdin and output
+///
+/// Takes the place of a screen session: the process is spawned directly in its
+/// own process group, optionally under a pseudo terminal for servers that want one.
+/// Console lines go to its stdin and its output is kept for the console,
+/// and copied into a log file if there is one
+///
+/// # Example
+/// ```
+/// use std::{thread, time::Duration};
+/// use web_server::hostable_servers::{process::CancelToken, supervisor::Supervisor};
+///
+/// let supervisor = Supervisor::new(["cat"]);
+/// supervisor.start().unwrap();
+/// assert!(supervisor.status().is_running());
+///
+/// supervisor.send("hello").unwrap();
+/// while supervisor.output().is_empty() {
+///     thread::sleep(Duration::from_millis(10));
+/// }
+/// assert_eq!(supervisor.output(), ["hello"]);
+///
+/// supervisor.stop(Duration::from_secs(5), &CancelToken::new()).unwrap();
+/// let status = supervisor.status();
+/// assert!(!status.is_running());
+/// assert!(status.last_exit.is_some());
+/// ```
+pub struct Supervisor {
+    /// Program followed by its arguments
+    command: Vec<String>,
+    /// Directory to run in, the current one if `None`
+    working_dir: Option<PathBuf>,
+    /// Variables added to the inherited environment
+    env: BTreeMap<String, String>,
+    /// Whether to run under a pseudo terminal instead of pipes
+    pty: bool,
+    /// Console line that makes the server shut down, SIGTERM if `None`
+    stop_command: Option<String>,
+    /// File the output is appended to
+    log_file: Option<PathBuf>,
+    /// The process and its output
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Supervisor {
+    /// Returns a supervisor for `command`, the program followed by its arguments
+    #[must_use]
+    pub fn new<I, S>(command: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            command: command.into_iter().map(Into::into).collect(),
+            working_dir: None,
+            env: BTreeMap::new(),
+            pty: false,
+            stop_command: None,
+            log_file: None,
+            shared: Arc::default(),
+        }
+    }
+
+    /// Runs the process in `working_dir`
+    #[must_use]
+    pub fn current_dir(mut self, working_dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(working_dir.into());
+        self
+    }
+
+    /// Sets an environment variable, everything else is inherited
+    #[must_use]
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    /// Runs the process under a pseudo terminal, for servers that only talk to terminals
+    #[must_use]
+    pub const fn pty(mut self, pty: bool) -> Self {
+        self.pty = pty;
+        self
+    }
+
+    /// Sends `line` to the console to stop the server, like `stop` for minecraft
+    #[must_use]
+    pub fn stop_command(mut self, line: impl Into<String>) -> Self {
+        self.stop_command = Some(line.into());
+        self
+    }
+
+    /// Appends everything the process prints to `log_file`
+    #[must_use]
+    pub fn log_file(mut self, log_file: impl Into<PathBuf>) -> Self {
+        self.log_file = Some(log_file.into());
+        self
+    }
+
+    /// Replaces the command, the running process keeps going until it is stopped
+    pub fn set_command(&mut self, command: Vec<String>) {
+        self.command = command;
+    }
+
+    /// Starts the process, does nothing if it is already running
+    ///
+    /// Returns its process id
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::SpawnFailed`] if it can't be started
+    pub fn start(&self) -> Result<u32, CommandFailure> {
+        let mut shared = self.lock();
+        if let Some(running) = &shared.running {
+            return Ok(running.pid);
+        }
+
+        let spawned = if self.pty {
+            self.spawn_pty()
+        } else {
+            self.spawn_piped()
+        };
+        let (mut child, stdin, readers, pty) =
+            spawned.map_err(|reason| CommandFailure::SpawnFailed {
+                program: self.name(),
+                reason,
+            })?;
+        // signalling the group of pid 0 would hit the web server itself
+        let Some(pid) = child.process_id() else {
+            let _ = child.kill();
+            return Err(CommandFailure::SpawnFailed {
+                program: self.name(),
+                reason: "it has no process id".to_owned(),
+            });
+        };
+
+        shared.running = Some(Running {
+            pid,
+            started: Utc::now(),
+            stdin: Self::spawn_writer(stdin),
+            _pty: pty,
+        });
+        drop(shared);
+        println!("\x1b[36m{}: started with pid {pid}\x1b[39m", self.name());
+
+        for reader in readers {
+            let shared = Arc::clone(&self.shared);
+            let log_file = self.log_file.clone();
+            thread::spawn(move || Self::read_output(reader, &shared, log_file.as_deref()));
+        }
+        // reaps the process, so the exit status is known without anybody polling
+        let shared = Arc::clone(&self.shared);
+        let name = self.name();
+        thread::spawn(move || {
+            let exit = match child.wait() {
+                Ok(status) => Exit {
+                    code: status.signal().is_none().then(|| status.exit_code()),
+                    signal: status.signal().map(str::to_owned),
+                    at: Utc::now(),
+                },
+                Err(e) => {
+                    eprintln!("\x1b[31m{name}: couldn't wait for pid {pid}: {e}\x1b[39m");
+                    Exit {
+                        code: None,
+                        signal: None,
+                        at: Utc::now(),
+                    }
+                }
+            };
+            println!("\x1b[36m{name}: pid {pid} exited: {exit:?}\x1b[39m");
+            let mut shared = Self::lock_shared(&shared);
+            if shared
+                .running
+                .as_ref()
+                .is_some_and(|running| running.pid == pid)
+            {
+                shared.running = None;
+            }
+            shared.last_exit = Some(exit);
+        });
+        Ok(pid)
+    }
+
+    /// Writes `line` to the stdin of the process
+    ///
+    /// The line is queued for a thread writing it, a process that doesn't read its input
+    /// can't block the caller
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::NotRunning`] if there is no process, or it closed its stdin,
+    /// and [`CommandFailure::Remote`] if it doesn't read what it was sent
+    pub fn send(&self, line: &str) -> Result<(), CommandFailure> {
+        let stdin = self
+            .lock()
+            .running
+            .as_ref()
+            .map(|running| running.stdin.clone());
+        let Some(stdin) = stdin else {
+            return Err(CommandFailure::NotRunning {
+                program: self.name(),
+            });
+        };
+        match stdin.try_send(format!("{line}\n")) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err(CommandFailure::Remote {
+                service: format!("stdin of {}", self.name()),
+                reason: format!("{INPUT_LINES} lines are waiting, the process isn't reading them"),
+            }),
+            Err(TrySendError::Disconnected(_)) => Err(CommandFailure::NotRunning {
+                program: self.name(),
+            }),
+        }
+    }
+
+    /// Stops the process and waits for it to exit
+    ///
+    /// It is asked with the stop command, or SIGTERM to its process group, and killed
+    /// if it is still there after `timeout` or when `cancel` is cancelled
+    ///
+    /// # Errors
+    /// Returns [`CommandFailure::TimedOut`] if it survives even that
+    pub fn stop(&self, timeout: Duration, cancel: &CancelToken) -> Result<(), CommandFailure> {
+        let started = Instant::now();
+        let Some(pid) = self.status().pid else {
+            return Ok(());
+        };
+        let running = cancel.enter();
+
+        let asked = self
+            .stop_command
+            .as_deref()
+            .is_some_and(|line| self.send(line).is_ok());
+        if !asked {
+            let _ = send_signal(pid, "TERM", true);
+        }
+        if self.wait_for_exit(pid, timeout, Some(&running)) {
+            return Ok(());
+        }
+
+        eprintln!(
+            "\x1b[31m{}: pid {pid} didn't stop within {}s, killing it\x1b[39m",
+            self.name(),
+            timeout.as_secs()
+        );
+        let _ = send_signal(pid, "KILL", true);
+        if self.wait_for_exit(pid, KILL_GRACE, None) {
+            return Ok(());
+        }
+        Err(CommandFailure::TimedOut(Output {
+            program: self.name(),
+            code: None,
+            stdout: self.output().join("\n"),
+            stderr: String::new(),
+            duration: started.elapsed(),
+        }))
+    }
+
+    /// Returns the process id, start time and last exit
+    #[must_use]
+    pub fn status(&self) -> SupervisorStatus {
+        let shared = self.lock();
+        SupervisorStatus {
+            pid: shared.running.as_ref().map(|running| running.pid),
+            started: shared.running.as_ref().map(|running| running.started),
+            last_exit: shared.last_exit.clone(),
+        }
+    }
+
+    /// Returns the last lines the process printed, oldest first
+    #[must_use]
+    pub fn output(&self) -> Vec<String> {
+        self.lock().output.iter().cloned().collect()
+    }
+
+    /// Starts the thread writing the lines sent to it into `stdin`
+    ///
+    /// It ends when the process closes its stdin or the sender is dropped with the process
+    fn spawn_writer(mut stdin: Box<dyn Write + Send>) -> SyncSender<String> {
+        let (sender, lines) = mpsc::sync_channel::<String>(INPUT_LINES);
+        thread::spawn(move || {
+            for line in lines {
+                if stdin
+                    .write_all(line.as_bytes())
+                    .and_then(|()| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        sender
+    }
+
+    /// Spawns the process with its stdio connected to pipes
+    fn spawn_piped(&self) -> Result<Spawned, String> {
+        let (program, args) = self.command.split_first().ok_or("empty command")?;
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .envs(&self.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(working_dir) = &self.working_dir {
+            command.current_dir(working_dir);
+        }
+        // its own group, so stopping it takes whatever it started along
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        let mut child = command.spawn().map_err(|e| e.to_string())?;
+        let stdin: Box<dyn Write + Send> = match child.stdin.take() {
+            Some(stdin) => Box::new(stdin),
+            None => Box::new(std::io::sink()),
+        };
+        let mut readers: Vec<Box<dyn Read + Send>> = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(Box::new(stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(Box::new(stderr));
+        }
+        Ok((Box::new(child), stdin, readers, None))
+    }
+
+    /// Spawns the process as the session leader of a new pseudo terminal
+    fn spawn_pty(&self) -> Result<Spawned, String> {
+        let (program, args) = self.command.split_first().ok_or("empty command")?;
+        let pair = native_pty_system()
+            .openpty(PTY_SIZE)
+            .map_err(|e| e.to_string())?;
+        let mut command = CommandBuilder::new(program);
+        command.args(args);
+        for (key, value) in &self.env {
+            command.env(key, value);
+        }
+        // portable_pty would pick the home directory otherwise
+        let working_dir = match &self.working_dir {
+            Some(working_dir) => working_dir.clone(),
+            None => std::env::current_dir().map_err(|e| e.to_string())?,
+        };
+        command.cwd(working_dir);
+
+        let child = pair
+            .slave
+            .spawn_command(command)
+            .map_err(|e| e.to_string())?;
+        // only the process may hold the slave, or reading never ends
+        drop(pair.slave);
+        let reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let stdin = pair.master.take_writer().map_err(|e| e.to_string())?;
+        Ok((child, stdin, vec![reader], Some(pair.master)))
+    }
+
+    /// Keeps the lines of `stream` until it is closed, and appends them to `log_file`
+    fn read_output(stream: Box<dyn Read + Send>, shared: &Mutex<Shared>, log_file: Option<&Path>) {
+        let mut log = log_file.and_then(|log_file| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_file)
+                .map_err(|e| {
+                    eprintln!(
+                        "\x1b[31mCouldn't open the log file {}: {e}\x1b[39m",
+                        log_file.display()
+                    );
+                })
+                .ok()
+        });
+        let mut stream = BufReader::new(stream);
+        let mut line = Vec::new();
+        // a pty reports an error instead of the end once the process is gone
+        while let Ok(1..) = stream.read_until(b'\n', &mut line) {
+            if let Some(log) = &mut log {
+                let _ = log.write_all(&line);
+            }
+            let text = String::from_utf8_lossy(&line)
+                .trim_end_matches(['\r', '\n'])
+                .to_owned();
+            line.clear();
+
+            let mut shared = Self::lock_shared(shared);
+            if shared.output.len() == OUTPUT_LINES {
+                shared.output.pop_front();
+            }
+            shared.output.push_back(text);
+        }
+    }
+
+    /// Waits until process `pid` exited, returns false after `timeout` or on `cancel`
+    fn wait_for_exit(&self, pid: u32, timeout: Duration, cancel: Option<&CancelGuard>) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self
+                .lock()
+                .running
+                .as_ref()
+                .is_none_or(|running| running.pid != pid)
+            {
+                return true;
+            }
+            if Instant::now() >= deadline || cancel.is_some_and(CancelGuard::is_cancelled) {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Name of the program without its directory, for messages
+    fn name(&self) -> String {
+        self.command.first().map_or_else(String::new, |program| {
+            Path::new(program).file_name().map_or_else(
+                || program.clone(),
+                |name| name.to_string_lossy().into_owned(),
+            )
+        })
+    }
+
+    /// Locks the shared state
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        Self::lock_shared(&self.shared)
+    }
+
+    /// Locks `shared`, a panicking reader thread doesn't make it unusable
+    fn lock_shared(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+        shared.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Supervisor, INPUT_LINES};
+    use crate::hostable_servers::process::{CancelToken, CommandFailure};
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    /// Time a test process gets to stop
+    const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Waits up to 5 s for `done`, returns whether it happened
+    fn eventually(done: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            if Instant::now() > deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
+    #[test]
+    fn stop_command_is_sent_to_stdin() {
+        let supervisor = Supervisor::new(["sh", "-c", "read line; echo \"got $line\"; exit 0"])
+            .stop_command("quit");
+        let pid = supervisor.start().unwrap();
+        assert_ne!(pid, 0);
+        assert_eq!(supervisor.start().unwrap(), pid);
+
+        supervisor.stop(STOP_TIMEOUT, &CancelToken::new()).unwrap();
+        let status = supervisor.status();
+        assert!(!status.is_running());
+        assert_eq!(status.last_exit.unwrap().code, Some(0));
+        assert!(eventually(|| supervisor.output() == ["got quit"]));
+    }
+
+    #[test]
+    fn exit_status_is_kept() {
+        let supervisor = Supervisor::new(["sh", "-c", "echo bye >&2; exit 3"]);
+        supervisor.start().unwrap();
+        assert!(eventually(|| !supervisor.status().is_running()));
+
+        let exit = supervisor.status().last_exit.unwrap();
+        assert_eq!(exit.code, Some(3));
+        assert!(exit.signal.is_none());
+        assert!(eventually(|| supervisor.output() == ["bye"]));
+        assert!(matches!(
+            supervisor.send("anybody there?"),
+            Err(CommandFailure::NotRunning { .. })
+        ));
+    }
+
+    #[test]
+    fn stopping_without_a_stop_command_signals() {
+        let supervisor = Supervisor::new(["sleep", "60"]);
+        supervisor.start().unwrap();
+        supervisor.stop(STOP_TIMEOUT, &CancelToken::new()).unwrap();
+
+        let exit = supervisor.status().last_exit.unwrap();
+        assert_eq!(exit.code, None);
+        assert!(exit.signal.is_some());
+    }
+
+    #[test]
+    fn a_process_not_reading_stdin_blocks_nobody() {
+        let supervisor = Supervisor::new(["sleep", "60"]);
+        supervisor.start().unwrap();
+
+        // far more than a pipe holds
+        let line = "x".repeat(64 * 1024);
+        let refused = (0..=INPUT_LINES + 2).find_map(|_| supervisor.send(&line).err());
+        assert!(matches!(refused, Some(CommandFailure::Remote { .. })));
+        assert!(supervisor.status().is_running());
+
+        supervisor.stop(STOP_TIMEOUT, &CancelToken::new()).unwrap();
+        assert!(!supervisor.status().is_running());
+    }
+
+    #[test]
+    fn empty_commands_dont_start() {
+        let supervisor = Supervisor::new(Vec::<String>::new());
+        assert!(matches!(
+            supervisor.start(),
+            Err(CommandFailure::SpawnFailed { .. })
+        ));
+        assert!(!supervisor.status().is_running());
+    }
+}
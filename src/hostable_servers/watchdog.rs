@@ -0,0 +1,559 @@
+//! =============================================================
+//! Rust Game Hosting Server - `hostable_servers/watchdog.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Notices when a server crashes, remembers why and restarts it
+//! =============================================================
+
+use super::{lifecycle::State, supervisor::Exit, CommandFailure, HostableServer};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, time::Duration};
+
+/// Crashes a [`Watchdog`] remembers
+const CRASH_HISTORY: usize = 20;
+
+/// When a crashed server is restarted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Never, crashes are only recorded
+    #[default]
+    Never,
+    /// Unless the process exited with 0 on its own, like after `/stop` in the game
+    OnFailure,
+    /// After every crash
+    Always,
+}
+
+/// How a [`Watchdog`] watches a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogSettings {
+    /// Time between two status checks
+    pub interval: Duration,
+    /// Which crashes are followed by a restart
+    pub restart: RestartPolicy,
+    /// Restarts in a row before giving up
+    pub max_retries: u32,
+    /// Wait before the first restart, doubled for every further one
+    pub backoff: Duration,
+    /// Longest wait before a restart, a server running that long counts as recovered
+    pub max_backoff: Duration,
+    /// Time a server may be unhealthy before it counts as crashed, forever if `None`
+    pub unhealthy: Option<Duration>,
+    /// Lines of output kept with every crash
+    pub log_lines: usize,
+}
+impl Default for WatchdogSettings {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            restart: RestartPolicy::Never,
+            max_retries: 5,
+            backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_mins(10),
+            unhealthy: None,
+            log_lines: 50,
+        }
+    }
+}
+
+/// A crash noticed by a [`Watchdog`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Crash {
+    /// When it was noticed
+    pub at: DateTime<Utc>,
+    /// State the server was in before
+    pub after: State,
+    /// What happened, like `exited with 1`
+    pub reason: String,
+    /// False if the process exited with 0 on its own
+    pub failure: bool,
+    /// How the process ended, if the backend knows
+    pub exit: Option<Exit>,
+    /// Last lines the server printed
+    pub log: Vec<String>,
+    /// When the server is restarted, `None` if it isn't
+    pub restart_at: Option<DateTime<Utc>>,
+}
+
+/// Checks on a server every [`WatchdogSettings::interval`], records its crashes
+/// and restarts it according to its [`RestartPolicy`]
+///
+/// A crash is a server going down while running, staying unhealthy for too long
+/// or not becoming ready in time, see [`crate::hostable_servers::lifecycle::Lifecycle`]
+#[derive(Debug, Serialize)]
+pub struct Watchdog {
+    /// How to watch
+    #[serde(skip)]
+    settings: WatchdogSettings,
+    /// Copy of [`WatchdogSettings::restart`] for the status
+    restart: RestartPolicy,
+    /// Restarts since the server last ran long enough
+    attempts: u32,
+    /// When the next restart is due
+    next_restart: Option<DateTime<Utc>>,
+    /// Last crashes, oldest first
+    crashes: VecDeque<Crash>,
+    /// State seen by the last check
+    #[serde(skip)]
+    last_state: State,
+    /// When the last recorded crash happened, so it isn't recorded twice
+    #[serde(skip)]
+    last_crash: Option<DateTime<Utc>>,
+}
+
+impl Watchdog {
+    /// Returns a watchdog that hasn't seen the server yet
+    #[must_use]
+    pub const fn new(settings: WatchdogSettings) -> Self {
+        Self {
+            restart: settings.restart,
+            settings,
+            attempts: 0,
+            next_restart: None,
+            crashes: VecDeque::new(),
+            last_state: State::Unknown,
+            last_crash: None,
+        }
+    }
+
+    /// Returns the time between two checks
+    #[must_use]
+    pub const fn interval(&self) -> Duration {
+        self.settings.interval
+    }
+
+    /// Returns the recorded crashes, oldest first
+    #[must_use]
+    pub const fn crashes(&self) -> &VecDeque<Crash> {
+        &self.crashes
+    }
+
+    /// Updates the status of `server` and records a new crash, returns true once its restart is due
+    ///
+    /// The restart itself is left to the caller, stopping and starting takes a while
+    /// and the watchdog shouldn't be locked all that time, see [`Watchdog::restart_failed`].
+    /// Servers without a [`HostableServer::lifecycle_mut`] can't be watched
+    pub fn check(&mut self, server: &mut dyn HostableServer) -> bool {
+        if let Err(e) = server.update_status() {
+            eprintln!(
+                "\x1b[31m{}: the watchdog couldn't update the status: {e}\x1b[39m",
+                server.get_path()
+            );
+        }
+        let Some(lifecycle) = server.lifecycle_mut() else {
+            return false;
+        };
+
+        let now = Utc::now();
+        let state = lifecycle.state();
+        let crash = match state {
+            State::Unhealthy
+                if self
+                    .settings
+                    .unhealthy
+                    .is_some_and(|limit| Self::elapsed(lifecycle.since(), now) > limit) =>
+            {
+                lifecycle.crash();
+                Some((
+                    format!(
+                        "unhealthy for more than {}s",
+                        self.settings.unhealthy.unwrap_or_default().as_secs()
+                    ),
+                    true,
+                ))
+            }
+            State::Crashed if self.last_crash != Some(lifecycle.since()) => {
+                Some(self.describe(server.last_exit().as_ref()))
+            }
+            _ => None,
+        };
+        if let Some((reason, failure)) = crash {
+            self.record(server, reason, failure);
+        }
+
+        self.follow_up(server, now)
+    }
+
+    /// Records that the restart after a crash failed, it is retried after a longer backoff
+    pub fn restart_failed(&mut self, server: &mut dyn HostableServer, error: &CommandFailure) {
+        if let Some(lifecycle) = server.lifecycle_mut() {
+            lifecycle.crash();
+        }
+        self.record(server, format!("restart failed: {error}"), true);
+    }
+
+    /// Says whether a crashed server is due for its restart, forgets old restarts of a recovered one
+    fn follow_up(&mut self, server: &mut dyn HostableServer, now: DateTime<Utc>) -> bool {
+        let Some(lifecycle) = server.lifecycle_mut() else {
+            return false;
+        };
+        let state = lifecycle.state();
+        let since = lifecycle.since();
+        self.last_state = state;
+        match state {
+            State::Crashed => {}
+            State::Running if Self::elapsed(since, now) > self.settings.max_backoff => {
+                self.attempts = 0;
+                self.next_restart = None;
+                return false;
+            }
+            // started or stopped by somebody, or still on its way
+            _ => {
+                if matches!(state, State::Stopping | State::Stopped) {
+                    self.attempts = 0;
+                }
+                self.next_restart = None;
+                return false;
+            }
+        }
+        if self.next_restart.is_none_or(|at| now < at) {
+            return false;
+        }
+
+        self.next_restart = None;
+        self.attempts += 1;
+        println!(
+            "\x1b[36m{}: restarting after a crash, attempt {} of {}\x1b[39m",
+            server.get_path(),
+            self.attempts,
+            self.settings.max_retries
+        );
+        true
+    }
+
+    /// Remembers a crash of `server` and schedules its restart
+    fn record(&mut self, server: &mut dyn HostableServer, reason: String, failure: bool) {
+        let now = Utc::now();
+        let restart = match self.settings.restart {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => failure,
+            RestartPolicy::Always => true,
+        };
+        self.next_restart = if restart && self.attempts < self.settings.max_retries {
+            chrono::Duration::from_std(self.backoff())
+                .ok()
+                .and_then(|backoff| now.checked_add_signed(backoff))
+        } else {
+            None
+        };
+
+        let mut log = server.console_output().unwrap_or_default();
+        log.drain(..log.len().saturating_sub(self.settings.log_lines));
+        let crash = Crash {
+            at: now,
+            after: self.last_state,
+            reason,
+            failure,
+            exit: server.last_exit(),
+            log,
+            restart_at: self.next_restart,
+        };
+        eprintln!(
+            "\x1b[31m{} crashed: {}, {}\x1b[39m",
+            server.get_path(),
+            crash.reason,
+            match (restart, crash.restart_at) {
+                (false, _) => "not restarting it".to_owned(),
+                (true, None) => format!("gave up after {} restarts", self.attempts),
+                (true, Some(at)) => format!("restarting it at {at}"),
+            }
+        );
+
+        self.last_crash = server.lifecycle_mut().map(|lifecycle| lifecycle.since());
+        if self.crashes.len() == CRASH_HISTORY {
+            self.crashes.pop_front();
+        }
+        self.crashes.push_back(crash);
+    }
+
+    /// Says why the server crashed, and whether it was a failure
+    fn describe(&self, exit: Option<&Exit>) -> (String, bool) {
+        if self.last_state == State::Starting {
+            return ("wasn't ready within the ready timeout".to_owned(), true);
+        }
+        match exit {
+            Some(Exit {
+                signal: Some(signal),
+                ..
+            }) => (format!("killed by {signal}"), true),
+            Some(Exit { code: Some(0), .. }) => ("exited with 0".to_owned(), false),
+            Some(Exit {
+                code: Some(code), ..
+            }) => (format!("exited with {code}"), true),
+            _ => ("the process went away".to_owned(), true),
+        }
+    }
+
+    /// Wait before the next restart, doubled for every attempt so far
+    fn backoff(&self) -> Duration {
+        let factor = 2_u32.saturating_pow(self.attempts);
+        self.settings
+            .backoff
+            .saturating_mul(factor)
+            .min(self.settings.max_backoff)
+    }
+
+    /// Time from `since` to `now`
+    fn elapsed(since: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+        (now - since).to_std().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RestartPolicy, Watchdog, WatchdogSettings};
+    use crate::hostable_servers::{
+        lifecycle::{Lifecycle, Observation, State},
+        supervisor::Exit,
+        CommandFailure, HostableServer,
+    };
+    use chrono::Utc;
+    use std::{thread, time::Duration};
+
+    /// Server without any process, the test decides what happens to it
+    struct Fake {
+        /// Lifecycle the watchdog looks at
+        lifecycle: Lifecycle,
+        /// Code the process exits with in [`Fake::exit`]
+        code: Option<u32>,
+        /// Makes every start fail
+        broken: bool,
+        /// Number of starts
+        starts: usize,
+    }
+    impl Fake {
+        /// Returns a server that is running
+        fn running() -> Self {
+            let mut lifecycle = Lifecycle::new(Duration::from_mins(1));
+            lifecycle.observe(Observation::Ready);
+            Self {
+                lifecycle,
+                code: None,
+                broken: false,
+                starts: 0,
+            }
+        }
+
+        /// Lets the process exit with `code`
+        fn exit(&mut self, code: u32) {
+            self.code = Some(code);
+            self.lifecycle.observe(Observation::Down);
+        }
+    }
+
+    impl HostableServer for Fake {
+        fn get_path(&self) -> &'static str {
+            "fake"
+        }
+
+        fn start(&mut self) -> Result<(), CommandFailure> {
+            self.lifecycle.begin_start()?;
+            if self.broken {
+                return Err(CommandFailure::SpawnFailed {
+                    program: "fake".to_owned(),
+                    reason: "broken".to_owned(),
+                });
+            }
+            self.starts += 1;
+            self.code = None;
+            self.lifecycle.observe(Observation::Ready);
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), CommandFailure> {
+            self.lifecycle.begin_stop()?;
+            self.lifecycle.observe(Observation::Down);
+            Ok(())
+        }
+
+        fn update_status(&mut self) -> Result<(), CommandFailure> {
+            Ok(())
+        }
+
+        fn to_json(&self) -> Result<String, serde_json::Error> {
+            Ok(String::new())
+        }
+
+        fn lifecycle_mut(&mut self) -> Option<&mut Lifecycle> {
+            Some(&mut self.lifecycle)
+        }
+
+        fn last_exit(&self) -> Option<Exit> {
+            self.code.map(|code| Exit {
+                code: Some(code),
+                signal: None,
+                at: Utc::now(),
+            })
+        }
+    }
+
+    /// Settings restarting right away with `restart`
+    fn settings(restart: RestartPolicy) -> WatchdogSettings {
+        WatchdogSettings {
+            restart,
+            backoff: Duration::ZERO,
+            ..WatchdogSettings::default()
+        }
+    }
+
+    /// Checks the server and restarts it when that is due, like the web server does
+    ///
+    /// Returns whether it tried to restart
+    fn watch(watchdog: &mut Watchdog, server: &mut Fake) -> bool {
+        if !watchdog.check(server) {
+            return false;
+        }
+        server.stop().ok();
+        if let Err(e) = server.start() {
+            watchdog.restart_failed(server, &e);
+        }
+        true
+    }
+
+    #[test]
+    fn never_only_records_crashes() {
+        let mut watchdog = Watchdog::new(settings(RestartPolicy::Never));
+        let mut server = Fake::running();
+        assert!(!watch(&mut watchdog, &mut server));
+        server.exit(1);
+        assert!(!watch(&mut watchdog, &mut server));
+        assert!(!watch(&mut watchdog, &mut server));
+
+        assert_eq!(server.starts, 0);
+        assert_eq!(server.lifecycle.state(), State::Crashed);
+        let crashes = watchdog.crashes();
+        assert_eq!(crashes.len(), 1, "a crash is only recorded once");
+        assert_eq!(crashes[0].reason, "exited with 1");
+        assert_eq!(crashes[0].after, State::Running);
+        assert!(crashes[0].failure);
+        assert_eq!(crashes[0].restart_at, None);
+    }
+
+    #[test]
+    fn on_failure_ignores_clean_exits() {
+        let mut watchdog = Watchdog::new(settings(RestartPolicy::OnFailure));
+        let mut server = Fake::running();
+        watch(&mut watchdog, &mut server);
+        server.exit(0);
+        watch(&mut watchdog, &mut server);
+        assert!(!watch(&mut watchdog, &mut server));
+        assert_eq!(server.starts, 0);
+        assert!(!watchdog.crashes()[0].failure);
+
+        // started by somebody, then it fails
+        server.start().unwrap();
+        watch(&mut watchdog, &mut server);
+        server.exit(1);
+        watch(&mut watchdog, &mut server);
+        assert!(watchdog.crashes()[1].restart_at.is_some());
+        assert!(watch(&mut watchdog, &mut server));
+        assert_eq!(server.starts, 2);
+        assert_eq!(server.lifecycle.state(), State::Running);
+    }
+
+    #[test]
+    fn always_restarts_clean_exits_too() {
+        let mut watchdog = Watchdog::new(settings(RestartPolicy::Always));
+        let mut server = Fake::running();
+        watch(&mut watchdog, &mut server);
+        server.exit(0);
+        watch(&mut watchdog, &mut server);
+        assert!(watch(&mut watchdog, &mut server));
+        assert_eq!(server.starts, 1);
+        assert_eq!(server.lifecycle.state(), State::Running);
+        assert_eq!(watchdog.attempts, 1);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let mut watchdog = Watchdog::new(WatchdogSettings {
+            backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_mins(1),
+            ..WatchdogSettings::default()
+        });
+        let waits: Vec<_> = (0..5)
+            .map(|attempts| {
+                watchdog.attempts = attempts;
+                watchdog.backoff().as_secs()
+            })
+            .collect();
+        assert_eq!(waits, [10, 20, 40, 60, 60]);
+
+        // the restart isn't due before the backoff is over
+        let mut watchdog = Watchdog::new(WatchdogSettings {
+            restart: RestartPolicy::Always,
+            ..WatchdogSettings::default()
+        });
+        let mut server = Fake::running();
+        watch(&mut watchdog, &mut server);
+        server.exit(1);
+        assert!(!watch(&mut watchdog, &mut server));
+        let crash = &watchdog.crashes()[0];
+        assert_eq!(
+            crash.restart_at.map(|at| at - crash.at),
+            Some(chrono::Duration::seconds(10))
+        );
+        assert!(!watch(&mut watchdog, &mut server));
+        assert_eq!(server.starts, 0);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let mut watchdog = Watchdog::new(WatchdogSettings {
+            max_retries: 2,
+            ..settings(RestartPolicy::Always)
+        });
+        let mut server = Fake::running();
+        watch(&mut watchdog, &mut server);
+        server.broken = true;
+        server.exit(1);
+        let restarts = (0..10)
+            .filter(|_| watch(&mut watchdog, &mut server))
+            .count();
+
+        assert_eq!(restarts, 2);
+        assert_eq!(server.lifecycle.state(), State::Crashed);
+        let reasons: Vec<_> = watchdog.crashes().iter().map(|c| &*c.reason).collect();
+        assert_eq!(
+            reasons,
+            [
+                "exited with 1",
+                "restart failed: Couldn't run fake: broken",
+                "restart failed: Couldn't run fake: broken",
+            ]
+        );
+        assert_eq!(watchdog.crashes()[2].restart_at, None);
+
+        // a start by somebody else counts as a fresh start
+        server.broken = false;
+        server.start().unwrap();
+        server.stop().unwrap();
+        watch(&mut watchdog, &mut server);
+        assert_eq!(watchdog.attempts, 0);
+    }
+
+    #[test]
+    fn unhealthy_servers_crash_after_the_limit() {
+        let mut watchdog = Watchdog::new(settings(RestartPolicy::Always));
+        let mut server = Fake::running();
+        server.lifecycle.observe(Observation::NotReady);
+        thread::sleep(Duration::from_millis(5));
+        for _ in 0..3 {
+            assert!(!watch(&mut watchdog, &mut server));
+        }
+        assert_eq!(server.lifecycle.state(), State::Unhealthy, "no limit set");
+        assert!(watchdog.crashes().is_empty());
+
+        let mut watchdog = Watchdog::new(WatchdogSettings {
+            unhealthy: Some(Duration::from_millis(1)),
+            ..settings(RestartPolicy::Always)
+        });
+        watch(&mut watchdog, &mut server);
+        assert_eq!(watchdog.crashes()[0].reason, "unhealthy for more than 0s");
+        assert!(watch(&mut watchdog, &mut server));
+        assert_eq!(server.starts, 1);
+        assert_eq!(server.lifecycle.state(), State::Running);
+    }
+}
@@ -1,4 +1,3 @@
-
 //! =============================================================
 //! Rust Game Hosting Server - http/mod.rs
 //!
@@ -6,103 +5,427 @@
 //! Describes all the fun stuff that has something to do with HTTP requests
 //! =============================================================
 
-use std::io;
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+pub mod request;
+pub mod sse;
+pub mod static_files;
+pub mod websocket;
+
+/// HTTP header map, names are compared case-insensitively
+#[derive(Debug, Clone, Default)]
+pub struct Headers(Vec<(String, String)>);
+impl Headers {
+    /// Returns an empty header map
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+    /// Returns the first value of the header `name`
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+    /// Adds a value to the header `name`, keeping earlier ones
+    ///
+    /// Line breaks are dropped so a value can't end the header and start another one
+    pub fn append(&mut self, name: &str, value: &str) {
+        let single_line = |text: &str| text.replace(['\r', '\n'], "");
+        self.0.push((single_line(name), single_line(value)));
+    }
+    /// Sets the header `name` to `value`, dropping all previous values
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.append(name, value);
+    }
+    /// Removes every value of the header `name`
+    pub fn remove(&mut self, name: &str) {
+        self.0.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+    /// Iterates over all `(name, value)` pairs in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+}
 
 /// Message meant to be sent over Http
 #[derive(Debug)]
 pub struct Message {
     /// Describes the HTTP response variant
     pub variant: Variant,
-    /// Represents possible contents of an HTTP response
-    pub content: Content,
+    /// Headers sent along with the generated `Content-Length` and `Content-Type`
+    pub headers: Headers,
+    /// Raw response body
+    pub body: Vec<u8>,
 }
 impl Message {
     /// Returns a new Message
+    ///
+    /// `Content::File` is read right away, a missing file is logged and turns the message into a 500
     #[must_use]
-    pub const fn new(variant: Variant, content: Content) -> Self {
-        Self { variant, content }
+    pub fn new(variant: Variant, content: Content) -> Self {
+        let mut headers = Headers::new();
+        let body = match content.into_bytes() {
+            Ok((content_type, body)) => {
+                if let Some(content_type) = content_type {
+                    headers.set("Content-Type", content_type);
+                }
+                body
+            }
+            Err(e) => {
+                eprintln!("\x1b[31mCouldn't read the body of a response: {e}\x1b[39m");
+                return Self::internal_server_error(e.to_string());
+            }
+        };
+        Self {
+            variant,
+            headers,
+            body,
+        }
     }
-    /// A 500 internal server error with the text content of `e` 
+    /// A 500 internal server error with the text content of `e`
     #[must_use]
-    pub const  fn internal_server_error(e: String) -> Self {
+    pub fn internal_server_error(e: String) -> Self {
         Self::new(Variant::InternalServerError, Content::Text(e))
     }
+    /// Sets the header `name`, replacing the one [`Message::new`] picked if there was one
+    ///
+    /// Line breaks in `name` and `value` are dropped, see [`Headers::append`]
+    #[must_use]
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.set(name, value);
+        self
+    }
+    /// Returns the body as text, if it is valid UTF-8
+    #[must_use]
+    pub fn body_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.body).ok()
+    }
+    /// Writes the status line, the headers and the body to `writer`
+    ///
+    /// `Content-Length` is always computed from the body, responses that
+    /// can't have a body (101, 204, 304) are sent without one
+    ///
+    /// # Errors
+    /// Returns the error of the underlying writer
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::http::{Content, Message, Variant};
+    ///
+    /// let mut response = Vec::new();
+    /// Message::new(Variant::Ok, Content::Struct("[]".to_owned()))
+    ///     .write_to(&mut response)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     response,
+    ///     b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n[]"
+    /// );
+    /// ```
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        // collected first so the head goes out in one write
+        let mut head = Vec::new();
+        write!(head, "{}\r\n", self.status_line())?;
+
+        for (name, value) in self.headers.iter() {
+            if !name.eq_ignore_ascii_case("Content-Length") {
+                write!(head, "{name}: {value}\r\n")?;
+            }
+        }
+        let has_body = self.variant.allows_body();
+        if has_body {
+            write!(head, "Content-Length: {}\r\n", self.body.len())?;
+        }
+        head.extend_from_slice(b"\r\n");
+
+        writer.write_all(&head)?;
+        if has_body {
+            writer.write_all(&self.body)?;
+        }
+        Ok(())
+    }
+    /// Returns the status line without the trailing `\r\n`
+    #[must_use]
+    pub fn status_line(&self) -> String {
+        format!("HTTP/1.1 {} {}", self.variant.code(), self.variant.reason())
+    }
 }
 impl Default for Message {
     fn default() -> Self {
-        Self {
-            variant: Variant::Ok,
-            content: Content::Empty,
-        }
+        Self::new(Variant::Ok, Content::Empty)
     }
 }
-impl std::string::ToString for Message {
-    fn to_string(&self) -> String {
-        // status line
-        let mut message = match self.variant {
-            Variant::Ok => "HTTP/1.1 200 OK\r\n",
-            Variant::ServiceUnavailable => "HTTP/1.1 503 Service Unavailable\r\n",
-            Variant::NotFound => "HTTP/1.1 404 NOT FOUND\r\n",
-            Variant::InternalServerError => "500 Internal Server Error\r\n",
+impl fmt::Display for Message {
+    /// Human readable form of the message, binary bodies are summarized
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.status_line())?;
+        for (name, value) in self.headers.iter() {
+            writeln!(f, "{name}: {value}")?;
         }
-        .to_owned();
-
-        // header
-        message += match self.content {
-            Content::Text(_) => "Content-Type: text/plain\r\n",
-            _ => "\r\n",
-        };
-
-        // response
-        match self.content.to_string() {
-            Ok(ok) => {
-                message + &ok
-            },
-            Err(e) => {
-                format!("HTTP/1.1 404 NOT FOUND \r\n Content-Type: text/plain\r\n {e}")
-            },
+        match self.body_text() {
+            Some(text) => write!(f, "\n{text}"),
+            None => write!(f, "\n<{} bytes>", self.body.len()),
         }
     }
 }
 
-
 /// Describes the HTTP response variant
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Variant {
+    /// 101 Switching Protocols
+    SwitchingProtocols,
     /// 200 OK
-    Ok,                  
-    /// 503 Service Unavailable
-    ServiceUnavailable,  
+    Ok,
+    /// 201 Created
+    Created,
+    /// 204 No Content
+    NoContent,
+    /// 304 Not Modified
+    NotModified,
+    /// 400 Bad Request
+    BadRequest,
+    /// 401 Unauthorized
+    Unauthorized,
+    /// 403 Forbidden
+    Forbidden,
     /// 404 Not Found
-    NotFound,            
+    NotFound,
+    /// 405 Method Not Allowed
+    MethodNotAllowed,
+    /// 409 Conflict
+    Conflict,
+    /// 413 Payload Too Large
+    PayloadTooLarge,
+    /// 429 Too Many Requests
+    TooManyRequests,
+    /// 431 Request Header Fields Too Large
+    RequestHeaderFieldsTooLarge,
     /// 500 Internal Server Error
-    InternalServerError, 
+    InternalServerError,
+    /// 502 Bad Gateway
+    BadGateway,
+    /// 503 Service Unavailable
+    ServiceUnavailable,
+    /// 504 Gateway Timeout
+    GatewayTimeout,
+}
+impl Variant {
+    /// Numeric status code
+    #[must_use]
+    pub const fn code(self) -> u16 {
+        match self {
+            Self::SwitchingProtocols => 101,
+            Self::Ok => 200,
+            Self::Created => 201,
+            Self::NoContent => 204,
+            Self::NotModified => 304,
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::Forbidden => 403,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::Conflict => 409,
+            Self::PayloadTooLarge => 413,
+            Self::TooManyRequests => 429,
+            Self::RequestHeaderFieldsTooLarge => 431,
+            Self::InternalServerError => 500,
+            Self::BadGateway => 502,
+            Self::ServiceUnavailable => 503,
+            Self::GatewayTimeout => 504,
+        }
+    }
+    /// Reason phrase sent after the status code
+    #[must_use]
+    pub const fn reason(self) -> &'static str {
+        match self {
+            Self::SwitchingProtocols => "Switching Protocols",
+            Self::Ok => "OK",
+            Self::Created => "Created",
+            Self::NoContent => "No Content",
+            Self::NotModified => "Not Modified",
+            Self::BadRequest => "Bad Request",
+            Self::Unauthorized => "Unauthorized",
+            Self::Forbidden => "Forbidden",
+            Self::NotFound => "Not Found",
+            Self::MethodNotAllowed => "Method Not Allowed",
+            Self::Conflict => "Conflict",
+            Self::PayloadTooLarge => "Payload Too Large",
+            Self::TooManyRequests => "Too Many Requests",
+            Self::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
+            Self::InternalServerError => "Internal Server Error",
+            Self::BadGateway => "Bad Gateway",
+            Self::ServiceUnavailable => "Service Unavailable",
+            Self::GatewayTimeout => "Gateway Timeout",
+        }
+    }
+    /// Returns false for status codes that must not carry a body
+    #[must_use]
+    pub const fn allows_body(self) -> bool {
+        !matches!(
+            self,
+            Self::SwitchingProtocols | Self::NoContent | Self::NotModified
+        )
+    }
+    /// Returns true for 2xx status codes
+    #[must_use]
+    pub const fn is_success(self) -> bool {
+        self.code() / 100 == 2
+    }
 }
 
 /// Represents possible contents of an HTTP response
 #[derive(Debug)]
 pub enum Content {
-    /// File to be read using [`std::fs::read_to_string()`]
+    /// File to be read using [`std::fs::read()`]
     File(String),
     /// Generic text
     Text(String),
     /// Struct already parsed into json
     Struct(String),
-    /// RawBytes, used to transfer file such as the favicon.ico
+    /// `RawBytes`, used to transfer file such as the favicon.ico
+    ///
+    /// Set the `Content-Type` with [`Message::with_header`]
     RawBytes(Box<[u8]>),
     /// Notgin
     Empty,
 }
 
 impl Content {
-    /// This Should Not Be Used for `HttpContent::RawBytes` with any other purpose  
-    /// then displaying
-    fn to_string(&self) -> io::Result<String> {
+    /// Returns the default `Content-Type` and the bytes of the body
+    fn into_bytes(self) -> io::Result<(Option<&'static str>, Vec<u8>)> {
         match self {
-            Self::File(filename) => std::fs::read_to_string(filename),
-            Self::Text(txt) | Self::Struct(txt) => Ok(txt.clone()),
-            Self::Empty => Ok(String::new()),
-            Self::RawBytes(_) => Ok(String::from("RawBytes is not meant to be displayed ᓚᘏᗢ")),
+            Self::File(filename) => {
+                let bytes = std::fs::read(&filename)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{filename}: {e}")))?;
+                Ok((Some(content_type_of(&filename)), bytes))
+            }
+            Self::Text(txt) => Ok((Some("text/plain; charset=utf-8"), txt.into_bytes())),
+            Self::Struct(json) => Ok((Some("application/json"), json.into_bytes())),
+            Self::RawBytes(bytes) => Ok((Some("application/octet-stream"), bytes.into_vec())),
+            Self::Empty => Ok((None, Vec::new())),
         }
     }
 }
+
+/// Guesses the `Content-Type` of a file from its extension
+#[must_use]
+pub fn content_type_of(filename: &str) -> &'static str {
+    let extension = std::path::Path::new(filename)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "log" | "sh" => "text/plain; charset=utf-8",
+        "ico" => "image/x-icon",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "wasm" => "application/wasm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_type_of, Content, Headers, Message, Variant};
+
+    /// Returns what `message` puts on the wire, as text
+    fn written(message: &Message) -> String {
+        let mut response = Vec::new();
+        message.write_to(&mut response).unwrap();
+        String::from_utf8(response).unwrap()
+    }
+
+    #[test]
+    fn header_names_ignore_case() {
+        let mut headers = Headers::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("set-cookie", "b=2");
+        assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+        assert_eq!(headers.iter().count(), 2);
+
+        headers.set("SET-cookie", "c=3");
+        assert_eq!(headers.iter().collect::<Vec<_>>(), [("SET-cookie", "c=3")]);
+        headers.remove("Set-Cookie");
+        assert_eq!(headers.get("Set-Cookie"), None);
+    }
+
+    #[test]
+    fn headers_cant_be_injected() {
+        let message = Message::new(Variant::Ok, Content::Empty)
+            .with_header("Location", "/\r\nSet-Cookie: session=stolen")
+            .with_header("X-Evil\r\n", "yes");
+
+        assert_eq!(
+            written(&message),
+            "HTTP/1.1 200 OK\r\nLocation: /Set-Cookie: session=stolen\r\nX-Evil: yes\r\n\
+             Content-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn the_body_decides_the_length() {
+        let message = Message::new(Variant::Ok, Content::Text("hello".to_owned()))
+            .with_header("Content-Length", "1000")
+            .with_header("Content-Type", "text/html");
+        assert_eq!(
+            written(&message),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 5\r\n\r\nhello"
+        );
+
+        let no_content = Message::new(Variant::NoContent, Content::Text("dropped".to_owned()));
+        assert_eq!(
+            written(&no_content),
+            "HTTP/1.1 204 No Content\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn missing_files_are_server_errors() {
+        let message = Message::new(
+            Variant::Ok,
+            Content::File("/nonexistent/web_server/index.html".to_owned()),
+        );
+
+        assert_eq!(message.variant, Variant::InternalServerError);
+        assert!(message
+            .body_text()
+            .unwrap()
+            .starts_with("/nonexistent/web_server/index.html: "));
+    }
+
+    #[test]
+    fn binary_bodies_are_summarized() {
+        let message = Message::new(Variant::Ok, Content::RawBytes(Box::new([0xff, 0xfe])));
+
+        assert_eq!(
+            message.headers.get("Content-Type"),
+            Some("application/octet-stream")
+        );
+        assert_eq!(
+            message.to_string(),
+            "HTTP/1.1 200 OK\nContent-Type: application/octet-stream\n\n<2 bytes>"
+        );
+        assert_eq!(
+            content_type_of("Update.JS"),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(content_type_of("start"), "application/octet-stream");
+    }
+}
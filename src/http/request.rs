@@ -0,0 +1,459 @@
+//! =============================================================
+//! Rust Game Hosting Server - http/request.rs
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Parses incoming HTTP/1.1 requests into a typed [`Request`]
+//! =============================================================
+
+use super::Headers;
+use std::{collections::HashMap, fmt, io, io::Read, net::SocketAddr};
+
+/// HTTP request method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Method {
+    /// GET
+    Get,
+    /// HEAD
+    Head,
+    /// POST
+    Post,
+    /// PUT
+    Put,
+    /// DELETE
+    Delete,
+    /// OPTIONS
+    Options,
+    /// Any other token, kept verbatim
+    Other(String),
+}
+impl Method {
+    /// Parses the method token of the request line
+    fn parse(token: &str) -> Self {
+        match token {
+            "GET" => Self::Get,
+            "HEAD" => Self::Head,
+            "POST" => Self::Post,
+            "PUT" => Self::Put,
+            "DELETE" => Self::Delete,
+            "OPTIONS" => Self::Options,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Options => "OPTIONS",
+            Self::Other(other) => other,
+        })
+    }
+}
+
+/// Size limits enforced while reading a [`Request`]
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum size of the request line and headers together, in bytes
+    pub max_head: usize,
+    /// Maximum size of the body, in bytes
+    pub max_body: usize,
+}
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_head: 8 * 1024,
+            max_body: 1024 * 1024,
+        }
+    }
+}
+
+/// Reasons why a [`Request`] couldn't be read
+#[derive(Debug)]
+pub enum ParseError {
+    /// The underlying stream failed
+    Io(io::Error),
+    /// The peer closed the connection before sending a full request
+    ConnectionClosed,
+    /// The request line or headers exceeded [`Limits::max_head`]
+    HeadTooLarge,
+    /// The announced body exceeded [`Limits::max_body`]
+    BodyTooLarge,
+    /// The request isn't valid HTTP/1.1
+    Malformed(String),
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Error with reading the stream: {e}"),
+            Self::ConnectionClosed => {
+                f.write_str("Connection closed before the request was complete")
+            }
+            Self::HeadTooLarge => f.write_str("Request head is too large"),
+            Self::BodyTooLarge => f.write_str("Request body is too large"),
+            Self::Malformed(reason) => write!(f, "Malformed request: {reason}"),
+        }
+    }
+}
+impl std::error::Error for ParseError {}
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A parsed HTTP/1.1 request
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// Request method
+    pub method: Method,
+    /// Percent-decoded path, without the query string
+    ///
+    /// Route by [`Request::segments`], an encoded `%2F` turns into a `/` here
+    pub path: String,
+    /// Percent-decoded segments of the path, split before decoding
+    segments: Vec<String>,
+    /// Percent-decoded query parameters
+    pub query: HashMap<String, String>,
+    /// Request headers, looked up case-insensitively
+    pub headers: Headers,
+    /// Raw request body
+    pub body: Vec<u8>,
+    /// Address of the client, filled in by whoever accepted the connection
+    pub peer: Option<SocketAddr>,
+}
+
+impl Request {
+    /// Reads a whole request from `reader`, calling `read` as often as needed
+    ///
+    /// # Errors
+    /// Returns a [`ParseError`] if the stream fails, the peer hangs up early,
+    /// the request breaks one of the `limits` or isn't valid HTTP/1.1
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::http::request::{Limits, Method, Request};
+    ///
+    /// let raw = b"POST /minecraft/start?force=1 HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi";
+    /// let request = Request::read_from(&mut &raw[..], Limits::default()).unwrap();
+    ///
+    /// assert_eq!(request.method, Method::Post);
+    /// assert_eq!(request.path, "/minecraft/start");
+    /// assert_eq!(request.query["force"], "1");
+    /// assert_eq!(request.headers.get("content-length"), Some("2"));
+    /// assert_eq!(request.body, b"hi");
+    /// ```
+    pub fn read_from<R: Read>(reader: &mut R, limits: Limits) -> Result<Self, ParseError> {
+        let mut buffer = Vec::with_capacity(1024);
+        let mut chunk = [0; 1024];
+
+        // read until the end of the headers, the body might already be partially in the buffer
+        let head_end = loop {
+            if let Some(end) = find_head_end(&buffer) {
+                break end;
+            }
+            if buffer.len() > limits.max_head {
+                return Err(ParseError::HeadTooLarge);
+            }
+            let read = reader.read(&mut chunk)?;
+            if read == 0 {
+                return Err(ParseError::ConnectionClosed);
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+        };
+        if head_end > limits.max_head {
+            return Err(ParseError::HeadTooLarge);
+        }
+        // the body starts after the empty line
+        let mut body = buffer.split_off(head_end + 4);
+
+        let head = std::str::from_utf8(&buffer[..head_end])
+            .map_err(|_| ParseError::Malformed("head isn't valid UTF-8".to_owned()))?;
+        let mut lines = head.split("\r\n");
+
+        let request_line = lines.next().unwrap_or_default();
+        let mut request_line_split = request_line.split(' ');
+        let (Some(method), Some(target), Some(version), None) = (
+            request_line_split.next(),
+            request_line_split.next(),
+            request_line_split.next(),
+            request_line_split.next(),
+        ) else {
+            return Err(ParseError::Malformed(format!(
+                "invalid request line: {request_line:?}"
+            )));
+        };
+        if method.is_empty() || !method.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(ParseError::Malformed(format!("invalid method: {method:?}")));
+        }
+        if version != "HTTP/1.1" && version != "HTTP/1.0" {
+            return Err(ParseError::Malformed(format!(
+                "unsupported version: {version:?}"
+            )));
+        }
+        if !target.starts_with('/') {
+            return Err(ParseError::Malformed(format!("invalid target: {target:?}")));
+        }
+
+        let mut headers = Headers::new();
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(ParseError::Malformed(format!("invalid header: {line:?}")));
+            };
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace()) {
+                return Err(ParseError::Malformed(format!(
+                    "invalid header name: {name:?}"
+                )));
+            }
+            headers.append(name, value.trim());
+        }
+
+        if headers.get("transfer-encoding").is_some() {
+            return Err(ParseError::Malformed(
+                "Transfer-Encoding isn't supported, use Content-Length".to_owned(),
+            ));
+        }
+        let content_length = match headers.get("content-length") {
+            None => 0,
+            Some(length) => length.parse::<usize>().map_err(|_| {
+                ParseError::Malformed(format!("invalid Content-Length: {length:?}"))
+            })?,
+        };
+        if content_length > limits.max_body {
+            return Err(ParseError::BodyTooLarge);
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let invalid_path = || ParseError::Malformed(format!("invalid path: {path:?}"));
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(percent_decode)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid_path)?;
+        let path = percent_decode(path).ok_or_else(invalid_path)?;
+        let query = parse_query(query)
+            .ok_or_else(|| ParseError::Malformed(format!("invalid query: {query:?}")))?;
+
+        body.truncate(content_length);
+        while body.len() < content_length {
+            let wanted = (content_length - body.len()).min(chunk.len());
+            let read = reader.read(&mut chunk[..wanted])?;
+            if read == 0 {
+                return Err(ParseError::ConnectionClosed);
+            }
+            body.extend_from_slice(&chunk[..read]);
+        }
+
+        Ok(Self {
+            method: Method::parse(method),
+            path,
+            segments,
+            query,
+            headers,
+            body,
+            peer: None,
+        })
+    }
+
+    /// Returns the path split into its non-empty segments
+    ///
+    /// `/minecraft/get_status` becomes `["minecraft", "get_status"]`, while
+    /// `/minecraft%2Fget_status` is the single segment `minecraft/get_status`
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().map(String::as_str)
+    }
+}
+
+/// Returns the index of the `\r\n\r\n` ending the headers, if it was received yet
+fn find_head_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n")
+}
+
+/// Decodes `%XX` escapes, returns `None` for invalid escapes or non UTF-8 results
+fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // from_str_radix would take a sign like in `%+5`
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Parses a `a=1&b=2` query string, `+` is decoded as a space
+fn parse_query(query: &str) -> Option<HashMap<String, String>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((
+                percent_decode(&key.replace('+', " "))?,
+                percent_decode(&value.replace('+', " "))?,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, Limits, Method, ParseError, Request};
+    use std::io::{self, Read};
+
+    /// Hands out its bytes a few at a time, like a slow client
+    struct Trickle<'a>(&'a [u8], usize);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let read = self.1.min(buffer.len()).min(self.0.len());
+            buffer[..read].copy_from_slice(&self.0[..read]);
+            self.0 = &self.0[read..];
+            Ok(read)
+        }
+    }
+
+    /// Parses `raw` with the default limits
+    fn parse(raw: &[u8]) -> Result<Request, ParseError> {
+        Request::read_from(&mut &raw[..], Limits::default())
+    }
+
+    /// Parses `raw` and returns why it is malformed
+    fn malformed(raw: &[u8]) -> String {
+        match parse(raw) {
+            Err(ParseError::Malformed(reason)) => reason,
+            other => panic!("expected a malformed request, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn malformed_request_lines_are_refused() {
+        assert!(malformed(b"GET /\r\n\r\n").starts_with("invalid request line"));
+        assert!(malformed(b"GET / HTTP/1.1 extra\r\n\r\n").starts_with("invalid request line"));
+        assert!(malformed(b"GET  / HTTP/1.1\r\n\r\n").starts_with("invalid request line"));
+        assert!(malformed(b"get / HTTP/1.1\r\n\r\n").starts_with("invalid method"));
+        assert!(malformed(b"GET / HTTP/2\r\n\r\n").starts_with("unsupported version"));
+        assert!(malformed(b"GET minecraft HTTP/1.1\r\n\r\n").starts_with("invalid target"));
+        assert!(malformed(b"GET / HTTP/1.1\r\nHost\r\n\r\n").starts_with("invalid header"));
+        assert!(
+            malformed(b"GET / HTTP/1.1\r\nMy Host: a\r\n\r\n").starts_with("invalid header name")
+        );
+        assert!(malformed(b"GET /\xff HTTP/1.1\r\n\r\n").contains("UTF-8"));
+        assert!(matches!(
+            parse(b"GET / HTTP/1.1\r\n"),
+            Err(ParseError::ConnectionClosed)
+        ));
+
+        let request = parse(b"DELETE /a HTTP/1.0\r\n\r\n").unwrap();
+        assert_eq!(request.method, Method::Delete);
+        let request = parse(b"PATCH /a HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.method, Method::Other("PATCH".to_owned()));
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let limits = Limits {
+            max_head: 64,
+            max_body: 4,
+        };
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(64));
+        assert!(matches!(
+            Request::read_from(&mut long.as_bytes(), limits),
+            Err(ParseError::HeadTooLarge)
+        ));
+        // never ends its headers
+        let endless = format!("GET / HTTP/1.1\r\n{}", "X-A: b\r\n".repeat(1000));
+        assert!(matches!(
+            Request::read_from(&mut endless.as_bytes(), limits),
+            Err(ParseError::HeadTooLarge)
+        ));
+
+        let body = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+        assert!(matches!(
+            Request::read_from(&mut &body[..], limits),
+            Err(ParseError::BodyTooLarge)
+        ));
+        let request = Request::read_from(&mut &body[..], Limits::default()).unwrap();
+        assert_eq!(request.body, b"hello");
+
+        assert!(malformed(b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n")
+            .starts_with("invalid Content-Length"));
+        assert!(matches!(
+            parse(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhel"),
+            Err(ParseError::ConnectionClosed)
+        ));
+    }
+
+    #[test]
+    fn partial_reads_are_put_together() {
+        let raw =
+            b"POST /arma/console HTTP/1.1\r\nContent-Length: 11\r\n\r\nsay hello!\nGET / HTTP/1.1";
+        for step in [1, 3, 7, 4096] {
+            let request = Request::read_from(&mut Trickle(raw, step), Limits::default()).unwrap();
+            assert_eq!(request.path, "/arma/console");
+            // the next request on the connection isn't part of the body
+            assert_eq!(
+                request.body, b"say hello!\n",
+                "reading {step} bytes at a time"
+            );
+        }
+    }
+
+    #[test]
+    fn chunked_bodies_are_refused() {
+        let reason = malformed(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        );
+        assert!(
+            reason.starts_with("Transfer-Encoding isn't supported"),
+            "{reason}"
+        );
+    }
+
+    #[test]
+    fn percent_escapes_are_decoded() {
+        assert_eq!(percent_decode("/a%20b%2Fc").as_deref(), Some("/a b/c"));
+        assert_eq!(percent_decode("%C3%A4").as_deref(), Some("\u{e4}"));
+        assert_eq!(percent_decode("a+b").as_deref(), Some("a+b"));
+        for invalid in ["%", "%2", "%zz", "%+5", "%-1", "%C3", "%2%41"] {
+            assert_eq!(percent_decode(invalid), None, "{invalid}");
+        }
+
+        let request =
+            parse(b"GET /files/my%20world?name=Steve+Alex&x=%26&flag HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(request.path, "/files/my world");
+        assert_eq!(
+            request.segments().collect::<Vec<_>>(),
+            ["files", "my world"]
+        );
+        assert_eq!(request.query["name"], "Steve Alex");
+        assert_eq!(request.query["x"], "&");
+        assert_eq!(request.query["flag"], "");
+        assert!(malformed(b"GET /%+5 HTTP/1.1\r\n\r\n").starts_with("invalid path"));
+        assert!(malformed(b"GET /?a=%zz HTTP/1.1\r\n\r\n").starts_with("invalid query"));
+    }
+
+    #[test]
+    fn encoded_slashes_stay_in_their_segment() {
+        let request = parse(b"GET //minecraft%2Fstart/a%2F..%2Fb/ HTTP/1.1\r\n\r\n").unwrap();
+
+        assert_eq!(
+            request.segments().collect::<Vec<_>>(),
+            ["minecraft/start", "a/../b"]
+        );
+        assert!(malformed(b"GET /ok/%zz HTTP/1.1\r\n\r\n").starts_with("invalid path"));
+    }
+}
@@ -0,0 +1,322 @@
+//! =============================================================
+//! Rust Game Hosting Server - http/sse.rs
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Server-Sent Events, numbered so reconnecting clients can catch up
+//! =============================================================
+
+use super::request::Request;
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// One event of an `text/event-stream`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Sent as `id:`, browsers send the last one back as `Last-Event-ID` when they reconnect
+    pub id: u64,
+    /// Sent as `event:`, the type `addEventListener` listens for
+    pub name: String,
+    /// Sent as `data:`, one line per line
+    pub data: String,
+    /// What the event is about, like the path of a server, isn't sent
+    ///
+    /// Lets every client filter out what it may not see
+    pub topic: String,
+}
+
+impl Event {
+    /// Writes the event in the `text/event-stream` format
+    ///
+    /// # Errors
+    /// Returns the error of the underlying writer
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::http::sse::Event;
+    ///
+    /// let event = Event {
+    ///     id: 3,
+    ///     name: "status".to_owned(),
+    ///     data: "{}".to_owned(),
+    ///     topic: "minecraft".to_owned(),
+    /// };
+    /// let mut bytes = Vec::new();
+    /// event.write_to(&mut bytes).unwrap();
+    /// assert_eq!(bytes, b"id: 3\nevent: status\ndata: {}\n\n");
+    /// ```
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = format!("id: {}\nevent: {}\n", self.id, self.name);
+        for line in self.data.split('\n') {
+            bytes.push_str("data: ");
+            bytes.push_str(line);
+            bytes.push('\n');
+        }
+        bytes.push('\n');
+        writer.write_all(bytes.as_bytes())
+    }
+}
+
+/// Writes the response head of an event stream and how long browsers wait before reconnecting
+///
+/// The stream has no `Content-Length`, it ends when the connection is closed
+///
+/// # Errors
+/// Returns the error of the underlying writer
+pub fn write_head<W: Write>(writer: &mut W, retry: Duration) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+         Connection: close\r\nX-Accel-Buffering: no\r\n\r\nretry: {}\n\n",
+        retry.as_millis()
+    )
+}
+
+/// Writes a comment that keeps proxies from closing an idle stream
+///
+/// # Errors
+/// Returns the error of the underlying writer, usually because the client went away
+pub fn write_heartbeat<W: Write>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(b": heartbeat\n\n")
+}
+
+/// Returns the `Last-Event-ID` a reconnecting browser sent
+#[must_use]
+pub fn last_event_id(request: &Request) -> Option<u64> {
+    request
+        .headers
+        .get("Last-Event-ID")
+        .and_then(|id| id.trim().parse().ok())
+}
+
+/// The latest events, shared by every client of a stream
+///
+/// Events are numbered from 1 on. A client that missed more than the
+/// `capacity` kept can't catch up and has to start over
+#[derive(Debug)]
+pub struct EventLog {
+    /// The kept events, oldest first
+    events: Mutex<Events>,
+    /// Woken up for every new event
+    published: Condvar,
+    /// Number of events kept
+    capacity: usize,
+}
+
+/// Content of an [`EventLog`]
+#[derive(Debug, Default)]
+struct Events {
+    /// The kept events, oldest first
+    kept: VecDeque<Event>,
+    /// Id of the newest event, 0 before the first
+    last_id: u64,
+}
+
+impl EventLog {
+    /// Returns an empty log keeping the last `capacity` events
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(Events::default()),
+            published: Condvar::new(),
+            capacity,
+        }
+    }
+
+    /// Adds an event and wakes up everybody waiting, returns its id
+    pub fn publish(&self, name: &str, topic: &str, data: String) -> u64 {
+        let id = {
+            let mut events = self.lock();
+            events.last_id += 1;
+            let id = events.last_id;
+            if events.kept.len() == self.capacity {
+                events.kept.pop_front();
+            }
+            events.kept.push_back(Event {
+                id,
+                name: name.to_owned(),
+                data,
+                topic: topic.to_owned(),
+            });
+            id
+        };
+        self.published.notify_all();
+        id
+    }
+
+    /// Returns the id of the newest event, 0 if there wasn't any yet
+    #[must_use]
+    pub fn last_id(&self) -> u64 {
+        self.lock().last_id
+    }
+
+    /// Returns the events after `id`
+    ///
+    /// `None` if some of them were already dropped, or `id` is from before a restart
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::http::sse::EventLog;
+    ///
+    /// let log = EventLog::new(2);
+    /// for data in ["a", "b", "c"] {
+    ///     log.publish("status", "minecraft", data.to_owned());
+    /// }
+    /// let events = log.since(1).unwrap();
+    /// assert_eq!(events.iter().map(|e| e.id).collect::<Vec<_>>(), [2, 3]);
+    ///
+    /// assert!(log.since(0).is_none());
+    /// assert!(log.since(7).is_none());
+    /// ```
+    #[must_use]
+    pub fn since(&self, id: u64) -> Option<Vec<Event>> {
+        Self::collect(&self.lock(), id)
+    }
+
+    /// Waits up to `timeout` for events after `id`, see [`EventLog::since`]
+    ///
+    /// Returns no events if none came in time
+    #[must_use]
+    pub fn wait(&self, id: u64, timeout: Duration) -> Option<Vec<Event>> {
+        let deadline = Instant::now() + timeout;
+        let mut events = self.lock();
+        while events.last_id == id {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            events = self
+                .published
+                .wait_timeout(events, left)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        let collected = Self::collect(&events, id);
+        drop(events);
+        collected
+    }
+
+    /// Copies the events after `id` out of `events`
+    fn collect(events: &Events, id: u64) -> Option<Vec<Event>> {
+        let oldest = events.kept.front().map_or(events.last_id + 1, |e| e.id);
+        if id > events.last_id || id + 1 < oldest {
+            return None;
+        }
+        Some(
+            events
+                .kept
+                .iter()
+                .filter(|event| event.id > id)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Locks the events, a panic in another thread doesn't lose them
+    fn lock(&self) -> MutexGuard<'_, Events> {
+        self.events.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{last_event_id, write_head, write_heartbeat, Event, EventLog};
+    use crate::http::request::{Limits, Request};
+    use std::{sync::Arc, thread, time::Duration};
+
+    /// A `GET /events` with the `headers`, each ending with `\r\n`
+    fn request(headers: &str) -> Request {
+        let text = format!("GET /events HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        Request::read_from(&mut text.as_bytes(), Limits::default()).unwrap()
+    }
+
+    /// Returns the ids of `events`
+    fn ids(events: &[Event]) -> Vec<u64> {
+        events.iter().map(|event| event.id).collect()
+    }
+
+    #[test]
+    fn reads_the_last_event_id() {
+        assert_eq!(last_event_id(&request("Last-Event-ID: 42\r\n")), Some(42));
+        assert_eq!(last_event_id(&request("last-event-id:  7 \r\n")), Some(7));
+        assert_eq!(last_event_id(&request("Last-Event-ID: seven\r\n")), None);
+        assert_eq!(last_event_id(&request("")), None);
+    }
+
+    #[test]
+    fn reconnecting_clients_catch_up() {
+        let log = EventLog::new(4);
+        assert_eq!(log.since(0), Some(Vec::new()));
+        for data in ["a", "b", "c"] {
+            log.publish("status", "minecraft", data.to_owned());
+        }
+
+        assert_eq!(ids(&log.since(0).unwrap()), [1, 2, 3]);
+        assert_eq!(ids(&log.since(2).unwrap()), [3]);
+        assert_eq!(log.since(3), Some(Vec::new()));
+        assert_eq!(log.last_id(), 3);
+    }
+
+    #[test]
+    fn unknown_ids_start_over() {
+        let log = EventLog::new(2);
+        for data in ["a", "b", "c"] {
+            log.publish("status", "minecraft", data.to_owned());
+        }
+        // event 1 was dropped already
+        assert_eq!(log.since(0), None);
+        assert_eq!(ids(&log.since(1).unwrap()), [2, 3]);
+        // from before a restart of the web server
+        assert_eq!(log.since(99), None);
+        assert_eq!(log.wait(99, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn waiting_ends_with_an_event_or_nothing_for_a_heartbeat() {
+        let log = Arc::new(EventLog::new(4));
+        assert_eq!(log.wait(0, Duration::from_millis(20)), Some(Vec::new()));
+        let mut bytes = Vec::new();
+        write_heartbeat(&mut bytes).unwrap();
+        assert_eq!(bytes, b": heartbeat\n\n");
+
+        let publisher = {
+            let log = Arc::clone(&log);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                log.publish("status", "arma", "{}".to_owned())
+            })
+        };
+        let events = log.wait(0, Duration::from_secs(10)).unwrap();
+        assert_eq!(ids(&events), [publisher.join().unwrap()]);
+        assert_eq!(events[0].topic, "arma");
+    }
+
+    #[test]
+    fn writes_the_stream_format() {
+        let mut bytes = Vec::new();
+        write_head(&mut bytes, Duration::from_secs(3)).unwrap();
+        let head = String::from_utf8(bytes).unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+        assert!(
+            head.contains("Content-Type: text/event-stream\r\n"),
+            "{head}"
+        );
+        assert!(head.ends_with("\r\n\r\nretry: 3000\n\n"), "{head}");
+
+        let event = Event {
+            id: 1,
+            name: "status".to_owned(),
+            data: "first\nsecond".to_owned(),
+            topic: "minecraft".to_owned(),
+        };
+        let mut bytes = Vec::new();
+        event.write_to(&mut bytes).unwrap();
+        assert_eq!(
+            bytes,
+            b"id: 1\nevent: status\ndata: first\ndata: second\n\n"
+        );
+    }
+}
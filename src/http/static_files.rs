@@ -0,0 +1,160 @@
+//! =============================================================
+//! Rust Game Hosting Server - `http/static_files.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Serves files from a single root directory, nothing outside of it
+//! =============================================================
+
+use super::{content_type_of, request::Request, Content, Message, Variant};
+use chrono::{DateTime, Utc};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Format of the `Last-Modified` header, RFC 9110 IMF-fixdate
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Static files rooted at a directory
+///
+/// Paths are canonicalized before they are opened, so neither `..` nor
+/// symlinks can reach anything outside of the root.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    /// Directory every served file has to be inside of
+    root: PathBuf,
+}
+
+impl StaticFiles {
+    /// Returns static files served from `root`
+    ///
+    /// The root is resolved on every request, so it may be created later
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the root directory as it was configured
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Serves the file at `relative_path` inside the root
+    ///
+    /// Answers 304 when the `If-None-Match` or `If-Modified-Since` headers of
+    /// `request` show that the client's copy is still fresh
+    ///
+    /// # Errors
+    /// Returns a 403 for paths leaving the root and a 404 for missing files
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::http::{request::{Limits, Request}, static_files::StaticFiles, Variant};
+    ///
+    /// let raw = b"GET /file/../Cargo.toml HTTP/1.1\r\n\r\n";
+    /// let request = Request::read_from(&mut &raw[..], Limits::default()).unwrap();
+    ///
+    /// let static_files = StaticFiles::new("src");
+    /// assert_eq!(static_files.serve("../Cargo.toml", &request).variant, Variant::Forbidden);
+    /// assert_eq!(static_files.serve("lib.rs", &request).variant, Variant::Ok);
+    /// ```
+    #[must_use]
+    pub fn serve(&self, relative_path: &str, request: &Request) -> Message {
+        let path = match self.resolve(relative_path) {
+            Ok(path) => path,
+            Err(message) => return message,
+        };
+
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return not_found(relative_path),
+        };
+        let modified: DateTime<Utc> = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH).into();
+        let etag = format!(
+            "\"{:x}-{:x}\"",
+            metadata.len(),
+            modified.timestamp_nanos_opt().unwrap_or_default()
+        );
+        let last_modified = modified.format(HTTP_DATE).to_string();
+
+        if is_fresh(request, &etag, modified) {
+            return Message::new(Variant::NotModified, Content::Empty)
+                .with_header("ETag", &etag)
+                .with_header("Last-Modified", &last_modified);
+        }
+
+        match fs::read(&path) {
+            Ok(bytes) => Message::new(Variant::Ok, Content::RawBytes(bytes.into_boxed_slice()))
+                .with_header("Content-Type", content_type_of(relative_path))
+                .with_header("ETag", &etag)
+                .with_header("Last-Modified", &last_modified)
+                // always revalidate, the 304s are cheap
+                .with_header("Cache-Control", "no-cache"),
+            Err(e) => Message::internal_server_error(e.to_string()),
+        }
+    }
+
+    /// Turns `relative_path` into a canonical path that is inside of the root
+    fn resolve(&self, relative_path: &str) -> Result<PathBuf, Message> {
+        let relative = Path::new(relative_path);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(forbidden(relative_path));
+        }
+
+        let root = self.root.canonicalize().map_err(|e| {
+            eprintln!(
+                "\x1b[31mStatic root {} is unusable: {e}\x1b[39m",
+                self.root.display()
+            );
+            not_found(relative_path)
+        })?;
+        let path = root
+            .join(relative)
+            .canonicalize()
+            .map_err(|_| not_found(relative_path))?;
+
+        // a symlink could still point somewhere else
+        if path.starts_with(&root) {
+            Ok(path)
+        } else {
+            Err(forbidden(relative_path))
+        }
+    }
+}
+
+/// Returns true if the client already has the current version of the file
+fn is_fresh(request: &Request, etag: &str, modified: DateTime<Utc>) -> bool {
+    // If-None-Match wins over If-Modified-Since, RFC 9110 13.2.2
+    if let Some(if_none_match) = request.headers.get("If-None-Match") {
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag);
+    }
+    request
+        .headers
+        .get("If-Modified-Since")
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+        .is_some_and(|since| modified.timestamp() <= since.timestamp())
+}
+
+/// 404 for a file that isn't there
+fn not_found(relative_path: &str) -> Message {
+    Message::new(
+        Variant::NotFound,
+        Content::Text(format!("File Not Found: {relative_path}")),
+    )
+}
+
+/// 403 for a path that tries to leave the root
+fn forbidden(relative_path: &str) -> Message {
+    Message::new(
+        Variant::Forbidden,
+        Content::Text(format!("Access denied: {relative_path}")),
+    )
+}
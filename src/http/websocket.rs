@@ -0,0 +1,376 @@
+//! =============================================================
+//! Rust Game Hosting Server - http/websocket.rs
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! Just enough of RFC 6455 to talk to a browser over a WebSocket
+//! =============================================================
+
+use super::{
+    request::{Method, Request},
+    Content, Message, Variant,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+use std::io::{self, Read, Write};
+
+/// Appended to the `Sec-WebSocket-Key` before hashing it, see RFC 6455 section 1.3
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest message a client may send, console lines are short
+pub const MAX_MESSAGE: usize = 64 * 1024;
+
+/// Opcode of a continuation frame
+const CONTINUATION: u8 = 0x0;
+/// Opcode of a text frame
+const TEXT: u8 = 0x1;
+/// Opcode of a binary frame
+const BINARY: u8 = 0x2;
+/// Opcode of a close frame
+const CLOSE: u8 = 0x8;
+/// Opcode of a ping frame
+const PING: u8 = 0x9;
+/// Opcode of a pong frame
+const PONG: u8 = 0xA;
+
+/// A complete WebSocket message, fragments are already put together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// UTF-8 text
+    Text(String),
+    /// Anything else
+    Binary(Vec<u8>),
+    /// Has to be answered with a [`Frame::Pong`] carrying the same bytes
+    Ping(Vec<u8>),
+    /// Answer to a [`Frame::Ping`]
+    Pong(Vec<u8>),
+    /// The other side is going away, with a status code if it sent one
+    Close(Option<u16>),
+}
+
+/// Returns true if `request` asks to switch to the WebSocket protocol
+#[must_use]
+pub fn is_upgrade(request: &Request) -> bool {
+    request.method == Method::Get
+        && request
+            .headers
+            .get("Upgrade")
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+/// Computes the `Sec-WebSocket-Accept` answer to a `Sec-WebSocket-Key`
+///
+/// # Example
+/// The example of RFC 6455:
+/// ```
+/// use web_server::http::websocket::accept_key;
+///
+/// assert_eq!(
+///     accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+///     "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+/// );
+/// ```
+#[must_use]
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    STANDARD.encode(sha1.finalize())
+}
+
+/// Returns the `101 Switching Protocols` answer to an upgrade `request`
+///
+/// # Errors
+/// Returns a 400 if the request lacks the key or wants another version than 13
+pub fn handshake(request: &Request) -> Result<Message, Message> {
+    if request.headers.get("Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        return Err(Message::new(
+            Variant::BadRequest,
+            Content::Text("Only WebSocket version 13 is supported".to_owned()),
+        )
+        .with_header("Sec-WebSocket-Version", "13"));
+    }
+    let Some(key) = request.headers.get("Sec-WebSocket-Key") else {
+        return Err(Message::new(
+            Variant::BadRequest,
+            Content::Text("Missing Sec-WebSocket-Key".to_owned()),
+        ));
+    };
+    Ok(Message::new(Variant::SwitchingProtocols, Content::Empty)
+        .with_header("Upgrade", "websocket")
+        .with_header("Connection", "Upgrade")
+        .with_header("Sec-WebSocket-Accept", &accept_key(key)))
+}
+
+/// Reads messages a client sends, putting fragmented ones together
+pub struct FrameReader<R> {
+    /// Where the frames come from
+    inner: R,
+    /// Opcode and payload of a fragmented message received so far
+    partial: Option<(u8, Vec<u8>)>,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Wraps `inner`
+    pub const fn new(inner: R) -> Self {
+        Self {
+            inner,
+            partial: None,
+        }
+    }
+
+    /// Reads the next message, blocks until it is complete
+    ///
+    /// # Errors
+    /// Returns the error of the underlying reader, and [`io::ErrorKind::InvalidData`]
+    /// for unmasked frames, messages over [`MAX_MESSAGE`] and text that isn't UTF-8
+    ///
+    /// # Example
+    /// A masked `Hello` as a browser sends it:
+    /// ```
+    /// use web_server::http::websocket::{Frame, FrameReader};
+    ///
+    /// let bytes: &[u8] = &[0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+    /// let mut reader = FrameReader::new(bytes);
+    /// assert_eq!(reader.read().unwrap(), Frame::Text("Hello".to_owned()));
+    /// ```
+    pub fn read(&mut self) -> io::Result<Frame> {
+        loop {
+            let (fin, opcode, payload) = self.read_frame()?;
+            let (opcode, payload) = match opcode {
+                // control frames may arrive between the fragments of a message
+                CLOSE => {
+                    let code = payload
+                        .get(..2)
+                        .map(|code| u16::from_be_bytes([code[0], code[1]]));
+                    return Ok(Frame::Close(code));
+                }
+                PING => return Ok(Frame::Ping(payload)),
+                PONG => return Ok(Frame::Pong(payload)),
+                CONTINUATION => {
+                    let Some((opcode, mut message)) = self.partial.take() else {
+                        return Err(invalid("continuation without a message to continue"));
+                    };
+                    if message.len() + payload.len() > MAX_MESSAGE {
+                        return Err(invalid("message too large"));
+                    }
+                    message.extend_from_slice(&payload);
+                    (opcode, message)
+                }
+                TEXT | BINARY if self.partial.is_none() => (opcode, payload),
+                TEXT | BINARY => return Err(invalid("new message before the last one ended")),
+                _ => return Err(invalid("unknown opcode")),
+            };
+            if !fin {
+                self.partial = Some((opcode, payload));
+                continue;
+            }
+            return if opcode == TEXT {
+                String::from_utf8(payload)
+                    .map(Frame::Text)
+                    .map_err(|_| invalid("text that isn't UTF-8"))
+            } else {
+                Ok(Frame::Binary(payload))
+            };
+        }
+    }
+
+    /// Reads a single frame and unmasks its payload
+    fn read_frame(&mut self) -> io::Result<(bool, u8, Vec<u8>)> {
+        let mut head = [0; 2];
+        self.inner.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        if head[1] & 0x80 == 0 {
+            return Err(invalid("clients have to mask their frames"));
+        }
+        let length = match head[1] & 0x7F {
+            126 => {
+                let mut length = [0; 2];
+                self.inner.read_exact(&mut length)?;
+                u64::from(u16::from_be_bytes(length))
+            }
+            127 => {
+                let mut length = [0; 8];
+                self.inner.read_exact(&mut length)?;
+                u64::from_be_bytes(length)
+            }
+            length => u64::from(length),
+        };
+        let length = usize::try_from(length)
+            .ok()
+            .filter(|length| *length <= MAX_MESSAGE)
+            .ok_or_else(|| invalid("message too large"))?;
+
+        let mut mask = [0; 4];
+        self.inner.read_exact(&mut mask)?;
+        let mut payload = vec![0; length];
+        self.inner.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        Ok((fin, opcode, payload))
+    }
+}
+
+/// Writes `frame` unmasked and unfragmented, the way a server sends it
+///
+/// # Errors
+/// Returns the error of the underlying writer
+///
+/// # Example
+/// ```
+/// use web_server::http::websocket::{write_frame, Frame};
+///
+/// let mut bytes = Vec::new();
+/// write_frame(&mut bytes, &Frame::Text("Hello".to_owned())).unwrap();
+/// assert_eq!(bytes, b"\x81\x05Hello");
+/// ```
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame) -> io::Result<()> {
+    let close;
+    let (opcode, payload) = match frame {
+        Frame::Text(text) => (TEXT, text.as_bytes()),
+        Frame::Binary(bytes) => (BINARY, bytes.as_slice()),
+        Frame::Ping(bytes) => (PING, bytes.as_slice()),
+        Frame::Pong(bytes) => (PONG, bytes.as_slice()),
+        Frame::Close(code) => {
+            close = code.map(u16::to_be_bytes);
+            (CLOSE, close.as_ref().map_or(&[][..], |code| &code[..]))
+        }
+    };
+
+    // collected first so the frame goes out in one write
+    let mut bytes = Vec::with_capacity(payload.len() + 10);
+    bytes.push(0x80 | opcode);
+    match u16::try_from(payload.len()) {
+        Ok(length @ 0..=125) => bytes.push(length.to_be_bytes()[1]),
+        Ok(length) => {
+            bytes.push(126);
+            bytes.extend_from_slice(&length.to_be_bytes());
+        }
+        Err(_) => {
+            bytes.push(127);
+            bytes.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        }
+    }
+    bytes.extend_from_slice(payload);
+    writer.write_all(&bytes)
+}
+
+/// An [`io::ErrorKind::InvalidData`] error for a broken frame
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        write_frame, Frame, FrameReader, BINARY, CLOSE, CONTINUATION, MAX_MESSAGE, PING, TEXT,
+    };
+    use std::io::ErrorKind;
+
+    /// A frame as a client sends it, masked with a fixed key
+    fn masked(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x12, 0x34, 0x56, 0x78];
+        let mut bytes = vec![if fin { 0x80 | opcode } else { opcode }];
+        match payload.len() {
+            length @ 0..=125 => bytes.push(0x80 | u8::try_from(length).unwrap()),
+            length => {
+                bytes.push(0x80 | 127);
+                bytes.extend_from_slice(&(length as u64).to_be_bytes());
+            }
+        }
+        bytes.extend_from_slice(&mask);
+        bytes.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        bytes
+    }
+
+    /// Reads the first message of `bytes`
+    fn read(bytes: &[u8]) -> std::io::Result<Frame> {
+        FrameReader::new(bytes).read()
+    }
+
+    #[test]
+    fn fragments_are_put_together() {
+        let bytes = [
+            masked(false, TEXT, b"Hel"),
+            // a ping may come between the fragments
+            masked(true, PING, b"?"),
+            masked(false, CONTINUATION, b"l"),
+            masked(true, CONTINUATION, b"o"),
+            masked(true, BINARY, &[1, 2]),
+        ]
+        .concat();
+        let mut reader = FrameReader::new(&bytes[..]);
+
+        assert_eq!(reader.read().unwrap(), Frame::Ping(b"?".to_vec()));
+        assert_eq!(reader.read().unwrap(), Frame::Text("Hello".to_owned()));
+        assert_eq!(reader.read().unwrap(), Frame::Binary(vec![1, 2]));
+    }
+
+    #[test]
+    fn broken_fragments_are_refused() {
+        let error = read(&masked(true, CONTINUATION, b"lo")).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let bytes = [masked(false, TEXT, b"Hel"), masked(true, TEXT, b"lo")].concat();
+        let error = read(&bytes).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn oversized_messages_are_refused() {
+        let error = read(&masked(true, TEXT, &vec![b'a'; MAX_MESSAGE + 1])).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        // every fragment fits, together they don't
+        let half = vec![b'a'; MAX_MESSAGE / 2 + 1];
+        let bytes = [
+            masked(false, TEXT, &half),
+            masked(true, CONTINUATION, &half),
+        ]
+        .concat();
+        let error = read(&bytes).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        let text = "a".repeat(MAX_MESSAGE);
+        assert_eq!(
+            read(&masked(true, TEXT, text.as_bytes())).unwrap(),
+            Frame::Text(text)
+        );
+    }
+
+    #[test]
+    fn unmasked_frames_are_refused() {
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, &Frame::Text("Hello".to_owned())).unwrap();
+        let error = read(&bytes).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn close_frames_carry_their_code() {
+        assert_eq!(
+            read(&masked(true, CLOSE, &1000_u16.to_be_bytes())).unwrap(),
+            Frame::Close(Some(1000))
+        );
+        assert_eq!(read(&masked(true, CLOSE, &[])).unwrap(), Frame::Close(None));
+
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, &Frame::Close(Some(1001))).unwrap();
+        write_frame(&mut bytes, &Frame::Close(None)).unwrap();
+        assert_eq!(bytes, [0x88, 2, 0x03, 0xE9, 0x88, 0]);
+    }
+
+    #[test]
+    fn long_frames_get_longer_lengths() {
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, &Frame::Binary(vec![0; 300])).unwrap();
+        assert_eq!(bytes[..4], [0x82, 126, 0x01, 0x2C]);
+        assert_eq!(bytes.len(), 4 + 300);
+
+        let mut bytes = Vec::new();
+        write_frame(&mut bytes, &Frame::Binary(vec![0; 70_000])).unwrap();
+        assert_eq!(bytes[..2], [0x82, 127]);
+        assert_eq!(bytes[2..10], 70_000_u64.to_be_bytes());
+    }
+}
@@ -10,34 +10,229 @@
 //! Servers that implement the [`HostableServer`] trait can be run on it
 //! =============================================================
 
-use hostable_servers::HostableServer;
-use http::{Content, Message, Variant};
+use auth::{Auth, AuthError, Identity, Permission};
+use config::{Config, ConfigError};
+use hostable_servers::{
+    console::ConsoleFeed, lifecycle::State, process::CancelToken, watchdog::Watchdog,
+    CommandFailure, HostableServer, ListAddition, PlayerList,
+};
+use http::{
+    request::{Limits, Method, ParseError, Request},
+    sse::{self, EventLog},
+    static_files::StaticFiles,
+    websocket::{self, Frame, FrameReader},
+    Content, Message, Variant,
+};
 use std::{
-    fs,
+    collections::BTreeMap,
     io::prelude::*,
-    net::{TcpListener, TcpStream},
+    net::{Shutdown, TcpListener, TcpStream},
+    path::PathBuf,
     process::Command,
     str::from_utf8,
-    sync::mpsc::{self, Sender},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex, MutexGuard, PoisonError, TryLockError,
+    },
     thread::{self, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
+use thread_pool::{Slots, ThreadPool};
 
+pub mod auth;
+pub mod cli;
+pub mod config;
 pub mod hostable_servers;
 pub mod http;
+pub mod thread_pool;
+
+/// Idle time used when [`WebServer::set_idle_shutdown`] isn't called
+const DEFAULT_IDLE_SHUTDOWN: Duration = Duration::from_mins(30);
+/// Worker threads used when [`WebServer::set_worker_pool`] isn't called
+const DEFAULT_WORKERS: usize = 8;
+/// Queued connections allowed when [`WebServer::set_worker_pool`] isn't called
+const DEFAULT_QUEUE_CAPACITY: usize = 64;
+/// How long a client may take to send a chunk of its request
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often console WebSockets look for new output
+const CONSOLE_POLL: Duration = Duration::from_millis(250);
+/// How often console WebSockets ping their client, a dead one is noticed on the next write
+const CONSOLE_PING: Duration = Duration::from_secs(30);
+/// Console WebSockets open at once, every one has its own thread
+const MAX_CONSOLE_SOCKETS: usize = 32;
+/// How often the statuses behind `/events` are updated
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+/// Longest silence on `/events` before a heartbeat is sent
+const EVENTS_HEARTBEAT: Duration = Duration::from_secs(15);
+/// Status events kept for clients that reconnect with a `Last-Event-ID`
+const EVENTS_KEPT: usize = 256;
+/// `/events` streams open at once, every one has its own thread
+const MAX_EVENT_STREAMS: usize = 32;
+
+/// A [`HostableServer`] shared between the worker threads
+///
+/// Every server has its own lock, so a slow `stop` on one server
+/// doesn't hold up requests for any other
+struct HostedServer {
+    /// Cached [`HostableServer::get_path`], routing doesn't need the lock
+    path: String,
+    /// The server itself
+    server: Mutex<Box<dyn HostableServer>>,
+    /// Cached [`HostableServer::cancel_token`], cancelling can't wait for the lock
+    cancel: Option<CancelToken>,
+    /// Crash history and restarts, only locked after `server`
+    watchdog: Mutex<Watchdog>,
+    /// Output shared by the console WebSockets, only locked before `server`
+    console: Mutex<ConsoleFeed>,
+    /// Data of the last status event, sent to clients that join `/events`
+    status: Mutex<Option<String>>,
+}
+impl HostedServer {
+    /// Locks the server, a panic in another worker doesn't make it unusable
+    fn lock(&self) -> MutexGuard<'_, Box<dyn HostableServer>> {
+        self.server.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the watchdog, see [`HostedServer::lock`]
+    fn lock_watchdog(&self) -> MutexGuard<'_, Watchdog> {
+        self.watchdog.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the console feed, see [`HostedServer::lock`]
+    fn lock_console(&self) -> MutexGuard<'_, ConsoleFeed> {
+        self.console.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Updates the console feed unless that happened within [`CONSOLE_POLL`]
+    ///
+    /// A server busy with a slow command is skipped, the feed just stays as it is
+    fn refresh_console(&self) {
+        let mut feed = self.lock_console();
+        if !feed.is_due(CONSOLE_POLL) {
+            return;
+        }
+        let server = match self.server.try_lock() {
+            Ok(server) => server,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        feed.update(server.console_output());
+        drop(server);
+        drop(feed);
+    }
+
+    /// Lets the watchdog check on the server every [`Watchdog::interval`], never returns
+    fn watch(&self) {
+        loop {
+            let interval = self.lock_watchdog().interval();
+            thread::sleep(interval);
+            let mut server = self.lock();
+            let restart = self.lock_watchdog().check(&mut **server);
+            drop(server);
+            if restart {
+                self.restart_crashed();
+            }
+        }
+    }
+
+    /// Restarts the server after a crash, see [`Watchdog::check`]
+    ///
+    /// The server is unlocked between stopping and starting and the watchdog is
+    /// only locked if the start fails, so `/status` or `/crashes` don't wait for all of it
+    fn restart_crashed(&self) {
+        // a process that is still there, like an unhealthy one, has to go first
+        let stopped = self.lock().stop();
+        if let Err(e) = stopped {
+            eprintln!(
+                "\x1b[31m{}: stopping before the restart failed: {e}\x1b[39m",
+                self.path
+            );
+        }
+        let mut server = self.lock();
+        // somebody may have started it in the meantime
+        let started = server.lifecycle_mut().is_some_and(|lifecycle| {
+            !matches!(
+                lifecycle.state(),
+                State::Stopped | State::Crashed | State::Unknown
+            )
+        });
+        if started {
+            return;
+        }
+        if let Err(e) = server.start() {
+            self.lock_watchdog().restart_failed(&mut **server, &e);
+        }
+    }
+}
+
+/// Message of a console WebSocket, see [`WebServer::console_socket`]
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ConsoleEvent<'a> {
+    /// A line the server printed
+    Line {
+        /// Number of the line, counted since the web server started
+        number: u64,
+        /// The line itself
+        text: &'a str,
+    },
+    /// Something went wrong
+    Error {
+        /// What went wrong
+        message: &'a str,
+    },
+}
+
+/// Wraps a [`ConsoleEvent`] in a text frame
+fn console_event(event: &ConsoleEvent) -> Frame {
+    Frame::Text(serde_json::to_string(event).unwrap_or_default())
+}
 
 /// Simple Web interface for the [`HostableServer`] trait
 pub struct WebServer {
-    /// hostable_servers
-    hostable_servers: Vec<Box<dyn HostableServer>>,
+    /// `hostable_servers`
+    hostable_servers: Vec<HostedServer>,
+    /// Decides who may use the POST routes, nobody if `None`
+    auth: Option<Auth>,
+    /// Files served by `/`, `/file/<path>` and `/<server>/update.js`
+    static_files: StaticFiles,
+    /// `ip:port` addresses to listen on
+    listen: Vec<String>,
+    /// Idle time after which the machine is shut down
+    idle_shutdown: Option<Duration>,
+    /// Number of threads handling connections
+    workers: usize,
+    /// Number of connections that may wait for a free worker
+    queue_capacity: usize,
+    /// Status changes streamed by `/events`
+    events: EventLog,
+    /// Threads of the open console WebSockets, see [`MAX_CONSOLE_SOCKETS`]
+    console_sockets: Slots,
+    /// Threads of the open `/events` streams, see [`MAX_EVENT_STREAMS`]
+    event_streams: Slots,
+}
+
+impl Default for WebServer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WebServer {
     /// Returns a new instance of `WebServer`
     #[must_use]
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             hostable_servers: Vec::new(),
+            auth: None,
+            static_files: StaticFiles::new("static"),
+            listen: Vec::new(),
+            idle_shutdown: Some(DEFAULT_IDLE_SHUTDOWN),
+            workers: DEFAULT_WORKERS,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            events: EventLog::new(EVENTS_KEPT),
+            console_sockets: Slots::new(MAX_CONSOLE_SOCKETS),
+            event_streams: Slots::new(MAX_EVENT_STREAMS),
         }
     }
 
@@ -56,17 +251,103 @@ impl WebServer {
     /// web_server.start("192.168.11.69", 31415);
     /// ```
     pub fn add_hostable_server(&mut self, server: Box<dyn HostableServer>) {
-        self.hostable_servers.push(server);
+        self.hostable_servers.push(HostedServer {
+            path: server.get_path().to_owned(),
+            cancel: server.cancel_token(),
+            watchdog: Mutex::new(Watchdog::new(server.watchdog())),
+            console: Mutex::new(ConsoleFeed::new()),
+            status: Mutex::new(None),
+            server: Mutex::new(server),
+        });
+    }
+
+    /// Sets the number of worker threads and how many connections may wait for one
+    ///
+    /// Connections that arrive while the queue is full are answered with a 503
+    ///
+    /// # Panics
+    /// Panics if `workers` is zero
+    pub fn set_worker_pool(&mut self, workers: usize, queue_capacity: usize) {
+        assert!(workers > 0, "A WebServer needs at least one worker");
+        self.workers = workers;
+        self.queue_capacity = queue_capacity;
+    }
+
+    /// Sets the directory `hello.html`, `/file/<path>` and every `<server>/update.js` are served from
+    ///
+    /// Defaults to `static` in the working directory. Everything in it can be downloaded
+    /// without logging in
+    pub fn set_static_root(&mut self, root: impl Into<PathBuf>) {
+        self.static_files = StaticFiles::new(root);
+    }
+
+    /// Finds the server whose path is the `first_domain` of a link
+    fn find_server(&self, first_domain: &str) -> Option<&HostedServer> {
+        self.hostable_servers
+            .iter()
+            .find(|s| s.path == first_domain)
+    }
+
+    /// Builds a `WebServer` from the config file at `path`
+    ///
+    /// See [`config`] for the format of the file
+    ///
+    /// # Errors
+    /// Returns a [`ConfigError`] pointing at the part of the file that is wrong
+    ///
+    /// # Example
+    /// ```no_run
+    /// let web_server = web_server::WebServer::from_config("web_server.toml").unwrap();
+    ///
+    /// web_server.run();
+    /// ```
+    pub fn from_config(path: impl AsRef<std::path::Path>) -> Result<Self, ConfigError> {
+        Ok(Self::from_parsed_config(&Config::load(path)?))
+    }
+
+    /// Builds a `WebServer` from an already loaded [`Config`]
+    #[must_use]
+    pub fn from_parsed_config(config: &Config) -> Self {
+        let mut web_server = Self::new();
+        for address in &config.listen {
+            web_server.add_listen_address(address);
+        }
+        web_server.set_static_root(&config.static_root);
+        web_server.set_idle_shutdown(config.idle.timeout());
+        web_server.set_worker_pool(config.workers.threads, config.workers.queue);
+        if let Some(auth) = &config.auth {
+            web_server.set_auth(auth.build());
+        }
+        for server in config.build_servers() {
+            web_server.add_hostable_server(server);
+        }
+        web_server
+    }
+
+    /// Sets who may use the POST routes
+    ///
+    /// Without an [`Auth`] every POST but `/login` and `/logout` is refused with a 403
+    pub fn set_auth(&mut self, auth: Auth) {
+        self.auth = Some(auth);
+    }
+
+    /// Adds an `ip:port` address to listen on, see [`WebServer::run`]
+    pub fn add_listen_address(&mut self, address: impl Into<String>) {
+        self.listen.push(address.into());
+    }
+
+    /// Sets how long the server may go without requests before the machine is shut down
+    ///
+    /// `None` keeps the machine running, the default is 30 minutes
+    pub const fn set_idle_shutdown(&mut self, allowed_idle_time: Option<Duration>) {
+        self.idle_shutdown = allowed_idle_time;
     }
 
     /// Returns a tcp listener with the set IP Adress and port
     ///
     /// # Pancis
     /// Panics if the `port` is used or blocker
-    fn get_tcp_listener(ip: &'static str, port: usize) -> TcpListener {
-        // Ip adress setup
-        let ip_and_port = format!("{ip}:{port}");
-
+    fn get_tcp_listener(ip_and_port: &str) -> TcpListener {
         println!("Http://{ip_and_port}/");
 
         TcpListener::bind(ip_and_port).expect("Problems with the IP and port")
@@ -87,25 +368,56 @@ impl WebServer {
     ///
     /// web_server.add_hostable_server(Box::new(GeneralBashServer::new("minecraft")));
     /// web_server.add_hostable_server(Box::new(GeneralBashServer::new("arma")));
+    /// web_server.set_worker_pool(4, 32);
     ///
     /// web_server.start("192.168.11.69", 31415);
     /// ```
-    pub fn start(&mut self, ip: &'static str, port: usize) -> ! {
+    pub fn start(mut self, ip: &str, port: usize) -> ! {
+        self.add_listen_address(format!("{ip}:{port}"));
+        self.run()
+    }
+
+    /// Listens on every address added with [`WebServer::add_listen_address`]
+    ///
+    /// # Panics
+    /// Panics if there is no address or one of them can't be bound
+    pub fn run(self) -> ! {
         // timeout setup
-        let (tx, timer_thread) = Self::create_timeout_thread(Duration::from_secs(60 * 30));
+        let (tx, timer_thread) = Self::create_timeout_thread(self.idle_shutdown);
 
         // TCP setup
+        assert!(!self.listen.is_empty(), "No address to listen on");
+        let mut listeners: Vec<TcpListener> = self
+            .listen
+            .iter()
+            .map(|address| Self::get_tcp_listener(address))
+            .collect();
 
-        let listener = Self::get_tcp_listener(ip, port);
+        // worker setup
+        let workers = self.workers;
+        let queue_capacity = self.queue_capacity;
+        let web_server = Arc::new(self);
+        for i in 0..web_server.hostable_servers.len() {
+            let web_server = Arc::clone(&web_server);
+            thread::spawn(move || web_server.hostable_servers[i].watch());
+        }
+        let status_publisher = Arc::clone(&web_server);
+        thread::spawn(move || status_publisher.publish_statuses());
+        let pool = Arc::new(ThreadPool::new(workers, queue_capacity, move |stream| {
+            web_server
+                .handle_connection(stream, &tx)
+                .unwrap_or_else(|e| {
+                    println!("Connection Failed: {e}");
+                });
+        }));
 
-        // accepting connections
-        for stream in listener.incoming() {
-            // listener.incoming() never returns None, read the docs for more info
-            let stream = stream.expect("Never happens");
-            self.handle_connection(stream, &tx).unwrap_or_else(|e| {
-                println!("Connection Failed: {e}");
-            });
+        // every additional address gets its own accepting thread
+        let main_listener = listeners.remove(0);
+        for listener in listeners {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || Self::accept_connections(&listener, &pool));
         }
+        Self::accept_connections(&main_listener, &pool);
 
         // IDK if I need this, but I always wanted to use drop somewhere
         // and when I put this here I know timer_thread will live long enough
@@ -117,143 +429,1112 @@ impl WebServer {
         }
     }
 
+    /// Hands every connection of `listener` to the `pool`, answers 503 if it is full
+    fn accept_connections(listener: &TcpListener, pool: &ThreadPool<TcpStream>) {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    println!("Connection Failed: {e}");
+                    continue;
+                }
+            };
+            if let Err(mut stream) = pool.try_execute(stream) {
+                println!("\x1b[31mAll workers are busy, refusing a connection\x1b[39m");
+                let _ = Message::new(
+                    Variant::ServiceUnavailable,
+                    Content::Text("The server is busy, try again later".to_owned()),
+                )
+                .with_header("Retry-After", "1")
+                .with_header("Connection", "close")
+                .write_to(&mut stream);
+            }
+        }
+    }
+
     /// Handles the TCP connection
     ///
     /// Prints updates to stdout or stderr during the whole operation
-    fn handle_connection(&mut self, mut stream: TcpStream, tx: &Sender<()>) -> std::io::Result<()> {
+    fn handle_connection(
+        self: &Arc<Self>,
+        mut stream: TcpStream,
+        tx: &Sender<()>,
+    ) -> std::io::Result<()> {
         let _ = tx.send(());
 
-        let mut buffer = vec![0; 1024];
+        // a client that stops sending mid request shouldn't hang the server
+        stream.set_read_timeout(Some(REQUEST_READ_TIMEOUT))?;
 
-        if let Err(e) = stream.read(&mut buffer) {
-            println!("Error with reading the stream: {e}");
-            return Err(e);
-        };
+        println!("\x1b[35m========================================================\x1b[39m");
+        println!("\x1b[36mTime: {}\x1b[39m", chrono::Local::now());
 
-        let buffer_str = match std::str::from_utf8(&buffer) {
-            Ok(ok) => ok.to_string(),
-            Err(e) => e.to_string(),
+        let htttp_response: Message = match Request::read_from(&mut stream, Limits::default()) {
+            Ok(mut request) => {
+                request.peer = stream.peer_addr().ok();
+                println!(
+                    "\x1b[36mPeer: '{:?}', Method: '{}', Link: '{}'\x1b[39m",
+                    stream.peer_addr(),
+                    request.method,
+                    request.path,
+                );
+                let message = if websocket::is_upgrade(&request) {
+                    match self.upgrade(&stream, &request, tx) {
+                        Ok(()) => return Ok(()),
+                        Err(message) => message,
+                    }
+                } else if request.method == Method::Get && request.path == "/events" {
+                    match self.subscribe(&stream, &request, tx) {
+                        Ok(()) => return Ok(()),
+                        Err(message) => message,
+                    }
+                } else {
+                    self.parse_http_request(&request)
+                };
+                self.clear_stale_session(&request, message)
+            }
+            Err(ParseError::Io(e)) => {
+                println!("Error with reading the stream: {e}");
+                return Err(e);
+            }
+            Err(ParseError::ConnectionClosed) => {
+                println!(
+                    "\x1b[36mPeer: '{:?}' closed the connection\x1b[39m",
+                    stream.peer_addr()
+                );
+                return Ok(());
+            }
+            Err(e) => {
+                println!("\x1b[36mPeer: '{:?}', {e}\x1b[39m", stream.peer_addr());
+                let variant = match e {
+                    ParseError::BodyTooLarge => Variant::PayloadTooLarge,
+                    ParseError::HeadTooLarge => Variant::RequestHeaderFieldsTooLarge,
+                    _ => Variant::BadRequest,
+                };
+                Message::new(variant, Content::Text(e.to_string()))
+            }
         };
-        let mut buffer_iter = buffer_str.split(' ');
 
-        let method = buffer_iter.next().unwrap_or_default();
-        let link = buffer_iter.next().unwrap_or_default();
+        if htttp_response.variant.is_success() {
+            println!("\x1b[32mᓚᘏᗢ\r\n{htttp_response}\r\nᓚᘏᗢ\x1b[39m");
+        } else if htttp_response.variant.code() >= 400 {
+            eprintln!("\x1b[31mServer Error: \r\n{htttp_response}\x1b[39m");
+        } else {
+            println!("\x1b[34mUnsure:\r\n{htttp_response}\r\nᓚᘏᗢ\x1b[39m");
+        }
+
+        htttp_response
+            .with_header("Connection", "close")
+            .write_to(&mut stream)?;
+        stream.flush()?;
 
         println!("\x1b[35m========================================================\x1b[39m");
-        println!("\x1b[36mTime: {}\x1b[39m", chrono::Local::now());
+
+        Ok(())
+    }
+
+    /// Switches `GET /<server>/console` to a WebSocket, see [`WebServer::console_socket`]
+    ///
+    /// # Errors
+    /// Returns the response for requests that can't be upgraded, a 503 if
+    /// [`MAX_CONSOLE_SOCKETS`] are open already
+    fn upgrade(
+        self: &Arc<Self>,
+        stream: &TcpStream,
+        request: &Request,
+        tx: &Sender<()>,
+    ) -> Result<(), Message> {
+        let segments: Vec<&str> = request.segments().collect();
+        let index = match segments[..] {
+            [server, "console"] => self.hostable_servers.iter().position(|s| s.path == server),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            Message::new(
+                Variant::NotFound,
+                Content::Text(format!("No WebSocket at {}", request.path)),
+            )
+        })?;
+        let hostable_server = &self.hostable_servers[index];
+        let identity = self.authenticate(request);
+        // like every GET, everybody may watch without Auth
+        if self.auth.is_some() {
+            let identity = identity.as_ref().map_err(Self::auth_error)?;
+            self.authorize(identity, Permission::View, Some(&hostable_server.path))?;
+        }
+        // writing needs what `POST /<server>/console` needs, so nobody may without Auth
+        let sender = match identity {
+            Ok(identity)
+                if self.auth.as_ref().is_some_and(|auth| {
+                    auth.allows(&identity, Permission::Console, Some(&hostable_server.path))
+                }) =>
+            {
+                Ok(identity)
+            }
+            Ok(identity) => Err(format!(
+                "{} may not {} {}",
+                identity.user,
+                Permission::Console,
+                hostable_server.path
+            )),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let handshake = websocket::handshake(request)?;
+        let slot = self.console_sockets.try_take().ok_or_else(|| {
+            Message::new(
+                Variant::ServiceUnavailable,
+                Content::Text("Too many consoles are open, try again later".to_owned()),
+            )
+            .with_header("Retry-After", "5")
+        })?;
+        let stream = stream
+            .try_clone()
+            .and_then(|stream| {
+                handshake.write_to(&mut &stream)?;
+                stream.set_read_timeout(None)?;
+                Ok(stream)
+            })
+            .map_err(|e| Message::internal_server_error(e.to_string()))?;
         println!(
-            "\x1b[36mPeer: '{:?}', Method: '{}', Link: '{}'\x1b[39m",
+            "\x1b[36mPeer: '{:?}' is watching the console of {}\x1b[39m",
             stream.peer_addr(),
-            method,
-            link,
+            hostable_server.path
         );
 
-        let htttp_response: Message = self.parse_http_request(method, link);
+        // a watcher can stay for hours, it doesn't get to block a worker
+        let web_server = Arc::clone(self);
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let hostable_server = &web_server.hostable_servers[index];
+            Self::console_socket(hostable_server, &stream, sender.as_ref(), &tx);
+            drop(slot);
+            println!(
+                "\x1b[36mPeer: '{:?}' stopped watching the console of {}\x1b[39m",
+                stream.peer_addr(),
+                hostable_server.path
+            );
+        });
+        Ok(())
+    }
 
-        // I know this sucks but I can't convince the borrow checker to work with me :(
-        if let Content::RawBytes(bytes) = htttp_response.content {
-            println!("ᓚᘏᗢ\r\nfavicon.ico \r\nᓚᘏᗢ");
-            let http_header = b"HTTP/1.1 200 OK\r\n\r\n";
-            let body: &[u8] = &bytes;
-            // magic
-            let vec: Vec<u8> = http_header.iter().copied().chain(body.to_owned()).collect();
+    /// Streams the console of `hostable_server` over the WebSocket `stream`
+    ///
+    /// The client first gets the lines kept in the [`ConsoleFeed`], then every new one
+    /// as `{"type": "line", "number": 7, "text": "..."}`. Text messages from the client
+    /// are written to the console if there is a `sender`, problems and the reason there
+    /// isn't one are answered with `{"type": "error", "message": "..."}`
+    fn console_socket(
+        hostable_server: &HostedServer,
+        stream: &TcpStream,
+        sender: Result<&Identity, &String>,
+        tx: &Sender<()>,
+    ) {
+        let writer = Mutex::new(stream);
+        let send = |frame: &Frame| {
+            let mut stream = writer.lock().unwrap_or_else(PoisonError::into_inner);
+            websocket::write_frame(&mut *stream, frame)
+        };
 
-            stream.write_all(&vec)?;
-        } else {
-            let response_str = htttp_response.to_string();
+        thread::scope(|scope| {
+            let reader = scope.spawn(|| {
+                let mut frames = FrameReader::new(stream);
+                loop {
+                    let reply = match frames.read() {
+                        Ok(Frame::Text(line)) => {
+                            Self::console_input(hostable_server, sender, &line)
+                                .err()
+                                .map(|e| console_event(&ConsoleEvent::Error { message: &e }))
+                        }
+                        Ok(Frame::Ping(bytes)) => Some(Frame::Pong(bytes)),
+                        Ok(Frame::Binary(_)) => Some(console_event(&ConsoleEvent::Error {
+                            message: "Expected a line of text",
+                        })),
+                        Ok(Frame::Pong(_)) => None,
+                        Ok(Frame::Close(_)) => {
+                            let _ = send(&Frame::Close(None));
+                            break;
+                        }
+                        Err(_) => break,
+                    };
+                    if reply.is_some_and(|reply| send(&reply).is_err()) {
+                        break;
+                    }
+                }
+                let _ = stream.shutdown(Shutdown::Both);
+            });
 
-            // This is neccesary because the File is loaded in the .to_string() method on the http_response
-            if &response_str[0..12] == "HTTP/1.1 200" {
-                println!("\x1b[32mᓚᘏᗢ\r\n{response_str}\r\nᓚᘏᗢ\x1b[39m");
-            } else if &response_str[0..12] == "HTTP/1.1 404" {
-                eprintln!("\x1b[31mServer Error: \r\n{response_str}\x1b[39m");
-            } else {
-                println!("\x1b[34mUnsure:\r\n{response_str}\r\nᓚᘏᗢ\x1b[39m");
+            let mut next = hostable_server.lock_console().first();
+            let mut error = None;
+            let mut pinged = Instant::now();
+            while !reader.is_finished() {
+                hostable_server.refresh_console();
+                let (lines, new_error) = {
+                    let feed = hostable_server.lock_console();
+                    let (new_next, lines) = feed.since(next);
+                    next = new_next;
+                    (lines, feed.error().map(str::to_owned))
+                };
+
+                let first = next - lines.len() as u64;
+                let mut frames: Vec<Frame> = (first..)
+                    .zip(&lines)
+                    .map(|(number, text)| console_event(&ConsoleEvent::Line { number, text }))
+                    .collect();
+                if new_error != error {
+                    if let Some(message) = &new_error {
+                        frames.push(console_event(&ConsoleEvent::Error { message }));
+                    }
+                    error = new_error;
+                }
+                if pinged.elapsed() >= CONSOLE_PING {
+                    // somebody watching counts as activity for the idle shutdown
+                    let _ = tx.send(());
+                    frames.push(Frame::Ping(Vec::new()));
+                    pinged = Instant::now();
+                }
+                if frames.iter().any(|frame| send(frame).is_err()) {
+                    let _ = stream.shutdown(Shutdown::Both);
+                    break;
+                }
+                thread::sleep(CONSOLE_POLL);
             }
+        });
+    }
 
-            let response = response_str.as_bytes();
+    /// Updates the status of every server every [`STATUS_INTERVAL`], never returns
+    ///
+    /// A status event is published when the state, the players or the health of a server
+    /// changed, so every `/events` client shares the same update. Servers busy with a
+    /// slow command are skipped until they are done. Nothing is updated while nobody
+    /// follows `/events`, the first client gets every status again
+    fn publish_statuses(&self) {
+        let mut published: Vec<Option<serde_json::Value>> = vec![None; self.hostable_servers.len()];
+        loop {
+            if self.event_streams.taken() == 0 {
+                published.fill(None);
+                for hostable_server in &self.hostable_servers {
+                    // too old for the next client, it waits for the new one instead
+                    *hostable_server
+                        .status
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner) = None;
+                }
+                thread::sleep(STATUS_INTERVAL);
+                continue;
+            }
+            for (hostable_server, published) in self.hostable_servers.iter().zip(&mut published) {
+                let status = {
+                    let mut server = match hostable_server.server.try_lock() {
+                        Ok(server) => server,
+                        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+                        Err(TryLockError::WouldBlock) => continue,
+                    };
+                    // a failed update is already visible as the Unknown state
+                    let _ = server.update_status();
+                    server.to_json()
+                };
+                let Ok(status) =
+                    status.and_then(|status| serde_json::from_str::<serde_json::Value>(&status))
+                else {
+                    continue;
+                };
 
-            dbg!(&response_str);
+                let summary = serde_json::json!({
+                    "state": status.get("state"),
+                    "players": status.get("players"),
+                    "healthy": status.get("probes").and_then(serde_json::Value::as_array).map(
+                        |probes| probes.iter().all(|probe| probe["ok"] == true)
+                    ),
+                });
+                if published.as_ref() == Some(&summary) {
+                    continue;
+                }
+                *published = Some(summary);
+                let data = serde_json::json!({
+                    "server": hostable_server.path,
+                    "status": status,
+                })
+                .to_string();
+                *hostable_server
+                    .status
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Some(data.clone());
+                self.events.publish("status", &hostable_server.path, data);
+            }
+            thread::sleep(STATUS_INTERVAL);
+        }
+    }
 
-            stream.write_all(response)?;
-        };
+    /// Answers `GET /events` with a stream of status events, see [`WebServer::stream_events`]
+    ///
+    /// # Errors
+    /// Returns a 401 if there is [`Auth`] and the sender isn't logged in, a 503 if
+    /// [`MAX_EVENT_STREAMS`] are open already
+    fn subscribe(
+        self: &Arc<Self>,
+        stream: &TcpStream,
+        request: &Request,
+        tx: &Sender<()>,
+    ) -> Result<(), Message> {
+        let identity = self.authenticate(request);
+        if let (Some(_), Err(e)) = (&self.auth, &identity) {
+            return Err(Self::auth_error(e));
+        }
+        let visible: Vec<usize> = (0..self.hostable_servers.len())
+            .filter(|&i| self.may_view(&identity, &self.hostable_servers[i].path))
+            .collect();
+        let last_id = sse::last_event_id(request);
+        let slot = self.event_streams.try_take().ok_or_else(|| {
+            Message::new(
+                Variant::ServiceUnavailable,
+                Content::Text("Too many event streams are open, try again later".to_owned()),
+            )
+            .with_header("Retry-After", "5")
+        })?;
 
-        stream.flush()?;
+        let stream = stream
+            .try_clone()
+            .and_then(|mut stream| {
+                sse::write_head(&mut stream, Duration::from_secs(3))?;
+                stream.set_read_timeout(None)?;
+                Ok(stream)
+            })
+            .map_err(|e| Message::internal_server_error(e.to_string()))?;
+        println!(
+            "\x1b[36mPeer: '{:?}' is following the events\x1b[39m",
+            stream.peer_addr()
+        );
 
-        println!("\x1b[35m========================================================\x1b[39m");
+        // like the console, a dashboard stays open for hours
+        let web_server = Arc::clone(self);
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let _ = web_server.stream_events(&mut &stream, &visible, last_id, &tx);
+            drop(slot);
+            println!(
+                "\x1b[36mPeer: '{:?}' stopped following the events\x1b[39m",
+                stream.peer_addr()
+            );
+        });
+        Ok(())
+    }
+
+    /// Writes status events of the `visible` servers until the client goes away
+    ///
+    /// A client that reconnects with a `Last-Event-ID` gets the events it missed,
+    /// anybody else, or somebody who missed too many, the current status of every server
+    fn stream_events(
+        &self,
+        stream: &mut &TcpStream,
+        visible: &[usize],
+        last_id: Option<u64>,
+        tx: &Sender<()>,
+    ) -> std::io::Result<()> {
+        let is_visible = |event: &sse::Event| {
+            visible
+                .iter()
+                .any(|&i| self.hostable_servers[i].path == event.topic)
+        };
+        let mut events = last_id.and_then(|id| self.events.since(id));
+        let mut last_id = last_id.unwrap_or_default();
+        loop {
+            match events {
+                Some(events) if events.is_empty() => {
+                    // somebody looking at the dashboard counts as activity for the idle shutdown
+                    let _ = tx.send(());
+                    sse::write_heartbeat(stream)?;
+                }
+                Some(events) => {
+                    for event in events.iter().filter(|event| is_visible(event)) {
+                        event.write_to(stream)?;
+                    }
+                    last_id = events.last().map_or(last_id, |event| event.id);
+                }
+                None => {
+                    last_id = self.events.last_id();
+                    for &i in visible {
+                        let hostable_server = &self.hostable_servers[i];
+                        let status = hostable_server
+                            .status
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .clone();
+                        if let Some(data) = status {
+                            sse::Event {
+                                id: last_id,
+                                name: "status".to_owned(),
+                                data,
+                                topic: hostable_server.path.clone(),
+                            }
+                            .write_to(stream)?;
+                        }
+                    }
+                }
+            }
+            stream.flush()?;
+            events = self.events.wait(last_id, EVENTS_HEARTBEAT);
+        }
+    }
 
+    /// Writes a line a console WebSocket received to the console of `hostable_server`
+    ///
+    /// # Errors
+    /// Returns why the line wasn't written
+    fn console_input(
+        hostable_server: &HostedServer,
+        sender: Result<&Identity, &String>,
+        line: &str,
+    ) -> Result<(), String> {
+        let identity = sender.map_err(String::clone)?;
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.is_empty() || line.contains('\n') {
+            return Err("Expected a single line of text".to_owned());
+        }
+        hostable_server
+            .lock()
+            .send_console(line)
+            .map_err(|e| e.to_string())?;
+        println!(
+            "\x1b[36mUser: '{}' sent {line:?} to {}\x1b[39m",
+            identity.user, hostable_server.path
+        );
         Ok(())
     }
 
     /// Parses the http to the best of it's abilities
     ///
     /// # Errors
-    /// Can only process GET and POST methods
-    fn parse_http_request(&mut self, method: &str, link: &str) -> Message {
-        match method {
-            "GET" => self.parse_get(link),
-            "POST" => self.parse_post(link),
-            e => {
+    /// Can only process GET, POST, PUT and DELETE methods
+    fn parse_http_request(&self, request: &Request) -> Message {
+        match request.method {
+            Method::Get => self.parse_get(request),
+            Method::Post => self.parse_post(request),
+            Method::Put => self.parse_put(request),
+            Method::Delete => self.parse_delete(request),
+            ref e => {
                 println!("Method not available: {e}");
                 Message::new(
-                    Variant::NotFound,
+                    Variant::MethodNotAllowed,
                     Content::Text(format!("Unkown method: {e}")),
                 )
+                .with_header("Allow", "GET, POST, PUT, DELETE")
             }
         }
     }
 
     /// Parses a post method
-    fn parse_post(&mut self, link: &str) -> Message {
-        match link {
-            "/Shutdown" => Self::shutdown(),
+    ///
+    /// Everything but `/login` and `/logout` needs an authenticated user whose
+    /// roles allow the action, see [`auth::Roles`]
+    fn parse_post(&self, request: &Request) -> Message {
+        match request.path.as_str() {
+            "/login" => return self.login(request),
+            "/logout" => return self.logout(request),
+            _ => {}
+        }
+
+        let identity = match self.authenticate(request) {
+            Ok(identity) => identity,
+            Err(e) => return Self::auth_error(&e),
+        };
+        println!(
+            "\x1b[36mUser: '{}' ({:?})\x1b[39m",
+            identity.user, identity.scheme
+        );
+
+        match request.path.as_str() {
+            "/Shutdown" => match self.authorize(&identity, Permission::Power, None) {
+                Ok(()) => Self::shutdown(),
+                Err(message) => message,
+            },
             "/Ping" => Message::new(Variant::Ok, Content::Text("Ping succesfull".to_owned())),
             link => {
-                let mut link_split = link.split('/');
-                let _ = link_split.next(); // the link starts with '/'
+                let mut link_split = request.segments();
 
                 let first_domain = link_split.next().unwrap_or("Unavailabe");
+                let second_domain = link_split.next().unwrap_or("Unavaiable");
 
-                self.hostable_servers
-                    // Checks if any of the paths match the domain
-                    .iter_mut()
-                    .find(|s| s.get_path() == first_domain)
-                    .map_or_else(
-                        || {
-                            Message::new(
-                                Variant::NotFound,
-                                Content::Text(format!("Unkown POST link: {link}",)),
-                            )
-                        },
-                        |hostable_server| {
-                            let second_domain = link_split.next().unwrap_or("Unavaiable");
-
-                            match second_domain {
-                                "start" => match hostable_server.start() {
-                                    Ok(()) => Message::default(),
-                                    Err(e) => Message::new(
-                                        Variant::InternalServerError,
-                                        Content::Text(e.to_string()),
-                                    ),
-                                },
-                                "stop" => match hostable_server.stop() {
-                                    Ok(()) => Message::default(),
-                                    Err(e) => Message::new(
-                                        Variant::InternalServerError,
-                                        Content::Text(e.to_string()),
-                                    ),
-                                },
-                                e => {
-                                    println!("Link not accesible: {e}");
-                                    Message::new(
-                                        Variant::NotFound,
-                                        Content::Text(format!("Unkown POST link: {link}",)),
-                                    )
-                                }
+                // Checks if any of the paths match the domain
+                self.find_server(first_domain).map_or_else(
+                    || {
+                        Message::new(
+                            Variant::NotFound,
+                            Content::Text(format!("Unkown POST link: {link}")),
+                        )
+                    },
+                    |hostable_server| {
+                        self.post_to_server(&identity, hostable_server, second_domain, request)
+                    },
+                )
+            }
+        }
+    }
+
+    /// Parses a put method, only `PUT /<server>/properties` is one
+    ///
+    /// Needs an authenticated user with the `configure` permission
+    fn parse_put(&self, request: &Request) -> Message {
+        let identity = match self.authenticate(request) {
+            Ok(identity) => identity,
+            Err(e) => return Self::auth_error(&e),
+        };
+        let segments: Vec<&str> = request.segments().collect();
+        let hostable_server = match segments[..] {
+            [server, "properties"] => self.find_server(server),
+            _ => None,
+        };
+        let Some(hostable_server) = hostable_server else {
+            println!("Link not accesible: {}", request.path);
+            return Message::new(
+                Variant::NotFound,
+                Content::Text(format!("Unkown PUT link: {}", request.path)),
+            );
+        };
+        if let Err(message) = self.authorize(
+            &identity,
+            Permission::Configure,
+            Some(&hostable_server.path),
+        ) {
+            return message;
+        }
+        Self::set_properties(&identity, hostable_server, &request.body)
+    }
+
+    /// Parses a delete method, only `DELETE /<server>/<list>/<name>` is one
+    ///
+    /// Removes a player from the whitelist, the ops or the bans, or an address from the IP
+    /// bans. Needs an authenticated user with the `configure` permission
+    fn parse_delete(&self, request: &Request) -> Message {
+        let identity = match self.authenticate(request) {
+            Ok(identity) => identity,
+            Err(e) => return Self::auth_error(&e),
+        };
+        let segments: Vec<&str> = request.segments().collect();
+        let target = match segments[..] {
+            [server, list, name] => self
+                .find_server(server)
+                .zip(PlayerList::from_name(list))
+                .map(|(hostable_server, list)| (hostable_server, list, name)),
+            _ => None,
+        };
+        let Some((hostable_server, list, name)) = target else {
+            println!("Link not accesible: {}", request.path);
+            return Message::new(
+                Variant::NotFound,
+                Content::Text(format!("Unkown DELETE link: {}", request.path)),
+            );
+        };
+        if let Err(message) = self.authorize(
+            &identity,
+            Permission::Configure,
+            Some(&hostable_server.path),
+        ) {
+            return message;
+        }
+
+        let result = hostable_server.lock().remove_from_list(list, name);
+        match result {
+            Ok(answer) => {
+                println!(
+                    "\x1b[36mUser: '{}' removed {name} from the {} of {}\x1b[39m",
+                    identity.user,
+                    list.name(),
+                    hostable_server.path
+                );
+                Message::new(Variant::Ok, Content::Text(answer))
+            }
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `PUT /<server>/properties`, changes the keys of the JSON object in the body
+    ///
+    /// Numbers and booleans are taken as they are written, the answer says that a restart is needed
+    fn set_properties(identity: &Identity, hostable_server: &HostedServer, body: &[u8]) -> Message {
+        let changes: Option<BTreeMap<String, String>> =
+            serde_json::from_slice::<BTreeMap<String, serde_json::Value>>(body)
+                .ok()
+                .and_then(|changes| {
+                    changes
+                        .into_iter()
+                        .map(|(key, value)| match value {
+                            serde_json::Value::String(text) => Some((key, text)),
+                            serde_json::Value::Number(_) | serde_json::Value::Bool(_) => {
+                                Some((key, value.to_string()))
                             }
-                        },
-                    )
+                            _ => None,
+                        })
+                        .collect()
+                });
+        let Some(changes) = changes else {
+            return Message::new(
+                Variant::BadRequest,
+                Content::Text("Expected a JSON object of strings, numbers and booleans".to_owned()),
+            );
+        };
+
+        let keys = changes.keys().cloned().collect::<Vec<_>>().join(", ");
+        let result = hostable_server.lock().set_properties(changes);
+        match result {
+            Ok(()) => {
+                println!(
+                    "\x1b[36mUser: '{}' changed {keys} of {}\x1b[39m",
+                    identity.user, hostable_server.path
+                );
+                Message::new(
+                    Variant::Ok,
+                    Content::Text("Saved, restart the server to apply the changes".to_owned()),
+                )
+            }
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// Runs the `action` of a POST to `/<server>/<action>` if `identity` may
+    fn post_to_server(
+        &self,
+        identity: &Identity,
+        hostable_server: &HostedServer,
+        action: &str,
+        request: &Request,
+    ) -> Message {
+        if let Some(list) = PlayerList::from_name(action) {
+            if let Err(message) =
+                self.authorize(identity, Permission::Configure, Some(&hostable_server.path))
+            {
+                return message;
+            }
+            return Self::add_to_list(identity, hostable_server, list, &request.body);
+        }
+        let permission = match action {
+            "start" => Permission::Start,
+            "stop" | "cancel" => Permission::Stop,
+            "restart" => Permission::Restart,
+            "console" | "command" => Permission::Console,
+            "missions" | "mods" => Permission::Configure,
+            e => {
+                println!("Link not accesible: {e}");
+                return Message::new(
+                    Variant::NotFound,
+                    Content::Text(format!("Unkown POST link: {}", request.path)),
+                );
+            }
+        };
+        if let Err(message) = self.authorize(identity, permission, Some(&hostable_server.path)) {
+            return message;
+        }
+        match action {
+            "cancel" => return Self::cancel(hostable_server),
+            "console" => return Self::console(identity, hostable_server, &request.body),
+            "command" => return Self::command(identity, hostable_server, &request.body),
+            "missions" | "mods" => {
+                return Self::select(identity, hostable_server, action, &request.body)
+            }
+            _ => {}
+        }
+
+        let result = {
+            let mut hostable_server = hostable_server.lock();
+            match permission {
+                Permission::Start => hostable_server.start(),
+                Permission::Stop => hostable_server.stop(),
+                _ => hostable_server.restart(),
+            }
+        };
+        match result {
+            Ok(()) => Message::default(),
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `POST /<server>/command`, runs the admin command in the body and answers with its result
+    fn command(identity: &Identity, hostable_server: &HostedServer, body: &[u8]) -> Message {
+        let command = match from_utf8(body).map(str::trim) {
+            Ok(command) if !command.is_empty() && !command.contains('\n') => command,
+            _ => {
+                return Message::new(
+                    Variant::BadRequest,
+                    Content::Text("Expected a single command".to_owned()),
+                )
+            }
+        };
+
+        let answer = hostable_server.lock().command(command);
+        match answer {
+            Ok(answer) => {
+                println!(
+                    "\x1b[36mUser: '{}' ran {command:?} on {}\x1b[39m",
+                    identity.user, hostable_server.path
+                );
+                Message::new(Variant::Ok, Content::Text(answer))
+            }
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `POST /<server>/missions` or `/<server>/mods`, chooses the ones named in the JSON array in the body
+    fn select(
+        identity: &Identity,
+        hostable_server: &HostedServer,
+        list: &str,
+        body: &[u8],
+    ) -> Message {
+        let Ok(names) = serde_json::from_slice::<Vec<String>>(body) else {
+            return Message::new(
+                Variant::BadRequest,
+                Content::Text("Expected a JSON array of names".to_owned()),
+            );
+        };
+
+        let result = {
+            let mut hostable_server = hostable_server.lock();
+            if list == "missions" {
+                hostable_server.select_missions(names)
+            } else {
+                hostable_server.select_mods(names)
+            }
+        };
+        match result {
+            Ok(()) => {
+                println!(
+                    "\x1b[36mUser: '{}' chose the {list} of {}\x1b[39m",
+                    identity.user, hostable_server.path
+                );
+                Message::default()
+            }
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `POST /<server>/<list>`, adds the player or address in the JSON body to the list,
+    /// like `{"name": "Alex", "reason": "Griefing"}` for `/<server>/bans`
+    fn add_to_list(
+        identity: &Identity,
+        hostable_server: &HostedServer,
+        list: PlayerList,
+        body: &[u8],
+    ) -> Message {
+        let addition = match serde_json::from_slice::<ListAddition>(body) {
+            Ok(addition) => addition,
+            Err(e) => {
+                return Message::new(
+                    Variant::BadRequest,
+                    Content::Text(format!("Expected a JSON object with a name: {e}")),
+                )
+            }
+        };
+
+        let name = addition.name.clone();
+        let result = hostable_server.lock().add_to_list(list, addition);
+        match result {
+            Ok(answer) => {
+                println!(
+                    "\x1b[36mUser: '{}' added {name} to the {} of {}\x1b[39m",
+                    identity.user,
+                    list.name(),
+                    hostable_server.path
+                );
+                Message::new(Variant::Ok, Content::Text(answer))
+            }
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `GET /<server>/<list>`, the entries of the whitelist, the ops or the bans
+    ///
+    /// Needs the `configure` permission, the bans hold the addresses of players
+    fn player_list(
+        &self,
+        request: &Request,
+        hostable_server: &HostedServer,
+        list: PlayerList,
+    ) -> Message {
+        if let Err(message) =
+            self.authorize_request(request, Permission::Configure, &hostable_server.path)
+        {
+            return message;
+        }
+        let entries = hostable_server.lock().player_list(list);
+        match entries.map(|entries| serde_json::to_string(&entries)) {
+            Ok(Ok(ok)) => Message::new(Variant::Ok, Content::Struct(ok)),
+            Ok(Err(e)) => Message::internal_server_error(e.to_string()),
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `GET /<server>/missions` or `/<server>/mods`, what can be chosen and what is
+    fn selection(&self, request: &Request, hostable_server: &HostedServer, list: &str) -> Message {
+        if let Err(message) = self.authorize_view(request, &hostable_server.path) {
+            return message;
+        }
+        let selection = {
+            let hostable_server = hostable_server.lock();
+            if list == "missions" {
+                hostable_server.missions()
+            } else {
+                hostable_server.mods()
+            }
+        };
+        match selection.map(|selection| serde_json::to_string(&selection)) {
+            Ok(Ok(ok)) => Message::new(Variant::Ok, Content::Struct(ok)),
+            Ok(Err(e)) => Message::internal_server_error(e.to_string()),
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `GET /<server>/properties`, the settings of the game as a JSON object
+    ///
+    /// Needs the `configure` permission like changing them, they say a lot about the machine
+    fn properties(&self, request: &Request, hostable_server: &HostedServer) -> Message {
+        if let Err(message) =
+            self.authorize_request(request, Permission::Configure, &hostable_server.path)
+        {
+            return message;
+        }
+        let properties = hostable_server.lock().properties();
+        match properties.map(|properties| serde_json::to_string(&properties)) {
+            Ok(Ok(ok)) => Message::new(Variant::Ok, Content::Struct(ok)),
+            Ok(Err(e)) => Message::internal_server_error(e.to_string()),
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// `POST /<server>/cancel`, kills the script the server is running right now
+    fn cancel(hostable_server: &HostedServer) -> Message {
+        match &hostable_server.cancel {
+            Some(cancel) if cancel.cancel() => {
+                println!(
+                    "\x1b[36mCancelled the command of {}\x1b[39m",
+                    hostable_server.path
+                );
+                Message::new(
+                    Variant::Ok,
+                    Content::Text(format!(
+                        "Cancelling the command of {}",
+                        hostable_server.path
+                    )),
+                )
+            }
+            Some(_) => Message::new(
+                Variant::Conflict,
+                Content::Text(format!("{} isn't running a command", hostable_server.path)),
+            ),
+            None => Message::new(
+                Variant::NotFound,
+                Content::Text(format!(
+                    "{} doesn't support cancelling",
+                    hostable_server.path
+                )),
+            ),
+        }
+    }
+
+    /// `POST /<server>/console`, writes the line in the body to the console of the server
+    ///
+    /// Answers with the last lines the server printed, an empty body only reads them
+    fn console(identity: &Identity, hostable_server: &HostedServer, body: &[u8]) -> Message {
+        let line = match from_utf8(body) {
+            Ok(line) if !line.trim_end_matches(['\r', '\n']).contains('\n') => {
+                line.trim_end_matches(['\r', '\n'])
+            }
+            _ => {
+                return Message::new(
+                    Variant::BadRequest,
+                    Content::Text("Expected a single line of text".to_owned()),
+                )
+            }
+        };
+
+        let output = {
+            let mut hostable_server = hostable_server.lock();
+            let sent = if line.is_empty() {
+                Ok(())
+            } else {
+                hostable_server.send_console(line)
+            };
+            sent.and_then(|()| hostable_server.console_output())
+        };
+        match output {
+            Ok(output) => {
+                if !line.is_empty() {
+                    println!(
+                        "\x1b[36mUser: '{}' sent {line:?} to {}\x1b[39m",
+                        identity.user, hostable_server.path
+                    );
+                }
+                Message::new(
+                    Variant::Ok,
+                    Content::Struct(serde_json::to_string(&output).unwrap_or_default()),
+                )
+            }
+            Err(e) => Self::command_failure(&e),
+        }
+    }
+
+    /// 500, 409 for the wrong state, 404 if unsupported, 400 if rejected or 504 for timeouts, with `{"message": ..., "failure": ...}` so the panel can show why a command failed
+    fn command_failure(e: &CommandFailure) -> Message {
+        eprintln!("\x1b[31m{e}\x1b[39m");
+        let variant = match e {
+            CommandFailure::TimedOut(_) => Variant::GatewayTimeout,
+            CommandFailure::InvalidTransition(_) | CommandFailure::NotRunning { .. } => {
+                Variant::Conflict
+            }
+            CommandFailure::Unsupported { .. } => Variant::NotFound,
+            CommandFailure::Rejected { .. } => Variant::BadRequest,
+            CommandFailure::Remote { .. } => Variant::BadGateway,
+            _ => Variant::InternalServerError,
+        };
+        let body = serde_json::json!({ "message": e.to_string(), "failure": e });
+        Message::new(variant, Content::Struct(body.to_string()))
+    }
+
+    /// Checks that `identity` may do `permission` on `server_path`, the machine itself if `None`
+    ///
+    /// # Errors
+    /// Returns a 403 if the roles of the user don't allow it
+    fn authorize(
+        &self,
+        identity: &Identity,
+        permission: Permission,
+        server_path: Option<&str>,
+    ) -> Result<(), Message> {
+        if self
+            .auth
+            .as_ref()
+            .is_some_and(|auth| auth.allows(identity, permission, server_path))
+        {
+            return Ok(());
+        }
+        println!(
+            "\x1b[36mUser: '{}' may not {permission} {}\x1b[39m",
+            identity.user,
+            server_path.unwrap_or("the machine")
+        );
+        Err(Message::new(
+            Variant::Forbidden,
+            Content::Text(format!(
+                "{} may not {permission} {}",
+                identity.user,
+                server_path.unwrap_or("the machine")
+            )),
+        ))
+    }
+
+    /// Checks that the sender of `request` may view `server_path`
+    ///
+    /// Without [`Auth`] everybody may look, only changing things is refused
+    fn authorize_view(&self, request: &Request, server_path: &str) -> Result<(), Message> {
+        if self.auth.is_none() {
+            return Ok(());
+        }
+        let identity = self
+            .authenticate(request)
+            .map_err(|e| Self::auth_error(&e))?;
+        self.authorize(&identity, Permission::View, Some(server_path))
+    }
+
+    /// Checks that the sender of `request` may `permission` on `server_path`
+    ///
+    /// Unlike [`WebServer::authorize_view`] nobody may without [`Auth`]
+    fn authorize_request(
+        &self,
+        request: &Request,
+        permission: Permission,
+        server_path: &str,
+    ) -> Result<(), Message> {
+        let identity = self
+            .authenticate(request)
+            .map_err(|e| Self::auth_error(&e))?;
+        self.authorize(&identity, permission, Some(server_path))
+    }
+
+    /// Returns true if the sender of `request` may see `server_path` in `/available-servers`
+    fn may_view(&self, identity: &Result<Identity, AuthError>, server_path: &str) -> bool {
+        match (&self.auth, identity) {
+            (None, _) => true,
+            (Some(auth), Ok(identity)) => {
+                auth.allows(identity, Permission::View, Some(server_path))
+            }
+            (Some(_), Err(_)) => false,
+        }
+    }
+
+    /// Returns who sent `request`
+    fn authenticate(&self, request: &Request) -> Result<Identity, AuthError> {
+        self.auth
+            .as_ref()
+            .ok_or(AuthError::NotConfigured)?
+            .authenticate(request)
+    }
+
+    /// Removes the session cookie of `request` in `message` if the session is gone, unless
+    /// `message` sets a cookie itself
+    fn clear_stale_session(&self, request: &Request, message: Message) -> Message {
+        let stale = self
+            .auth
+            .as_ref()
+            .and_then(|auth| auth.stale_cookie(request));
+        match stale {
+            Some(cookie) if message.headers.get("Set-Cookie").is_none() => {
+                message.with_header("Set-Cookie", &cookie)
             }
+            _ => message,
+        }
+    }
+
+    /// Turns an [`AuthError`] into a 401, 403 or 429
+    fn auth_error(e: &AuthError) -> Message {
+        let variant = match e {
+            AuthError::Missing | AuthError::Invalid => Variant::Unauthorized,
+            AuthError::NotConfigured => Variant::Forbidden,
+            AuthError::RateLimited => Variant::TooManyRequests,
+        };
+        let message = Message::new(variant, Content::Text(e.to_string()));
+        match e {
+            // Bearer, a Basic challenge would make browsers show their own login popup
+            AuthError::Missing | AuthError::Invalid => {
+                message.with_header("WWW-Authenticate", "Bearer realm=\"web_server\"")
+            }
+            AuthError::RateLimited => message.with_header("Retry-After", "300"),
+            AuthError::NotConfigured => message,
+        }
+    }
+
+    /// `POST /login` with a `{"username": "...", "password": "..."}` body, sets the session cookie
+    fn login(&self, request: &Request) -> Message {
+        /// Body of a login request
+        #[derive(serde::Deserialize)]
+        struct Login {
+            /// User name
+            username: String,
+            /// Plain text password
+            password: String,
+        }
+
+        let Some(auth) = &self.auth else {
+            return Self::auth_error(&AuthError::NotConfigured);
+        };
+        let Ok(login) = serde_json::from_slice::<Login>(&request.body) else {
+            return Message::new(
+                Variant::BadRequest,
+                Content::Text("Expected {\"username\": ..., \"password\": ...}".to_owned()),
+            );
+        };
+        match auth.login(request, &login.username, &login.password) {
+            Ok(cookie) => {
+                println!("\x1b[36mUser: '{}' logged in\x1b[39m", login.username);
+                Message::new(Variant::NoContent, Content::Empty).with_header("Set-Cookie", &cookie)
+            }
+            Err(e) => Self::auth_error(&e),
+        }
+    }
+
+    /// `POST /logout`, ends the session and removes the cookie
+    fn logout(&self, request: &Request) -> Message {
+        let message = Message::new(Variant::NoContent, Content::Empty);
+        match &self.auth {
+            Some(auth) => message.with_header("Set-Cookie", &auth.logout(request)),
+            None => message,
         }
     }
 
@@ -261,76 +1542,93 @@ impl WebServer {
     ///
     /// # Errors
     /// Returns a 404 if it can't find the file
-    fn parse_get(&mut self, link: &str) -> Message {
-        match link {
-            "/" => Message {
-                variant: Variant::Ok,
-                content: Content::File(String::from("hello.html")),
-            },
-            "/favicon.ico" => Message {
-                variant: Variant::Ok,
-                content: Content::RawBytes(Box::new(include_bytes!("../favicon.ico").to_owned())),
+    fn parse_get(&self, request: &Request) -> Message {
+        match request.path.as_str() {
+            "/" => self.static_files.serve("hello.html", request),
+            "/favicon.ico" => Message::new(
+                Variant::Ok,
+                Content::RawBytes(Box::new(include_bytes!("../favicon.ico").to_owned())),
+            )
+            .with_header("Content-Type", "image/x-icon"),
+            "/whoami" => match self.authenticate(request) {
+                Ok(identity) => Message::new(
+                    Variant::Ok,
+                    Content::Struct(serde_json::to_string(&identity).unwrap_or_default()),
+                ),
+                Err(e) => Self::auth_error(&e),
             },
             "/available-servers" => {
-                let servers: Vec<&str> =
-                    self.hostable_servers.iter().map(|s| s.get_path()).collect();
-                Message {
-                    variant: Variant::Ok,
-                    content: Content::Struct(serde_json::to_string(&servers).unwrap_or_default()),
-                }
+                // only the servers the caller may see, nothing before logging in
+                let identity = self.authenticate(request);
+                let servers: Vec<&str> = self
+                    .hostable_servers
+                    .iter()
+                    .map(|s| s.path.as_str())
+                    .filter(|path| self.may_view(&identity, path))
+                    .collect();
+                Message::new(
+                    Variant::Ok,
+                    Content::Struct(serde_json::to_string(&servers).unwrap_or_default()),
+                )
             }
             link => {
-                let mut link_split = link.split('/');
-                let _ = link_split.next(); // the link starts with '/'
+                let mut link_split = request.segments();
 
                 let first_domain = link_split.next().unwrap_or("Unavailable");
 
                 if first_domain == "file" {
-                    link_split.next().map_or_else(
-                        || {
-                            Message::new(
-                                Variant::NotFound,
-                                Content::Text(format!("File Not Found: {link}")),
-                            )
-                        },
-                        |file_path| match fs::read_to_string(file_path) {
-                            Ok(file_text) => Message::new(Variant::Ok, Content::File(file_text)),
-                            Err(e) => Message::new(
-                                Variant::InternalServerError,
-                                Content::Text(e.to_string()),
-                            ),
-                        },
-                    )
-                } else if let Some(hostable_server) = self
-                    .hostable_servers
-                    .iter_mut()
-                    .find(|s| s.get_path() == first_domain)
-                {
+                    let file_path: Vec<&str> = link_split.collect();
+                    self.static_files.serve(&file_path.join("/"), request)
+                } else if let Some(hostable_server) = self.find_server(first_domain) {
                     let second_domain = link_split.next().unwrap_or("Unavailable");
+                    if let Some(list) = PlayerList::from_name(second_domain) {
+                        return self.player_list(request, hostable_server, list);
+                    }
 
                     match second_domain {
                         "get_status" => {
-                            match hostable_server.update_status() {
+                            if let Err(message) =
+                                self.authorize_view(request, &hostable_server.path)
+                            {
+                                return message;
+                            }
+                            let status = {
+                                let mut hostable_server = hostable_server.lock();
+                                hostable_server
+                                    .update_status()
+                                    .map(|()| hostable_server.to_json())
+                            };
+                            match status {
                                 // succesfull update now send the message :)
-                                Ok(()) => {
-                                    let json = hostable_server.to_json();
-                                    match json {
-                                        Ok(ok) => Message::new(Variant::Ok, Content::Struct(ok)),
-                                        Err(e) => Message::internal_server_error(e.to_string()),
-                                    }
-                                }
+                                Ok(Ok(ok)) => Message::new(Variant::Ok, Content::Struct(ok)),
+                                Ok(Err(e)) => Message::internal_server_error(e.to_string()),
+                                Err(e) => Self::command_failure(&e),
+                            }
+                        }
+                        "crashes" => {
+                            if let Err(message) =
+                                self.authorize_view(request, &hostable_server.path)
+                            {
+                                return message;
+                            }
+                            let watchdog = serde_json::to_string(&*hostable_server.lock_watchdog());
+                            match watchdog {
+                                Ok(ok) => Message::new(Variant::Ok, Content::Struct(ok)),
                                 Err(e) => Message::internal_server_error(e.to_string()),
                             }
                         }
-                        "update.js" => Message::new(
-                            Variant::Ok,
-                            Content::File(format!("{first_domain}/update.js")),
-                        ),
+                        "missions" | "mods" => {
+                            self.selection(request, hostable_server, second_domain)
+                        }
+                        "properties" => self.properties(request, hostable_server),
+                        "update.js" => self
+                            .static_files
+                            .serve(&format!("{first_domain}/update.js"), request),
                         e => {
                             println!("Link not accesible: {e}");
                             Message::new(
                                 Variant::NotFound,
-                                Content::Text(format!("Unkown GET link: {link}",)),
+                                Content::Text(format!("Unkown GET link: {link}")),
                             )
                         }
                     }
@@ -339,7 +1637,7 @@ impl WebServer {
 
                     Message::new(
                         Variant::NotFound,
-                        Content::Text(format!("Unkown GET link: {link}",)),
+                        Content::Text(format!("Unkown GET link: {link}")),
                     )
                 }
             }
@@ -399,25 +1697,152 @@ impl WebServer {
     /// Creates a thread that monitors the activity of the server
     ///
     /// Everytime the server gets a request it should send `()` to the Sender to let it know it is still alive.
-    /// After `allowed_idle_time` the server will attempt to shutdown by calling the `shutdown()` function
-    fn create_timeout_thread(allowed_idle_time: Duration) -> (Sender<()>, JoinHandle<()>) {
+    /// After `allowed_idle_time` the server will attempt to shutdown by calling the `shutdown()` function.
+    /// Without an `allowed_idle_time` no thread is started and the messages go nowhere
+    fn create_timeout_thread(
+        allowed_idle_time: Option<Duration>,
+    ) -> (Sender<()>, Option<JoinHandle<()>>) {
         let (tx, rx) = mpsc::channel();
-        let timer_thread: JoinHandle<()> = thread::spawn(move || {
-            loop {
-                match rx.recv_timeout(allowed_idle_time) {
-                    Ok(()) => {}
-                    Err(_) => {
-                        Self::shutdown();
+        let timer_thread = allowed_idle_time.map(|allowed_idle_time| {
+            thread::spawn(move || {
+                loop {
+                    match rx.recv_timeout(allowed_idle_time) {
+                        Ok(()) => {}
+                        Err(_) => {
+                            Self::shutdown();
+                        }
                     }
                 }
-            }
 
-            #[allow(unreachable_code)]
-            // Just a fail safe
-            {
-                unreachable!("This would shut down the timer thread")
-            }
+                #[allow(unreachable_code)]
+                // Just a fail safe
+                {
+                    unreachable!("This would shut down the timer thread")
+                }
+            })
         });
         (tx, timer_thread)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::WebServer;
+    use crate::{
+        auth::sha256_hex,
+        config::Config,
+        http::{
+            request::{Limits, Request},
+            Message, Variant,
+        },
+    };
+    use std::fs;
+
+    /// Answers `GET path` like a connection would get it
+    fn get(web_server: &WebServer, path: &str) -> Message {
+        answer(web_server, "GET", path, "")
+    }
+
+    /// Answers `method path` with the extra `header` line like a connection would get it
+    fn answer(web_server: &WebServer, method: &str, path: &str, header: &str) -> Message {
+        let raw = format!("{method} {path} HTTP/1.1\r\n{header}\r\n\r\n");
+        let request = Request::read_from(&mut raw.as_bytes(), Limits::default()).unwrap();
+        web_server.parse_http_request(&request)
+    }
+
+    /// Web server with the `bash` servers `minecraft` and `arma`, where the token `friend` may
+    /// only look at and start `minecraft`
+    fn with_friend() -> WebServer {
+        let directory = std::env::temp_dir().join("web_server_lib_friend");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(directory.join("static")).unwrap();
+        for path in ["minecraft", "arma"] {
+            fs::create_dir_all(directory.join(path)).unwrap();
+            fs::write(directory.join(path).join("start.sh"), "").unwrap();
+            fs::write(directory.join(path).join("stop.sh"), "").unwrap();
+        }
+        let text = format!(
+            "listen = [\"127.0.0.1:31415\"]\n\
+             [[servers]]\nkind = \"bash\"\npath = \"minecraft\"\n\
+             [[servers]]\nkind = \"bash\"\npath = \"arma\"\n\
+             [[auth.tokens]]\nuser = \"friend\"\nsha256 = \"{}\"\n\
+             [auth.roles.friends]\nminecraft = [\"view\", \"start\"]\n\
+             [auth.users]\nfriend = [\"friends\"]",
+            sha256_hex("friend")
+        );
+        let config = Config::parse(&text, &directory.join("web_server.toml"), &directory).unwrap();
+        WebServer::from_parsed_config(&config)
+    }
+
+    #[test]
+    fn the_config_isnt_served() {
+        let web_server = WebServer::from_config("web_server.toml").unwrap();
+
+        assert_eq!(get(&web_server, "/").variant, Variant::Ok);
+        assert_eq!(
+            get(&web_server, "/minecraft/update.js").variant,
+            Variant::Ok
+        );
+        assert_eq!(
+            get(&web_server, "/file/web_server.toml").variant,
+            Variant::NotFound
+        );
+        assert_eq!(
+            get(&web_server, "/file/../web_server.toml").variant,
+            Variant::Forbidden
+        );
+        assert_eq!(
+            get(&web_server, "/file/minecraft/start.sh").variant,
+            Variant::NotFound
+        );
+        assert_eq!(
+            get(&web_server, "/file/minecraft%2F..%2F..%2Fweb_server.toml").variant,
+            Variant::Forbidden
+        );
+        assert_eq!(
+            get(&web_server, "/minecraft%2Fupdate.js").variant,
+            Variant::NotFound
+        );
+    }
+
+    #[test]
+    fn properties_need_a_login() {
+        let web_server = WebServer::from_config("web_server.toml").unwrap();
+        assert_eq!(
+            get(&web_server, "/minecraft/properties").variant,
+            Variant::Forbidden
+        );
+        assert_eq!(
+            get(&web_server, "/minecraft/get_status").variant,
+            Variant::Ok,
+            "looking is fine without auth"
+        );
+    }
+
+    #[test]
+    fn friends_only_reach_their_servers() {
+        let web_server = with_friend();
+        let friend = "Authorization: Bearer friend";
+
+        let answer = |method, path| answer(&web_server, method, path, friend).variant;
+        assert_eq!(answer("GET", "/minecraft/get_status"), Variant::Ok);
+        assert_eq!(answer("GET", "/arma/get_status"), Variant::Forbidden);
+        assert_eq!(answer("POST", "/arma/start"), Variant::Forbidden);
+        assert_eq!(answer("POST", "/minecraft/stop"), Variant::Forbidden);
+    }
+
+    #[test]
+    fn available_servers_are_the_visible_ones() {
+        let web_server = with_friend();
+
+        let servers = answer(
+            &web_server,
+            "GET",
+            "/available-servers",
+            "Authorization: Bearer friend",
+        );
+        assert_eq!(servers.variant, Variant::Ok);
+        assert_eq!(servers.body, br#"["minecraft"]"#);
+        assert_eq!(get(&web_server, "/available-servers").body, b"[]");
+    }
+}
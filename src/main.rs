@@ -2,16 +2,19 @@
 //! Rust Game Hosting Server - main.rs
 //! -------------------------------------------------------------
 //! STATUS: Project is in limbo and may not work on newer Rust versions.
-//! This file starts the web server and adds hostable game servers.
+//! This file parses the command line and runs the requested command,
+//! by default the web server described by the config file.
 //! =============================================================
 
-use web_server::{self, hostable_servers::GeneralBashServer};
+use std::process::ExitCode;
+use web_server::cli::{Cli, Exit, USAGE};
 
-fn main() {
-    let mut web_server = web_server::WebServer::new();
-
-    web_server.add_hostable_server(Box::new(GeneralBashServer::new("minecraft")));
-    web_server.add_hostable_server(Box::new(GeneralBashServer::new("arma")));
-
-    web_server.start("192.168.11.69", 31415);
+fn main() -> ExitCode {
+    match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli.run().into(),
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            Exit::Usage.into()
+        }
+    }
 }
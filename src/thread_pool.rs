@@ -0,0 +1,267 @@
+//! =============================================================
+//! Rust Game Hosting Server - `thread_pool.rs`
+//!
+//! STATUS: Project is in limbo and may not work on newer Rust versions.
+//! A fixed size pool of worker threads fed through a bounded queue, and a limit
+//! for the threads of long lived connections
+//! =============================================================
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// Fixed number of threads that all run the same handler on queued items
+///
+/// The queue is bounded, so a flood of connections can't eat all the memory,
+/// [`ThreadPool::try_execute`] hands the item back when it is full.
+pub struct ThreadPool<T: Send + 'static> {
+    /// Sending half of the queue, `None` once the pool is shutting down
+    sender: Option<SyncSender<T>>,
+    /// Worker threads
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> ThreadPool<T> {
+    /// Spawns `workers` threads that call `handler` for every queued item
+    ///
+    /// At most `queue_capacity` items wait for a free worker. A panicking
+    /// `handler` is reported on stderr and the worker keeps going.
+    ///
+    /// # Panics
+    /// Panics if `workers` is zero
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::mpsc;
+    /// use web_server::thread_pool::ThreadPool;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let pool = ThreadPool::new(2, 4, move |n: u32| tx.send(n * 2).unwrap());
+    ///
+    /// pool.try_execute(21).unwrap();
+    /// assert_eq!(rx.recv().unwrap(), 42);
+    /// ```
+    pub fn new<F>(workers: usize, queue_capacity: usize, handler: F) -> Self
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        assert!(workers > 0, "A thread pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::sync_channel(queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let handler = Arc::new(handler);
+
+        let workers = (0..workers)
+            .map(|id| {
+                let receiver = Arc::clone(&receiver);
+                let handler = Arc::clone(&handler);
+                thread::Builder::new()
+                    .name(format!("worker-{id}"))
+                    .spawn(move || Self::work(&receiver, &*handler))
+                    .expect("Couldn't spawn a worker thread")
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Queues `item` for the next free worker
+    ///
+    /// # Errors
+    /// Returns the `item` back if the queue is full
+    pub fn try_execute(&self, item: T) -> Result<(), T> {
+        let Some(sender) = &self.sender else {
+            return Err(item);
+        };
+        sender.try_send(item).map_err(|e| match e {
+            TrySendError::Full(item) | TrySendError::Disconnected(item) => item,
+        })
+    }
+
+    /// Loop run by every worker until the queue is closed
+    fn work(receiver: &Mutex<Receiver<T>>, handler: &(dyn Fn(T) + Send + Sync)) {
+        loop {
+            // the lock is only held while waiting, not while handling
+            let item = receiver
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .recv();
+            let Ok(item) = item else {
+                return;
+            };
+
+            if panic::catch_unwind(AssertUnwindSafe(|| handler(item))).is_err() {
+                eprintln!(
+                    "\x1b[31m{} panicked while handling a job\x1b[39m",
+                    thread::current().name().unwrap_or("worker")
+                );
+            }
+        }
+    }
+}
+
+impl<T: Send + 'static> Drop for ThreadPool<T> {
+    /// Lets the workers finish the queued items and waits for them
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Limits how many threads of one kind run at once, like those of open WebSockets
+///
+/// Those stay open for hours, so they get their own thread instead of a worker
+/// of the [`ThreadPool`], but not without a bound
+pub struct Slots {
+    /// Slots taken right now
+    taken: Arc<AtomicUsize>,
+    /// Slots there are
+    capacity: usize,
+}
+
+impl Slots {
+    /// Creates `capacity` free slots
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            taken: Arc::new(AtomicUsize::new(0)),
+            capacity,
+        }
+    }
+
+    /// Takes a slot until the returned [`Slot`] is dropped, `None` if all are taken
+    ///
+    /// # Example
+    /// ```
+    /// use web_server::thread_pool::Slots;
+    ///
+    /// let slots = Slots::new(1);
+    /// let slot = slots.try_take().unwrap();
+    /// assert!(slots.try_take().is_none());
+    ///
+    /// drop(slot);
+    /// assert!(slots.try_take().is_some());
+    /// ```
+    #[must_use]
+    pub fn try_take(&self) -> Option<Slot> {
+        self.taken
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |taken| {
+                (taken < self.capacity).then_some(taken + 1)
+            })
+            .ok()?;
+        Some(Slot {
+            taken: Arc::clone(&self.taken),
+        })
+    }
+
+    /// Returns the number of slots taken right now
+    #[must_use]
+    pub fn taken(&self) -> usize {
+        self.taken.load(Ordering::SeqCst)
+    }
+}
+
+/// A slot of [`Slots`], given back when it is dropped
+pub struct Slot {
+    /// Counter of the [`Slots`] it came from
+    taken: Arc<AtomicUsize>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.taken.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Slots, ThreadPool};
+    use std::{
+        sync::{mpsc, Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn full_queues_hand_the_item_back() {
+        let (started, on_start) = mpsc::channel();
+        let (release, gate) = mpsc::channel::<()>();
+        let gate = Mutex::new(gate);
+        let pool = ThreadPool::new(1, 1, move |n: u32| {
+            started.send(n).unwrap();
+            gate.lock().unwrap().recv().unwrap();
+        });
+
+        pool.try_execute(1).unwrap();
+        assert_eq!(on_start.recv().unwrap(), 1, "the worker is busy now");
+        pool.try_execute(2).unwrap();
+        assert_eq!(pool.try_execute(3), Err(3));
+
+        release.send(()).unwrap();
+        assert_eq!(on_start.recv().unwrap(), 2);
+        pool.try_execute(3).unwrap();
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        assert_eq!(on_start.recv().unwrap(), 3);
+    }
+
+    #[test]
+    fn panics_dont_take_the_worker_down() {
+        let (done, results) = mpsc::channel();
+        let pool = ThreadPool::new(1, 4, move |n: u32| {
+            assert!(n != 0, "job 0 panics");
+            done.send(n).unwrap();
+        });
+
+        pool.try_execute(0).unwrap();
+        pool.try_execute(1).unwrap();
+        pool.try_execute(0).unwrap();
+        pool.try_execute(2).unwrap();
+        assert_eq!(results.recv().unwrap(), 1);
+        assert_eq!(results.recv().unwrap(), 2);
+    }
+
+    #[test]
+    fn dropping_finishes_the_queue() {
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let pool = {
+            let handled = Arc::clone(&handled);
+            ThreadPool::new(1, 8, move |n: u32| {
+                thread::sleep(Duration::from_millis(10));
+                handled.lock().unwrap().push(n);
+            })
+        };
+        for n in 0..5 {
+            pool.try_execute(n).unwrap();
+        }
+        drop(pool);
+        assert_eq!(*handled.lock().unwrap(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn slots_come_back_from_other_threads() {
+        let slots = Slots::new(2);
+        let first = slots.try_take().unwrap();
+        let second = slots.try_take().unwrap();
+        assert!(slots.try_take().is_none());
+
+        thread::spawn(move || drop(second)).join().unwrap();
+        let third = slots.try_take().unwrap();
+        assert!(slots.try_take().is_none());
+        assert_eq!(slots.taken(), 2);
+        drop((first, third));
+        assert_eq!(slots.taken(), 0);
+        assert!(slots.try_take().is_some());
+    }
+}
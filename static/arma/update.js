@@ -6,22 +6,45 @@ async function stop_arma() {
     sendPost('/arma/stop');
     setTimeout(update_arma, 1000);
 }
+async function cancel_arma() {
+    sendPost('/arma/cancel');
+    setTimeout(update_arma, 1000);
+}
 var arma_global_state = false;
-async function update_arma(force_update = false) {
+// arma_status is passed along by the /events stream, the buttons fetch it
+async function update_arma(force_update = false, arma_status = null) {
     if (!force_update && !arma_global_state) {
         return;
     }
 
-    const arma_status_response = await getUpdate('/arma/get_status');
-
-    const arma_status = JSON.parse(arma_status_response);
+    if (arma_status == null) {
+        const arma_status_response = await getUpdate('/arma/get_status');
+        arma_status = JSON.parse(arma_status_response);
+    }
 
     console.log(arma_status);
 
-    var arma_new_status = "Status: " + arma_status.state;
-    if (arma_status.state == "On") {
+    var arma_new_status = "Status: " + arma_status.state
+        + " since " + new Date(arma_status.since).toLocaleTimeString();
+    const arma_failed_probes = arma_status.probes.filter(probe => !probe.ok);
+    if (arma_failed_probes.length > 0) {
+        arma_new_status += ", Failing: " + arma_failed_probes.map(probe => probe.probe + " (" + probe.detail + ")").join(", ");
+    }
+    if (arma_status.state == "Starting" || arma_status.state == "Stopping" || arma_status.state == "Unhealthy") {
+        // keep polling until it settles
+        arma_global_state = true;
+    } else if (arma_status.state == "Running") {
         arma_global_state = true;
         arma_new_status += ", Player count: " + arma_status.players.count;
+        if (arma_status.slots > 0) {
+            arma_new_status += "/" + arma_status.slots;
+        }
+        if (arma_status.mission != null) {
+            arma_new_status += ", Mission: " + arma_status.mission;
+        }
+        if (arma_status.map != null) {
+            arma_new_status += " on " + arma_status.map;
+        }
         if (arma_status.players.name_tags.length > 0) {
             arma_new_status += ", Player nametags: " + arma_status.players.name_tags;
         }
@@ -34,7 +57,7 @@ async function update_arma(force_update = false) {
     arma_status_div.innerHTML = arma_new_status;
 }
 
-document.body.innerHTML += '<section id="arma-section"><h2>Arma Control</h2><button id="start-arma" onclick="start_arma()">Start</button><button id="stop-arma" onclick="stop_arma()">Stop</button><button id="update-arma" onclick="update_arma(true)">Update</button><div id="arma-status">Status: Off</div></section>'
+document.body.innerHTML += '<section id="arma-section"><h2>Arma Control</h2><button id="start-arma" onclick="start_arma()">Start</button><button id="stop-arma" onclick="stop_arma()">Stop</button><button id="cancel-arma" onclick="cancel_arma()">Cancel</button><button id="update-arma" onclick="update_arma(true)">Update</button><div id="arma-status">Status: Off</div></section>'
 
 // =============================================================
 // Rust Game Hosting Server - arma/update.js
@@ -0,0 +1,103 @@
+async function start_mc() {
+    sendPost('/minecraft/start');
+    setTimeout(update_minecraft, 1000);
+}
+async function stop_mc() {
+    sendPost('/minecraft/stop');
+    setTimeout(update_minecraft, 1000);
+}
+async function cancel_mc() {
+    sendPost('/minecraft/cancel');
+    setTimeout(update_minecraft, 1000);
+}
+var mc_global_state = false;
+// mc_status is passed along by the /events stream, the buttons fetch it
+async function update_minecraft(force_update = false, mc_status = null) {
+    if (!force_update && !mc_global_state) {
+        return;
+    }
+
+    if (mc_status == null) {
+        const mc_status_response = await getUpdate('/minecraft/get_status');
+        mc_status = JSON.parse(mc_status_response);
+    }
+
+    console.log(mc_status);
+
+    var mc_new_status = "Status: " + mc_status.state
+        + " since " + new Date(mc_status.since).toLocaleTimeString();
+    const mc_failed_probes = mc_status.probes.filter(probe => !probe.ok);
+    if (mc_failed_probes.length > 0) {
+        mc_new_status += ", Failing: " + mc_failed_probes.map(probe => probe.probe + " (" + probe.detail + ")").join(", ");
+    }
+    if (mc_status.state == "Starting" || mc_status.state == "Stopping" || mc_status.state == "Unhealthy") {
+        // keep polling until it settles
+        mc_global_state = true;
+    } else if (mc_status.state == "Running") {
+        mc_global_state = true;
+        mc_new_status += ", Player count: " + mc_status.players.count;
+        if (mc_status.players.name_tags.length > 0) {
+            mc_new_status += ", Player nametags: " + mc_status.players.name_tags;
+        }
+    } else {
+        mc_global_state = false;
+    }
+    if (mc_status.restart_required) {
+        mc_new_status += " - server.properties changed, restart to apply it";
+    }
+
+    const minecraft_status_div = document
+        .getElementById("minecraft-status");
+    minecraft_status_div.innerHTML = mc_new_status;
+}
+
+// server.properties as it was loaded, to only send what was changed
+var mc_properties = {};
+function escape_mc_html(text) {
+    return text.replaceAll('&', '&amp;').replaceAll('"', '&quot;').replaceAll('<', '&lt;');
+}
+async function load_mc_properties() {
+    mc_properties = JSON.parse(await getUpdate('/minecraft/properties'));
+
+    var mc_rows = "";
+    for (const key in mc_properties) {
+        mc_rows += '<tr><td><label for="mc-property-' + key + '">' + key + '</label></td>'
+            + '<td><input id="mc-property-' + key + '" value="' + escape_mc_html(mc_properties[key]) + '"></td></tr>';
+    }
+    document.getElementById("minecraft-properties").innerHTML = '<p>Changes apply after a restart.</p><table>'
+        + mc_rows + '</table><button onclick="save_mc_properties()">Save</button>';
+}
+async function save_mc_properties() {
+    var mc_changes = {};
+    for (const key in mc_properties) {
+        const value = document.getElementById("mc-property-" + key).value;
+        if (value != mc_properties[key]) {
+            mc_changes[key] = value;
+        }
+    }
+
+    const response = await fetch(server_addr + '/minecraft/properties', {
+        method: 'PUT',
+        body: JSON.stringify(mc_changes),
+    });
+    if (response.ok) {
+        alert(await response.text());
+        load_mc_properties();
+        update_minecraft(true);
+    } else if (response.status == 401 || response.status == 403) {
+        showLogin("Not allowed");
+    } else {
+        // 409 while the server is starting or stopping
+        const failure = await response.json().catch(() => ({ message: response.statusText }));
+        alert(failure.message);
+    }
+}
+
+document.body.innerHTML += '<section id="minecraft-section"><h2>Minecraft Control</h2><button id="start-mc" onclick="start_mc()">Start</button><button id="stop-mc" onclick="stop_mc()">Stop</button><button id="cancel-mc" onclick="cancel_mc()">Cancel</button><button id="update-mc" onclick="update_minecraft(true)">Update</button><div id="minecraft-status">Status: Off</div><details ontoggle="if (this.open) load_mc_properties()"><summary>server.properties</summary><div id="minecraft-properties"></div></details></section>'
+
+// =============================================================
+// Rust Game Hosting Server - minecraft/update.js
+// -------------------------------------------------------------
+// STATUS: Project is in limbo and may not work on newer Rust versions.
+// This script updates the Minecraft server status in the web interface.
+// =============================================================